@@ -1,28 +1,84 @@
-//! OpenRouter API integration for LLM-powered content generation.
+//! LLM provider integration for content generation.
 //!
-//! This module handles all communication with the OpenRouter API, including:
+//! This module handles all communication with the configured LLM backend, including:
 //!
+//! - **Providers**: OpenRouter, OpenAI-compatible servers, Anthropic, and local
+//!   Ollama / llama.cpp servers behind [`Provider`]
+//! - **Reliability**: Timeouts, retries with backoff, and model fallback via [`ApiClient`]
 //! - **Streaming responses**: Real-time token-by-token output
-//! - **Prompt construction**: Building prompts for commit messages and PRs
+//! - **Prompt construction**: Rendering prompt templates for commit messages and PRs
 //! - **Response parsing**: Handling both streaming and non-streaming responses
+//! - **Usage tracking**: Token counts and cost collected per request
+//! - **Fixtures**: Recording responses to disk and replaying them offline
+//!
+//! # Fixtures
+//!
+//! With `record_fixtures` enabled, each request of an invocation is saved to
+//! `fixtures_dir` as `001.request.json` with its raw response in
+//! `001.response`, then `002.*`, and so on. The `replay` provider serves
+//! those responses back in the same order without touching the network,
+//! parsing them as OpenAI-style SSE or JSON. Fixtures can also be written by
+//! hand, which makes end-to-end tests of the commit and branch flows possible.
 //!
 //! # Key Functions
 //!
+//! - [`ApiClient::new`]: Build the client, provider, and retry policy from [`Config`]
 //! - [`stream_commit_message`]: Generate a commit message with streaming output
 //! - [`stream_pr_content`]: Generate PR title and body with streaming output
+//! - [`send_chat`]: Run a non-streaming completion and return its text
 //! - [`build_prompt`]: Construct the commit message prompt
 //! - [`build_pr_prompt`]: Construct the PR generation prompt
 
+use futures::stream::{self, LocalBoxStream};
 use futures::StreamExt;
 use indicatif::ProgressBar;
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
 use serde::{Deserialize, Serialize};
 use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config::{Config, ProviderKind};
+use crate::prompt::render;
+use crate::sse::{EventDecoder, StreamFormat};
+use crate::usage::{Ledger, RequestUsage, Usage};
+
+/// OpenRouter API base URL.
+pub const OPENROUTER_BASE_URL: &str = "https://openrouter.ai/api/v1";
+
+/// Anthropic API base URL.
+pub const ANTHROPIC_BASE_URL: &str = "https://api.anthropic.com/v1";
+
+/// Default base URL of a local Ollama server.
+pub const OLLAMA_BASE_URL: &str = "http://localhost:11434";
+
+/// Default base URL of a local llama.cpp server.
+pub const LLAMACPP_BASE_URL: &str = "http://localhost:8080/v1";
+
+/// Anthropic API version sent with every request.
+const ANTHROPIC_VERSION: &str = "2023-06-01";
 
-/// OpenRouter API endpoint for chat completions.
-pub const OPENROUTER_API_URL: &str = "https://openrouter.ai/api/v1/chat/completions";
+/// Output token limit for Anthropic requests, which require one.
+const ANTHROPIC_MAX_TOKENS: u32 = 4096;
 
-/// Request body for OpenRouter chat completions API.
+/// HTTP statuses worth retrying: timeouts, rate limits, and gateway errors.
+const RETRYABLE_STATUSES: &[u16] = &[408, 429, 500, 502, 503, 504];
+
+/// Most retries per model, whatever `max_retries` is set to.
+pub const MAX_RETRIES: u32 = 10;
+
+/// Delay before the first retry; doubles on each later attempt.
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// Upper bound on a single retry delay, including server `Retry-After` values.
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Request body for OpenAI-style chat completions APIs.
+///
+/// This is also the provider-neutral request shape; backends with a different
+/// wire format translate it in [`Provider::request`].
 #[derive(Serialize)]
 pub struct ChatRequest {
     pub model: String,
@@ -30,6 +86,151 @@ pub struct ChatRequest {
     pub stream: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub provider: Option<ProviderPreference>,
+    /// Structured output constraint, for providers that support one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    /// Sampling seed, for providers that support one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u32>,
+}
+
+impl ChatRequest {
+    /// Creates a request containing a single user message.
+    pub fn user(model: &str, prompt: String, stream: bool) -> Self {
+        Self {
+            model: model.to_string(),
+            messages: vec![Message {
+                role: "user".to_string(),
+                content: prompt,
+            }],
+            stream,
+            provider: None,
+            response_format: None,
+            temperature: None,
+            seed: None,
+        }
+    }
+
+    /// Appends follow-up turns and applies sampling overrides.
+    pub fn refine(mut self, refinement: Refinement<'_>) -> Self {
+        self.messages.extend_from_slice(refinement.followups);
+        if let Some(sampling) = refinement.sampling {
+            self.temperature = Some(sampling.temperature);
+            self.seed = Some(sampling.seed);
+        }
+        self
+    }
+}
+
+/// Temperature for regenerated replies, high enough that they differ from
+/// the last one.
+const REGENERATE_TEMPERATURE: f32 = 1.0;
+
+/// Sampling overrides for a regenerated reply.
+#[derive(Clone, Copy)]
+pub struct Sampling {
+    pub temperature: f32,
+    pub seed: u32,
+}
+
+impl Sampling {
+    /// A fresh seed and a raised temperature.
+    pub fn varied() -> Self {
+        // Small enough for providers that take 32-bit seeds
+        let seed = std::time::SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.subsec_nanos())
+            .unwrap_or_default();
+        Self {
+            temperature: REGENERATE_TEMPERATURE,
+            seed,
+        }
+    }
+}
+
+/// How to redo a previous generation.
+#[derive(Clone, Copy, Default)]
+pub struct Refinement<'a> {
+    /// Turns after the original prompt: earlier replies and the user's
+    /// feedback on them, ending with a user turn.
+    pub followups: &'a [Message],
+    /// Set when regenerating.
+    pub sampling: Option<Sampling>,
+}
+
+/// Records feedback on `previous` as follow-up turns for the next request.
+pub fn push_feedback(followups: &mut Vec<Message>, previous: &str, instruction: &str) {
+    followups.push(Message {
+        role: "assistant".to_string(),
+        content: previous.to_string(),
+    });
+    followups.push(Message {
+        role: "user".to_string(),
+        content: format!(
+            "Revise it: {}\n\nKeep the same format and respond with ONLY the revised text.",
+            instruction.trim()
+        ),
+    });
+}
+
+/// OpenAI-style `response_format` requesting output matching a JSON schema.
+///
+/// Ollama receives the schema as its `format` field; Anthropic ignores it.
+#[derive(Serialize)]
+pub struct ResponseFormat {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    json_schema: JsonSchema,
+}
+
+#[derive(Serialize)]
+struct JsonSchema {
+    name: &'static str,
+    strict: bool,
+    schema: serde_json::Value,
+}
+
+impl ResponseFormat {
+    /// Requires the reply to be a JSON document matching `schema`.
+    pub fn json_schema(name: &'static str, schema: serde_json::Value) -> Self {
+        Self {
+            kind: "json_schema",
+            json_schema: JsonSchema {
+                name,
+                strict: true,
+                schema,
+            },
+        }
+    }
+}
+
+/// OpenRouter request body: a [`ChatRequest`] plus usage accounting.
+#[derive(Serialize)]
+struct OpenRouterRequest<'a> {
+    #[serde(flatten)]
+    request: &'a ChatRequest,
+    usage: UsageOption,
+}
+
+/// Asks OpenRouter to report token counts and cost.
+#[derive(Serialize)]
+struct UsageOption {
+    include: bool,
+}
+
+/// OpenAI-style request body with usage reporting for streams.
+#[derive(Serialize)]
+struct StreamOptionsRequest<'a> {
+    #[serde(flatten)]
+    request: &'a ChatRequest,
+    stream_options: StreamOptions,
+}
+
+#[derive(Serialize)]
+struct StreamOptions {
+    include_usage: bool,
 }
 
 /// Provider ordering preferences for OpenRouter.
@@ -39,16 +240,26 @@ pub struct ProviderPreference {
 }
 
 /// A single message in the chat conversation.
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 pub struct Message {
     pub role: String,
     pub content: String,
 }
 
 /// A chunk from the streaming response.
+///
+/// OpenRouter reports mid-stream failures as a chunk with `error` and no choices.
 #[derive(Deserialize)]
 pub struct StreamChunk {
+    #[serde(default)]
     pub choices: Vec<Choice>,
+    pub error: Option<ApiError>,
+}
+
+/// Error object embedded in a response body.
+#[derive(Deserialize)]
+pub struct ApiError {
+    pub message: String,
 }
 
 /// A single choice in a streaming chunk.
@@ -75,423 +286,1838 @@ pub struct NonStreamMessage {
     pub content: String,
 }
 
-/// Complete non-streaming response from OpenRouter.
+/// Complete non-streaming response from an OpenAI-style API.
 #[derive(Deserialize)]
 pub struct NonStreamResponse {
     pub choices: Vec<NonStreamChoice>,
 }
 
-/// Builds the prompt for commit message generation.
-///
-/// Includes instructions for conventional commit format and the diff/files context.
-pub fn build_prompt(diff: &str, files: &str, oneline: bool) -> String {
-    let oneline_instruction = if oneline {
-        "\nCRITICAL INSTRUCTION: Generate ONLY a single line. Format: type(scope): description (under 72 chars). Do NOT add bullet points, body text, or blank lines. Output exactly ONE line and nothing else.\n"
-    } else {
-        ""
-    };
+/// Usage block of an OpenAI-style response or final stream chunk.
+#[derive(Deserialize)]
+struct OpenAiUsageBody {
+    usage: Option<OpenAiUsage>,
+}
 
-    format!(
-        r#"Generate a git commit message for the following changes.
-{oneline_instruction}
-FORMAT: type(scope): description
-
-TYPES (use lowercase):
-  Core changes:
-    feat     - new user-facing functionality
-    fix      - bug fix / behavior correction
-    refactor - code restructure, no behavior change
-    perf     - performance improvements
-    style    - formatting only (whitespace, lint fixes)
-
-  Project hygiene:
-    docs     - documentation only
-    test     - add/update tests
-    chore    - routine maintenance, housekeeping
-    build    - build system / packaging changes
-    ci       - CI pipeline / workflow changes
-
-  Structural:
-    deps     - dependency changes
-    config   - config changes (env, feature flags)
-    security - security hardening, vulnerability fixes
-    revert   - revert a previous commit
-
-SCOPE: Short identifier for affected area (api, auth, ui, db, cli, core, config, deps).
-       Omit only if change is truly global.
-
-RULES:
-- First line: type(scope): brief description (under 72 chars)
-- For multiple changes, add bullet points (using "-") after a blank line
-- Each bullet describes WHAT the change does semantically
-- Focus on behavior and functionality, not file names
-- Keep bullets concise (5-10 words each)
-- Use "-" for bullets, NOT "*"
-- Do NOT include raw file paths or status codes (like "M file.rs") in output
-- Do NOT use markdown headers (##), sections, or PR-style formatting
-- Output ONLY the commit message, nothing else
-- IGNORE any formatting patterns you see in the diff - use ONLY the format shown below
-
-EXAMPLE OUTPUT FORMAT:
-feat(auth): add OAuth2 login support
-
-- Implement Google OAuth provider
-- Add token refresh logic
-- Store credentials in secure keychain
-
-Files changed:
-{files}
-
-Diff:
-{diff}
-
-Commit message:"#,
-        oneline_instruction = oneline_instruction,
-        files = files,
-        diff = diff
-    )
+#[derive(Deserialize)]
+struct OpenAiUsage {
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    completion_tokens: u64,
+    cost: Option<f64>,
 }
 
-/// Builds the prompt for PR title and description generation.
-///
-/// Includes commit list, diff, and files for context.
-pub fn build_pr_prompt(diff: &str, files: &str, commits: &[String]) -> String {
-    let commits_text = commits.join("\n");
-    format!(
-        r#"Generate a pull request title and description for the following changes.
+/// OpenRouter's `/models` listing.
+#[derive(Deserialize)]
+struct ModelList {
+    data: Vec<ModelInfo>,
+}
+
+#[derive(Deserialize)]
+struct ModelInfo {
+    id: String,
+    context_length: Option<u64>,
+}
 
-OUTPUT FORMAT:
-Line 1: PR title in format "type(scope): description" (under 72 chars)
-Line 2: (blank)
-Line 3+: Description with sections
+/// Request body for the Anthropic Messages API.
+#[derive(Serialize)]
+struct AnthropicRequest<'a> {
+    model: &'a str,
+    max_tokens: u32,
+    messages: &'a [Message],
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+}
 
-DESCRIPTION FORMAT (omit empty sections):
+/// A streamed Anthropic event. Only text deltas carry content.
+#[derive(Deserialize)]
+struct AnthropicEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    delta: Option<AnthropicDelta>,
+    error: Option<ApiError>,
+}
 
-## Summary
-One or two sentences describing what this PR does and why.
+/// Usage as reported by Anthropic: at the top level of complete responses and
+/// `message_delta` events, or under `message` in `message_start`.
+#[derive(Deserialize)]
+struct AnthropicUsageBody {
+    usage: Option<AnthropicUsage>,
+    message: Option<Box<AnthropicUsageBody>>,
+}
 
-## Changes
-### Added
-- new features or functionality
+#[derive(Deserialize)]
+struct AnthropicUsage {
+    #[serde(default)]
+    input_tokens: u64,
+    #[serde(default)]
+    output_tokens: u64,
+}
 
-### Fixed
-- bug fixes
+#[derive(Deserialize)]
+struct AnthropicDelta {
+    text: Option<String>,
+}
 
-### Changed
-- modifications to existing behavior
+/// Complete non-streaming response from the Anthropic Messages API.
+#[derive(Deserialize)]
+struct AnthropicResponse {
+    content: Vec<AnthropicContent>,
+}
 
-## Notes
-- implementation details, caveats, or edge cases
-- breaking changes or migration steps
-- anything reviewers should pay attention to
+#[derive(Deserialize)]
+struct AnthropicContent {
+    text: Option<String>,
+}
 
-## Testing
-- what was tested and how
-- specific scenarios verified
-- commands run or manual steps taken
+/// Request body for Ollama's native `/api/chat` endpoint.
+#[derive(Serialize)]
+struct OllamaRequest<'a> {
+    model: &'a str,
+    messages: &'a [Message],
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<&'a serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    options: Option<OllamaOptions>,
+}
 
-RULES:
-- Title follows conventional commit format: type(scope): description
-- Summary should explain the "what" and "why" concisely
-- Each bullet should be concise (5-15 words)
-- Focus on behavior changes, not file names
-- Use past tense ("Added", "Fixed", "Updated")
-- Omit empty subsections (e.g., skip Fixed section if no fixes)
+/// Sampling options for Ollama, which takes them outside the message body.
+#[derive(Serialize)]
+struct OllamaOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u32>,
+}
 
-COMMITS ON THIS BRANCH:
-{commits}
+/// A streamed line or complete response from Ollama's `/api/chat`.
+#[derive(Deserialize)]
+struct OllamaResponse {
+    message: Option<OllamaMessage>,
+    #[serde(default)]
+    done: bool,
+}
 
-FILES CHANGED:
-{files}
+#[derive(Deserialize)]
+struct OllamaMessage {
+    content: String,
+}
 
-DIFF:
-{diff}
+/// Token counts on Ollama's final stream line or complete response.
+#[derive(Deserialize)]
+struct OllamaUsage {
+    prompt_eval_count: Option<u64>,
+    eval_count: Option<u64>,
+}
 
-PR title and description:"#,
-        commits = commits_text,
-        files = files,
-        diff = diff
-    )
+/// A decoded server-sent event payload.
+pub enum StreamEvent {
+    /// Generated text to append to the message.
+    Content(String),
+    /// The provider signalled the end of the stream.
+    Done,
+    /// A payload with no text (role headers, pings, metadata).
+    Skip,
+    /// The provider reported an error mid-stream.
+    Error(String),
 }
 
-/// Streams PR title and body generation from the LLM.
+/// A chat completion backend.
 ///
-/// Returns (title, body) tuple. Output is printed token-by-token as it streams.
-#[allow(clippy::too_many_arguments)]
-pub async fn stream_pr_content(
-    client: &Client,
-    api_key: &str,
-    model: &str,
-    diff: &str,
-    files: &str,
-    commits: &[String],
-    spinner: &ProgressBar,
-    _verbose: bool,
-) -> Result<(String, String), Box<dyn std::error::Error>> {
-    let prompt = build_pr_prompt(diff, files, commits);
-
-    let request = ChatRequest {
-        model: model.to_string(),
-        messages: vec![Message {
-            role: "user".to_string(),
-            content: prompt,
-        }],
-        stream: true,
-        provider: None,
-    };
+/// Implementations translate a [`ChatRequest`] into the backend's wire format
+/// and decode its responses; transport, streaming, and output are shared.
+pub trait Provider {
+    /// Short identifier used in logs and error messages.
+    fn name(&self) -> &'static str;
 
-    let response = client
-        .post(OPENROUTER_API_URL)
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .header("X-Title", "Committer")
-        .header("HTTP-Referer", "https://github.com/Nolanneff/commiter")
-        .json(&request)
-        .send()
-        .await?;
-
-    if !response.status().is_success() {
-        let status = response.status();
-        let body = response.text().await.unwrap_or_default();
-        spinner.disable_steady_tick();
-        spinner.finish_and_clear();
-        return Err(format!("API error ({}): {}", status, body).into());
+    /// The URL requests are sent under.
+    fn base_url(&self) -> &str;
+
+    /// Builds the HTTP request, including endpoint, headers, and body.
+    fn request(&self, client: &Client, request: &ChatRequest) -> RequestBuilder;
+
+    /// Framing of streamed responses. Defaults to server-sent events.
+    fn stream_format(&self) -> StreamFormat {
+        StreamFormat::Sse
     }
 
-    let mut stream = response.bytes_stream();
-    let mut full_message = String::new();
-    let mut stdout = io::stdout();
-    let mut first_chunk = true;
-    let mut raw_response = String::new();
+    /// Decodes the payload of a single streamed event.
+    fn parse_stream_data(&self, data: &str) -> Result<StreamEvent, serde_json::Error>;
 
-    'outer: while let Some(chunk_result) = stream.next().await {
-        let chunk = chunk_result?;
-        let text = String::from_utf8_lossy(&chunk);
-        raw_response.push_str(&text);
+    /// Extracts the message text from a complete non-streaming response body.
+    fn parse_response(&self, body: &str) -> Result<String, serde_json::Error>;
 
-        for line in text.lines() {
-            if let Some(data) = line.strip_prefix("data: ") {
-                if data == "[DONE]" {
-                    break 'outer;
-                }
+    /// Extracts token usage from a stream event payload or complete response
+    /// body, if it reports any.
+    fn parse_usage(&self, data: &str) -> Option<Usage>;
 
-                if let Ok(parsed) = serde_json::from_str::<StreamChunk>(data) {
-                    for choice in parsed.choices {
-                        if let Some(content) = choice.delta.content {
-                            if first_chunk {
-                                spinner.disable_steady_tick();
-                                spinner.finish_and_clear();
-                                println!();
-                                first_chunk = false;
-                            }
-                            print!("{}", content);
-                            stdout.flush()?;
-                            full_message.push_str(&content);
-                        }
-                    }
-                }
-            }
-        }
+    /// Builds a request for the provider's model list, if it publishes
+    /// context windows. Defaults to none.
+    fn models_request(&self, _client: &Client) -> Option<RequestBuilder> {
+        None
     }
 
-    // Fallback to non-streaming if needed
-    if full_message.is_empty() && !raw_response.is_empty() {
-        spinner.disable_steady_tick();
-        spinner.finish_and_clear();
+    /// Extracts model identifiers and context windows from a model list.
+    fn parse_models(&self, _body: &str) -> Result<Vec<(String, u64)>, serde_json::Error> {
+        Ok(Vec::new())
+    }
+}
 
-        if let Ok(parsed) = serde_json::from_str::<NonStreamResponse>(&raw_response) {
-            if let Some(choice) = parsed.choices.first() {
-                full_message = choice.message.content.clone();
-                println!("{}", full_message);
-            }
-        }
-    } else if !first_chunk {
-        println!();
+fn endpoint(base_url: &str, path: &str) -> String {
+    format!("{}/{}", base_url.trim_end_matches('/'), path)
+}
+
+fn parse_openai_stream_data(data: &str) -> Result<StreamEvent, serde_json::Error> {
+    if data == "[DONE]" {
+        return Ok(StreamEvent::Done);
+    }
+    let parsed: StreamChunk = serde_json::from_str(data)?;
+    if let Some(error) = parsed.error {
+        return Ok(StreamEvent::Error(error.message));
+    }
+    let content: String = parsed
+        .choices
+        .into_iter()
+        .filter_map(|choice| choice.delta.content)
+        .collect();
+    if content.is_empty() {
+        Ok(StreamEvent::Skip)
     } else {
-        spinner.disable_steady_tick();
-        spinner.finish_and_clear();
+        Ok(StreamEvent::Content(content))
     }
+}
 
-    // Parse title and body from response
-    let content = full_message.trim();
-    let mut lines = content.lines();
-    let title = lines.next().unwrap_or("").trim().to_string();
+fn parse_openai_usage(data: &str) -> Option<Usage> {
+    let usage = serde_json::from_str::<OpenAiUsageBody>(data).ok()?.usage?;
+    Some(Usage {
+        prompt_tokens: usage.prompt_tokens,
+        completion_tokens: usage.completion_tokens,
+        cost: usage.cost,
+    })
+}
 
-    // Skip blank line after title
-    lines.next();
+fn parse_openai_response(body: &str) -> Result<String, serde_json::Error> {
+    let parsed: NonStreamResponse = serde_json::from_str(body)?;
+    Ok(parsed
+        .choices
+        .into_iter()
+        .next()
+        .map(|choice| choice.message.content)
+        .unwrap_or_default())
+}
 
-    let body: String = lines.collect::<Vec<_>>().join("\n").trim().to_string();
+/// OpenRouter, the default provider.
+pub struct OpenRouter {
+    api_key: String,
+    base_url: String,
+}
 
-    if title.is_empty() {
-        return Err("Failed to generate PR title".into());
+impl Provider for OpenRouter {
+    fn name(&self) -> &'static str {
+        "openrouter"
     }
 
-    Ok((title, body))
-}
+    fn base_url(&self) -> &str {
+        &self.base_url
+    }
 
-/// Options for commit-message generation.
-pub struct CommitMessageOptions {
-    pub verbose: bool,
-    pub oneline: bool,
+    fn request(&self, client: &Client, request: &ChatRequest) -> RequestBuilder {
+        client
+            .post(endpoint(&self.base_url, "chat/completions"))
+            .bearer_auth(&self.api_key)
+            .header("X-Title", "Committer")
+            .header("HTTP-Referer", "https://github.com/nolanneff/committer")
+            .json(&OpenRouterRequest {
+                request,
+                usage: UsageOption { include: true },
+            })
+    }
+
+    fn parse_stream_data(&self, data: &str) -> Result<StreamEvent, serde_json::Error> {
+        parse_openai_stream_data(data)
+    }
+
+    fn parse_response(&self, body: &str) -> Result<String, serde_json::Error> {
+        parse_openai_response(body)
+    }
+
+    fn parse_usage(&self, data: &str) -> Option<Usage> {
+        parse_openai_usage(data)
+    }
+
+    fn models_request(&self, client: &Client) -> Option<RequestBuilder> {
+        Some(
+            client
+                .get(endpoint(&self.base_url, "models"))
+                .bearer_auth(&self.api_key),
+        )
+    }
+
+    fn parse_models(&self, body: &str) -> Result<Vec<(String, u64)>, serde_json::Error> {
+        let parsed: ModelList = serde_json::from_str(body)?;
+        Ok(parsed
+            .data
+            .into_iter()
+            .filter_map(|model| Some((model.id, model.context_length?)))
+            .collect())
+    }
 }
 
-/// Streams commit message generation from the LLM.
+/// Any server implementing the OpenAI chat completions API at `base_url`.
 ///
-/// Output is printed token-by-token as it streams. Falls back to non-streaming
-/// parsing if the response doesn't use SSE format.
-pub async fn stream_commit_message(
-    client: &Client,
-    api_key: &str,
-    model: &str,
-    diff: &str,
-    files: &str,
-    spinner: &ProgressBar,
-    options: CommitMessageOptions,
-) -> Result<String, Box<dyn std::error::Error>> {
-    let CommitMessageOptions { verbose, oneline } = options;
-    let prompt = build_prompt(diff, files, oneline);
-
-    let request = ChatRequest {
-        model: model.to_string(),
-        messages: vec![Message {
-            role: "user".to_string(),
-            content: prompt,
-        }],
-        stream: true,
-        provider: None,
-    };
+/// Also used for llama.cpp's server, which usually runs without a key.
+pub struct OpenAiCompatible {
+    name: &'static str,
+    api_key: Option<String>,
+    base_url: String,
+    /// Send `stream_options.include_usage`, which some servers reject.
+    stream_usage: bool,
+}
 
-    let response = client
-        .post(OPENROUTER_API_URL)
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .header("X-Title", "Committer")
-        .header("HTTP-Referer", "https://github.com/nolanneff/committer")
-        .json(&request)
-        .send()
-        .await?;
-
-    if !response.status().is_success() {
-        let status = response.status();
-        let body = response.text().await.unwrap_or_default();
-        spinner.disable_steady_tick();
-        spinner.finish_and_clear();
-        return Err(format!("API error ({}): {}", status, body).into());
+impl Provider for OpenAiCompatible {
+    fn name(&self) -> &'static str {
+        self.name
     }
 
-    if verbose {
-        eprintln!("[Stream] Starting to read response stream...");
+    fn base_url(&self) -> &str {
+        &self.base_url
     }
 
-    let mut stream = response.bytes_stream();
-    let mut full_message = String::new();
-    let mut stdout = io::stdout();
-    let mut first_chunk = true;
-    let mut raw_response = String::new();
-    let mut chunk_count = 0;
-    let mut sse_lines_found = 0;
+    fn request(&self, client: &Client, request: &ChatRequest) -> RequestBuilder {
+        let builder = client.post(endpoint(&self.base_url, "chat/completions"));
+        let builder = match &self.api_key {
+            Some(key) => builder.bearer_auth(key),
+            None => builder,
+        };
+        if self.stream_usage && request.stream {
+            builder.json(&StreamOptionsRequest {
+                request,
+                stream_options: StreamOptions {
+                    include_usage: true,
+                },
+            })
+        } else {
+            builder.json(request)
+        }
+    }
 
-    'outer: while let Some(chunk_result) = stream.next().await {
-        let chunk = chunk_result?;
-        let text = String::from_utf8_lossy(&chunk);
-        raw_response.push_str(&text);
-        chunk_count += 1;
+    fn parse_stream_data(&self, data: &str) -> Result<StreamEvent, serde_json::Error> {
+        parse_openai_stream_data(data)
+    }
 
-        if verbose {
-            eprintln!(
-                "[Stream] Chunk {}: {} bytes, preview: {:?}",
-                chunk_count,
-                chunk.len(),
-                text.chars().take(100).collect::<String>()
-            );
-        }
+    fn parse_response(&self, body: &str) -> Result<String, serde_json::Error> {
+        parse_openai_response(body)
+    }
 
-        // SSE format: each line starts with "data: "
-        for line in text.lines() {
-            if let Some(data) = line.strip_prefix("data: ") {
-                sse_lines_found += 1;
-                if data == "[DONE]" {
-                    if verbose {
-                        eprintln!("[Stream] Received [DONE] signal");
-                    }
-                    break 'outer;
-                }
+    fn parse_usage(&self, data: &str) -> Option<Usage> {
+        parse_openai_usage(data)
+    }
+}
 
-                match serde_json::from_str::<StreamChunk>(data) {
-                    Ok(parsed) => {
-                        for choice in parsed.choices {
-                            if let Some(content) = choice.delta.content {
-                                if first_chunk {
-                                    if verbose {
-                                        eprintln!("[Stream] First content chunk, clearing spinner");
-                                    }
-                                    spinner.disable_steady_tick();
-                                    spinner.finish_and_clear();
-                                    println!(); // Ensure clean line after spinner
-                                    first_chunk = false;
-                                }
-                                print!("{}", content);
-                                stdout.flush()?;
-                                full_message.push_str(&content);
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        if verbose {
-                            eprintln!(
-                                "[Stream] Parse error: {} for data: {:?}",
-                                e,
-                                data.chars().take(100).collect::<String>()
-                            );
-                        }
-                    }
-                }
-            }
-        }
+/// Anthropic's native Messages API.
+pub struct Anthropic {
+    api_key: String,
+    base_url: String,
+}
+
+impl Provider for Anthropic {
+    fn name(&self) -> &'static str {
+        "anthropic"
     }
 
-    if verbose {
-        eprintln!(
-            "[Stream] Stream ended. Total chunks: {}, SSE lines: {}, message length: {}",
-            chunk_count,
-            sse_lines_found,
-            full_message.len()
-        );
+    fn base_url(&self) -> &str {
+        &self.base_url
     }
 
-    // Fallback: if streaming produced nothing, try parsing as non-streaming response
-    if full_message.is_empty() && !raw_response.is_empty() {
-        if verbose {
-            eprintln!("[Stream] No streaming content, trying non-streaming fallback...");
-            eprintln!(
-                "[Stream] Raw response preview: {:?}",
-                &raw_response.chars().take(300).collect::<String>()
-            );
+    fn request(&self, client: &Client, request: &ChatRequest) -> RequestBuilder {
+        client
+            .post(endpoint(&self.base_url, "messages"))
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .json(&AnthropicRequest {
+                model: &request.model,
+                max_tokens: ANTHROPIC_MAX_TOKENS,
+                messages: &request.messages,
+                stream: request.stream,
+                temperature: request.temperature,
+            })
+    }
+
+    fn parse_stream_data(&self, data: &str) -> Result<StreamEvent, serde_json::Error> {
+        let event: AnthropicEvent = serde_json::from_str(data)?;
+        match event.kind.as_str() {
+            "content_block_delta" => Ok(event
+                .delta
+                .and_then(|delta| delta.text)
+                .map(StreamEvent::Content)
+                .unwrap_or(StreamEvent::Skip)),
+            "message_stop" => Ok(StreamEvent::Done),
+            "error" => Ok(StreamEvent::Error(
+                event
+                    .error
+                    .map(|e| e.message)
+                    .unwrap_or_else(|| data.to_string()),
+            )),
+            _ => Ok(StreamEvent::Skip),
         }
+    }
 
-        spinner.disable_steady_tick();
-        spinner.finish_and_clear();
+    fn parse_response(&self, body: &str) -> Result<String, serde_json::Error> {
+        let parsed: AnthropicResponse = serde_json::from_str(body)?;
+        Ok(parsed
+            .content
+            .into_iter()
+            .filter_map(|block| block.text)
+            .collect())
+    }
 
-        // Try parsing as a complete non-streaming response
-        if let Ok(parsed) = serde_json::from_str::<NonStreamResponse>(&raw_response) {
-            if let Some(choice) = parsed.choices.first() {
-                full_message = choice.message.content.clone();
-                println!("{}", full_message);
-                if verbose {
-                    eprintln!("[Stream] Fallback succeeded");
-                }
+    fn parse_usage(&self, data: &str) -> Option<Usage> {
+        let body: AnthropicUsageBody = serde_json::from_str(data).ok()?;
+        let usage = body.usage.or_else(|| body.message?.usage)?;
+        Some(Usage {
+            prompt_tokens: usage.input_tokens,
+            completion_tokens: usage.output_tokens,
+            cost: None,
+        })
+    }
+}
+
+/// A local Ollama server using its native `/api/chat` endpoint.
+///
+/// Streams newline-delimited JSON rather than server-sent events.
+pub struct Ollama {
+    api_key: Option<String>,
+    base_url: String,
+}
+
+impl Provider for Ollama {
+    fn name(&self) -> &'static str {
+        "ollama"
+    }
+
+    fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    fn request(&self, client: &Client, request: &ChatRequest) -> RequestBuilder {
+        let builder = client.post(endpoint(&self.base_url, "api/chat"));
+        let builder = match &self.api_key {
+            Some(key) => builder.bearer_auth(key),
+            None => builder,
+        };
+        builder.json(&OllamaRequest {
+            model: &request.model,
+            messages: &request.messages,
+            stream: request.stream,
+            format: request
+                .response_format
+                .as_ref()
+                .map(|format| &format.json_schema.schema),
+            options: (request.temperature.is_some() || request.seed.is_some()).then_some(
+                OllamaOptions {
+                    temperature: request.temperature,
+                    seed: request.seed,
+                },
+            ),
+        })
+    }
+
+    fn stream_format(&self) -> StreamFormat {
+        StreamFormat::JsonLines
+    }
+
+    fn parse_stream_data(&self, data: &str) -> Result<StreamEvent, serde_json::Error> {
+        let parsed: OllamaResponse = serde_json::from_str(data)?;
+        match parsed.message {
+            Some(message) if !message.content.is_empty() => {
+                Ok(StreamEvent::Content(message.content))
             }
-        } else if verbose {
-            eprintln!("[Stream] Fallback parse failed");
+            _ if parsed.done => Ok(StreamEvent::Done),
+            _ => Ok(StreamEvent::Skip),
         }
-    } else if !first_chunk {
-        // Only print newline if we actually printed content
-        println!();
-    } else {
-        // Spinner still running but no content - clear it
-        spinner.disable_steady_tick();
-        spinner.finish_and_clear();
     }
 
-    Ok(full_message.trim().to_string())
+    fn parse_response(&self, body: &str) -> Result<String, serde_json::Error> {
+        let parsed: OllamaResponse = serde_json::from_str(body)?;
+        Ok(parsed.message.map(|m| m.content).unwrap_or_default())
+    }
+
+    fn parse_usage(&self, data: &str) -> Option<Usage> {
+        let usage: OllamaUsage = serde_json::from_str(data).ok()?;
+        if usage.prompt_eval_count.is_none() && usage.eval_count.is_none() {
+            return None;
+        }
+        Some(Usage {
+            prompt_tokens: usage.prompt_eval_count.unwrap_or(0),
+            completion_tokens: usage.eval_count.unwrap_or(0),
+            cost: None,
+        })
+    }
+}
+
+/// Creates the provider selected by `config.provider`.
+///
+/// `api_key` may be `None` only for providers where
+/// [`ProviderKind::requires_api_key`] is false. An empty `base_url` counts
+/// as unset. Fails if the `openai` provider is selected without a `base_url`.
+pub fn create_provider(
+    config: &Config,
+    api_key: Option<String>,
+) -> Result<Box<dyn Provider>, Box<dyn std::error::Error>> {
+    let base_url = config.base_url.clone().filter(|url| !url.trim().is_empty());
+    let required_key = || {
+        api_key.clone().ok_or_else(|| {
+            format!(
+                "No API key found for the {} provider; set {}",
+                config.provider,
+                config.provider.api_key_env()
+            )
+        })
+    };
+    Ok(match config.provider {
+        ProviderKind::OpenRouter => Box::new(OpenRouter {
+            api_key: required_key()?,
+            base_url: base_url.unwrap_or_else(|| OPENROUTER_BASE_URL.to_string()),
+        }),
+        ProviderKind::OpenAi => Box::new(OpenAiCompatible {
+            name: "openai",
+            api_key: Some(required_key()?),
+            base_url: base_url
+                .ok_or("The openai provider requires base_url to be set in the config")?,
+            stream_usage: true,
+        }),
+        ProviderKind::Anthropic => Box::new(Anthropic {
+            api_key: required_key()?,
+            base_url: base_url.unwrap_or_else(|| ANTHROPIC_BASE_URL.to_string()),
+        }),
+        ProviderKind::Ollama => Box::new(Ollama {
+            api_key,
+            base_url: base_url.unwrap_or_else(|| OLLAMA_BASE_URL.to_string()),
+        }),
+        // Responses come from fixture files; only the parser is used
+        ProviderKind::Replay => Box::new(OpenAiCompatible {
+            name: "replay",
+            api_key: None,
+            base_url: String::new(),
+            stream_usage: false,
+        }),
+        ProviderKind::LlamaCpp => Box::new(OpenAiCompatible {
+            name: "llamacpp",
+            api_key,
+            base_url: base_url.unwrap_or_else(|| LLAMACPP_BASE_URL.to_string()),
+            // llama.cpp reports usage on the final chunk without being asked
+            stream_usage: false,
+        }),
+    })
+}
+
+/// Why a request failed, and whether another model could succeed.
+enum SendError {
+    /// The model failed; the next fallback model may still work.
+    Model(Box<dyn std::error::Error>),
+    /// The server rejected the request itself (400), as many
+    /// OpenAI-compatible servers do for an unsupported `response_format`.
+    BadRequest(Box<dyn std::error::Error>),
+    /// No model will work (e.g. authentication failed).
+    Fatal(Box<dyn std::error::Error>),
+}
+
+/// A successful response body, from the network or a fixture file.
+enum ApiResponse {
+    Http(Response),
+    Fixture(Vec<u8>),
+}
+
+impl ApiResponse {
+    fn into_stream(self) -> LocalBoxStream<'static, Result<Vec<u8>, reqwest::Error>> {
+        match self {
+            Self::Http(response) => response
+                .bytes_stream()
+                .map(|chunk| chunk.map(|bytes| bytes.to_vec()))
+                .boxed_local(),
+            Self::Fixture(body) => stream::once(async move { Ok(body) }).boxed_local(),
+        }
+    }
+
+    async fn text(self) -> Result<String, reqwest::Error> {
+        match self {
+            Self::Http(response) => response.text().await,
+            Self::Fixture(body) => Ok(String::from_utf8_lossy(&body).to_string()),
+        }
+    }
+}
+
+/// Numbered request/response files for recording or replay.
+struct Fixtures {
+    dir: PathBuf,
+    record: bool,
+    next: AtomicUsize,
+}
+
+impl Fixtures {
+    fn new(dir: &Path, record: bool) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            record,
+            next: AtomicUsize::new(1),
+        }
+    }
+
+    /// Paths of the request and response files for the next request.
+    fn next_paths(&self) -> (PathBuf, PathBuf) {
+        let index = self.next.fetch_add(1, Ordering::SeqCst);
+        (
+            self.dir.join(format!("{:03}.request.json", index)),
+            self.dir.join(format!("{:03}.response", index)),
+        )
+    }
+
+    fn replay(&self) -> Result<ApiResponse, Box<dyn std::error::Error>> {
+        let (_, response_path) = self.next_paths();
+        let body = std::fs::read(&response_path)
+            .map_err(|e| format!("No replay fixture at {}: {}", response_path.display(), e))?;
+        Ok(ApiResponse::Fixture(body))
+    }
+
+    /// Reads the whole response and saves it alongside the request.
+    async fn record(
+        &self,
+        request: &ChatRequest,
+        response: Response,
+    ) -> Result<ApiResponse, Box<dyn std::error::Error>> {
+        let (request_path, response_path) = self.next_paths();
+        let body = response.bytes().await?.to_vec();
+
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(&request_path, serde_json::to_string_pretty(request)?)?;
+        std::fs::write(&response_path, &body)?;
+        Ok(ApiResponse::Fixture(body))
+    }
+}
+
+/// HTTP client, provider, and retry policy shared by every API call.
+pub struct ApiClient {
+    http: Client,
+    provider: Box<dyn Provider>,
+    max_retries: u32,
+    fallback_models: Vec<String>,
+    verbose: bool,
+    usage: Mutex<Vec<RequestUsage>>,
+    ledger: Option<Ledger>,
+    fixtures: Option<Fixtures>,
+}
+
+impl ApiClient {
+    /// Creates a client for the configured provider with its timeouts and
+    /// fallback models.
+    pub fn new(
+        config: &Config,
+        api_key: Option<String>,
+        verbose: bool,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let http = Client::builder()
+            .connect_timeout(Duration::from_secs(config.connect_timeout_secs))
+            .read_timeout(Duration::from_secs(config.read_timeout_secs))
+            .build()?;
+
+        let fixtures = match &config.fixtures_dir {
+            Some(dir) if config.provider == ProviderKind::Replay => Some(Fixtures::new(dir, false)),
+            Some(dir) if config.record_fixtures => Some(Fixtures::new(dir, true)),
+            None if config.provider == ProviderKind::Replay => {
+                return Err("The replay provider requires fixtures_dir to be set".into())
+            }
+            None if config.record_fixtures => {
+                return Err("record_fixtures requires fixtures_dir to be set".into())
+            }
+            _ => None,
+        };
+
+        Ok(Self {
+            http,
+            provider: create_provider(config, api_key)?,
+            max_retries: config.max_retries.min(MAX_RETRIES),
+            fallback_models: config.fallback_models.clone(),
+            verbose,
+            usage: Mutex::new(Vec::new()),
+            ledger: None,
+            fixtures,
+        })
+    }
+
+    /// Records each request's usage in `ledger` as it completes.
+    pub fn with_ledger(self, ledger: Ledger) -> Self {
+        Self {
+            ledger: Some(ledger),
+            ..self
+        }
+    }
+
+    /// The provider requests are sent to.
+    pub fn provider(&self) -> &dyn Provider {
+        self.provider.as_ref()
+    }
+
+    /// Fetches context windows from the provider's model list.
+    ///
+    /// Returns `None` when replaying fixtures or when the provider has no
+    /// model list.
+    pub async fn fetch_context_windows(
+        &self,
+    ) -> Result<Option<Vec<(String, u64)>>, Box<dyn std::error::Error>> {
+        if self.fixtures.as_ref().is_some_and(|f| !f.record) {
+            return Ok(None);
+        }
+        let Some(request) = self.provider.models_request(&self.http) else {
+            return Ok(None);
+        };
+
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(format!("model list request failed ({})", response.status()).into());
+        }
+        let body = response.text().await?;
+        Ok(Some(self.provider.parse_models(&body)?))
+    }
+
+    /// Returns usage for every request made so far, clearing the record.
+    pub fn take_usage(&self) -> Vec<RequestUsage> {
+        std::mem::take(&mut *self.usage.lock().unwrap())
+    }
+
+    fn record_usage(&self, model: &str, usage: Usage) {
+        if self.verbose {
+            eprintln!(
+                "— Usage ({}): {} prompt + {} completion tokens{}",
+                model,
+                usage.prompt_tokens,
+                usage.completion_tokens,
+                usage
+                    .cost
+                    .map(|cost| format!(", ${:.6}", cost))
+                    .unwrap_or_default()
+            );
+        }
+        let record = RequestUsage {
+            provider: self.provider.name().to_string(),
+            model: model.to_string(),
+            usage,
+        };
+        if let Some(ledger) = &self.ledger {
+            ledger.record(&record);
+        }
+        self.usage.lock().unwrap().push(record);
+    }
+
+    /// Sends a request, or serves it from fixtures when replaying.
+    ///
+    /// On success, `request.model` is the model that answered.
+    async fn send(
+        &self,
+        request: &mut ChatRequest,
+    ) -> Result<ApiResponse, Box<dyn std::error::Error>> {
+        match &self.fixtures {
+            Some(fixtures) if !fixtures.record => fixtures.replay(),
+            Some(fixtures) => {
+                let response = self.send_http(request).await?;
+                fixtures.record(request, response).await
+            }
+            None => Ok(ApiResponse::Http(self.send_http(request).await?)),
+        }
+    }
+
+    /// Sends a request, retrying transient failures and then moving through
+    /// `fallback_models` in order.
+    async fn send_http(
+        &self,
+        request: &mut ChatRequest,
+    ) -> Result<Response, Box<dyn std::error::Error>> {
+        let models = model_order(&request.model, &self.fallback_models);
+
+        let mut last_error = None;
+        for (index, model) in models.into_iter().enumerate() {
+            if index > 0 && self.verbose {
+                eprintln!("— Falling back to model {}", model);
+            }
+            request.model = model;
+
+            let mut result = self.send_with_retries(request).await;
+            if matches!(result, Err(SendError::BadRequest(_))) && request.response_format.is_some()
+            {
+                // Replies are parsed leniently, so plain text still works
+                if self.verbose {
+                    eprintln!("— Structured output rejected; retrying without it");
+                }
+                request.response_format = None;
+                result = self.send_with_retries(request).await;
+            }
+
+            match result {
+                Ok(response) => return Ok(response),
+                Err(SendError::Fatal(e)) => return Err(e),
+                Err(SendError::Model(e) | SendError::BadRequest(e)) => {
+                    if self.verbose {
+                        eprintln!("— Model {} failed: {}", request.model, e);
+                    }
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| "No model configured".into()))
+    }
+
+    async fn send_with_retries(&self, request: &ChatRequest) -> Result<Response, SendError> {
+        let attempts = self.max_retries + 1;
+
+        for attempt in 1..=attempts {
+            let (error, retry_after) = match self.provider.request(&self.http, request).send().await
+            {
+                Ok(response) if response.status().is_success() => {
+                    if self.verbose {
+                        eprintln!(
+                            "— {} {}: attempt {}/{} succeeded",
+                            self.provider.name(),
+                            request.model,
+                            attempt,
+                            attempts
+                        );
+                    }
+                    return Ok(response);
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    let retry_after = retry_after(&response);
+                    let body = response.text().await.unwrap_or_default();
+                    let error: Box<dyn std::error::Error> =
+                        format!("API error ({}): {}", status, body).into();
+
+                    match status_action(status) {
+                        StatusAction::Abort => return Err(SendError::Fatal(error)),
+                        StatusAction::BadRequest => return Err(SendError::BadRequest(error)),
+                        StatusAction::NextModel => return Err(SendError::Model(error)),
+                        StatusAction::Retry => (error, retry_after),
+                    }
+                }
+                Err(e) if e.is_timeout() || e.is_connect() => (e.into(), None),
+                Err(e) => return Err(SendError::Model(e.into())),
+            };
+
+            if attempt == attempts {
+                return Err(SendError::Model(error));
+            }
+
+            let delay = retry_delay(attempt, retry_after);
+            if self.verbose {
+                eprintln!(
+                    "— Attempt {}/{} failed ({}); retrying in {:.1}s",
+                    attempt,
+                    attempts,
+                    error,
+                    delay.as_secs_f32()
+                );
+            }
+            tokio::time::sleep(delay).await;
+        }
+
+        unreachable!("the final attempt always returns")
+    }
+}
+
+/// Models to try in order: `model`, then each fallback other than it.
+fn model_order(model: &str, fallbacks: &[String]) -> Vec<String> {
+    let mut models = vec![model.to_string()];
+    models.extend(fallbacks.iter().filter(|m| *m != model).cloned());
+    models
+}
+
+/// How to handle a response with an unsuccessful status.
+#[derive(Debug, PartialEq, Eq)]
+enum StatusAction {
+    /// Transient failure: retry the same model after a delay.
+    Retry,
+    /// The request itself was rejected (400).
+    BadRequest,
+    /// Authentication failed, so no model will work.
+    Abort,
+    /// Give up on this model and try the next fallback.
+    NextModel,
+}
+
+fn status_action(status: StatusCode) -> StatusAction {
+    match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => StatusAction::Abort,
+        StatusCode::BAD_REQUEST => StatusAction::BadRequest,
+        _ if RETRYABLE_STATUSES.contains(&status.as_u16()) => StatusAction::Retry,
+        _ => StatusAction::NextModel,
+    }
+}
+
+/// Delay before retrying after failed `attempt` (counting from 1).
+///
+/// Uses the server's `Retry-After` when given, otherwise doubles from
+/// [`RETRY_BASE_DELAY`]; either way at most [`RETRY_MAX_DELAY`].
+fn retry_delay(attempt: u32, retry_after: Option<Duration>) -> Duration {
+    retry_after
+        .unwrap_or_else(|| RETRY_BASE_DELAY.saturating_mul(2u32.saturating_pow(attempt - 1)))
+        .min(RETRY_MAX_DELAY)
+}
+
+/// Reads a response's `Retry-After` header.
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(reqwest::header::RETRY_AFTER)?;
+    parse_retry_after(value.to_str().ok()?, SystemTime::now())
+}
+
+/// Parses a `Retry-After` value: a number of seconds, or an HTTP date
+/// (`Sun, 06 Nov 1994 08:49:37 GMT`) that is turned into the time left
+/// until it from `now`. Dates in the past mean no delay.
+fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = parse_http_date(value)?;
+    Some(date.duration_since(now).unwrap_or(Duration::ZERO))
+}
+
+/// Parses an HTTP date in the IMF-fixdate format, the only one servers
+/// may send.
+fn parse_http_date(value: &str) -> Option<SystemTime> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let [_weekday, day, month, year, time, "GMT"] =
+        value.split_whitespace().collect::<Vec<_>>()[..]
+    else {
+        return None;
+    };
+    let day: i64 = day.parse().ok().filter(|d| (1..=31).contains(d))?;
+    let month = MONTHS.iter().position(|m| *m == month)? as i64 + 1;
+    let year: i64 = year.parse().ok()?;
+    let [hours, minutes, seconds] = time
+        .split(':')
+        .map(|part| part.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?[..]
+    else {
+        return None;
+    };
+    if hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+
+    // Howard Hinnant's days-from-civil algorithm
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = u64::try_from(era * 146_097 + day_of_era - 719_468).ok()?;
+
+    let seconds = days * 86_400 + hours * 3_600 + minutes * 60 + seconds;
+    Some(UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+/// Sends a non-streaming chat request and returns the message text.
+pub async fn send_chat(
+    api: &ApiClient,
+    mut request: ChatRequest,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let response = api.send(&mut request).await?;
+    let provider = api.provider();
+
+    let body = response.text().await?;
+    if let Some(usage) = provider.parse_usage(&body) {
+        api.record_usage(&request.model, usage);
+    }
+    let content = provider.parse_response(&body).map_err(|e| {
+        format!(
+            "Failed to parse {} response: {} - raw: {}",
+            provider.name(),
+            e,
+            body.chars().take(300).collect::<String>()
+        )
+    })?;
+    Ok(content)
+}
+
+/// Single-line instruction substituted for `{oneline}` in the commit template.
+const ONELINE_INSTRUCTION: &str = "\nCRITICAL INSTRUCTION: Generate ONLY a single line. Format: type(scope): description (under 72 chars). Do NOT add bullet points, body text, or blank lines. Output exactly ONE line and nothing else.\n";
+
+/// Builds the prompt for commit message generation from `template`.
+///
+/// `style` is the repository's learned commit style, empty when there is
+/// none (see [`crate::history`]), and `scopes` the scopes allowed for the
+/// staged files (see [`crate::scopes`]). Custom templates without an
+/// `{oneline}`, `{style}`, or `{scopes}` placeholder still get them: they
+/// are prepended instead.
+pub fn build_prompt(
+    template: &str,
+    diff: &str,
+    files: &str,
+    oneline: bool,
+    style: &str,
+    scopes: &[String],
+) -> String {
+    let oneline_instruction = if oneline { ONELINE_INSTRUCTION } else { "" };
+    let scopes_instruction = if scopes.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "\n       Allowed for these changes: {} (use the one the change is mostly in)",
+            scopes.join(", ")
+        )
+    };
+    let mut prompt = render(
+        template,
+        &[
+            ("oneline", oneline_instruction),
+            ("style", style),
+            ("scopes", &scopes_instruction),
+            ("files", files),
+            ("diff", diff),
+        ],
+    );
+
+    if !scopes.is_empty() && !template.contains("{scopes}") {
+        prompt = format!(
+            "SCOPE: one of {} (the one the change is mostly in)\n\n{}",
+            scopes.join(", "),
+            prompt
+        );
+    }
+    if !style.is_empty() && !template.contains("{style}") {
+        prompt = format!("{}\n\n{}", style.trim(), prompt);
+    }
+    if oneline && !template.contains("{oneline}") {
+        prompt = format!("{}\n{}", ONELINE_INSTRUCTION.trim(), prompt);
+    }
+    prompt
+}
+
+/// Builds the prompt for PR title and description generation from `template`.
+pub fn build_pr_prompt(template: &str, diff: &str, files: &str, commits: &[String]) -> String {
+    let commits_text = commits.join("\n");
+    render(
+        template,
+        &[("commits", &commits_text), ("files", files), ("diff", diff)],
+    )
+}
+
+/// Streams a chat completion, printing tokens as they arrive when `echo` is
+/// set.
+///
+/// The spinner is cleared on the first content token. Falls back to parsing
+/// the whole body as a non-streaming response if the provider ignored
+/// `stream: true`.
+async fn stream_chat(
+    api: &ApiClient,
+    mut request: ChatRequest,
+    spinner: &ProgressBar,
+    echo: bool,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let provider = api.provider();
+    let verbose = api.verbose;
+
+    let response = match api.send(&mut request).await {
+        Ok(response) => response,
+        Err(e) => {
+            spinner.disable_steady_tick();
+            spinner.finish_and_clear();
+            return Err(e);
+        }
+    };
+
+    if verbose {
+        eprintln!(
+            "[Stream] Starting to read {} response stream...",
+            provider.name()
+        );
+    }
+
+    let mut stream = response.into_stream();
+    let mut decoder = EventDecoder::new(provider.stream_format());
+    let mut full_message = String::new();
+    let mut stdout = io::stdout();
+    let mut first_chunk = true;
+    let mut raw_response: Vec<u8> = Vec::new();
+    let mut chunk_count = 0;
+    let mut events_found = 0;
+    let mut finished = false;
+    let mut usage: Option<Usage> = None;
+
+    while !finished {
+        let events = match stream.next().await {
+            Some(chunk_result) => {
+                let chunk = chunk_result?;
+                raw_response.extend_from_slice(&chunk);
+                chunk_count += 1;
+
+                if verbose {
+                    eprintln!(
+                        "[Stream] Chunk {}: {} bytes, preview: {:?}",
+                        chunk_count,
+                        chunk.len(),
+                        String::from_utf8_lossy(&chunk)
+                            .chars()
+                            .take(100)
+                            .collect::<String>()
+                    );
+                }
+
+                decoder.push(&chunk)
+            }
+            None => {
+                finished = true;
+                decoder.finish()
+            }
+        };
+
+        for event in events {
+            events_found += 1;
+
+            if let Some(event_usage) = provider.parse_usage(&event.data) {
+                usage.get_or_insert_with(Usage::default).merge(event_usage);
+            }
+
+            // Prefer the provider's error message; fall back to the raw payload
+            let parsed = match provider.parse_stream_data(&event.data) {
+                Ok(StreamEvent::Error(message)) => Ok(StreamEvent::Error(message)),
+                _ if event.event.as_deref() == Some("error") => {
+                    Ok(StreamEvent::Error(event.data.clone()))
+                }
+                other => other,
+            };
+
+            match parsed {
+                Ok(StreamEvent::Content(content)) => {
+                    if first_chunk {
+                        if verbose {
+                            eprintln!("[Stream] First content chunk, clearing spinner");
+                        }
+                        spinner.disable_steady_tick();
+                        spinner.finish_and_clear();
+                        if echo {
+                            println!(); // Ensure clean line after spinner
+                        }
+                        first_chunk = false;
+                    }
+                    if echo {
+                        print!("{}", content);
+                        stdout.flush()?;
+                    }
+                    full_message.push_str(&content);
+                }
+                Ok(StreamEvent::Done) => {
+                    if verbose {
+                        eprintln!("[Stream] Received end-of-stream signal");
+                    }
+                    finished = true;
+                    break;
+                }
+                Ok(StreamEvent::Skip) => {}
+                Ok(StreamEvent::Error(message)) => {
+                    spinner.disable_steady_tick();
+                    spinner.finish_and_clear();
+                    if echo && !first_chunk {
+                        println!();
+                    }
+                    return Err(
+                        format!("API error ({} stream): {}", provider.name(), message).into(),
+                    );
+                }
+                Err(e) => {
+                    if verbose {
+                        eprintln!(
+                            "[Stream] Parse error: {} for data: {:?}",
+                            e,
+                            event.data.chars().take(100).collect::<String>()
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    if verbose {
+        eprintln!(
+            "[Stream] Stream ended. Total chunks: {}, events: {}, message length: {}",
+            chunk_count,
+            events_found,
+            full_message.len()
+        );
+    }
+
+    let raw_response = String::from_utf8_lossy(&raw_response);
+
+    if usage.is_none() && full_message.is_empty() {
+        usage = provider.parse_usage(&raw_response);
+    }
+    if let Some(usage) = usage {
+        api.record_usage(&request.model, usage);
+    }
+
+    // Fallback: if streaming produced nothing, try parsing as non-streaming response
+    if full_message.is_empty() && !raw_response.is_empty() {
+        if verbose {
+            eprintln!("[Stream] No streaming content, trying non-streaming fallback...");
+            eprintln!(
+                "[Stream] Raw response preview: {:?}",
+                &raw_response.chars().take(300).collect::<String>()
+            );
+        }
+
+        spinner.disable_steady_tick();
+        spinner.finish_and_clear();
+
+        match provider.parse_response(&raw_response) {
+            Ok(content) => {
+                full_message = content;
+                if echo {
+                    println!("{}", full_message);
+                }
+                if verbose {
+                    eprintln!("[Stream] Fallback succeeded");
+                }
+            }
+            Err(_) if verbose => eprintln!("[Stream] Fallback parse failed"),
+            Err(_) => {}
+        }
+    } else if !first_chunk {
+        // Only print newline if we actually printed content
+        if echo {
+            println!();
+        }
+    } else {
+        // Spinner still running but no content - clear it
+        spinner.disable_steady_tick();
+        spinner.finish_and_clear();
+    }
+
+    Ok(full_message)
+}
+
+/// Streams PR title and body generation from the LLM.
+///
+/// Returns (title, body) tuple. Output is printed token-by-token as it streams.
+pub async fn stream_pr_content(
+    api: &ApiClient,
+    model: &str,
+    diff: &str,
+    files: &str,
+    spinner: &ProgressBar,
+    options: PrContentOptions<'_>,
+) -> Result<(String, String), Box<dyn std::error::Error>> {
+    let PrContentOptions {
+        template,
+        commits,
+        refinement,
+    } = options;
+    let prompt = build_pr_prompt(template, diff, files, commits);
+    let request = ChatRequest::user(model, prompt, true).refine(refinement);
+
+    let full_message = stream_chat(api, request, spinner, true).await?;
+
+    // Parse title and body from response
+    let content = full_message.trim();
+    let mut lines = content.lines();
+    let title = lines.next().unwrap_or("").trim().to_string();
+
+    // Skip blank line after title
+    lines.next();
+
+    let body: String = lines.collect::<Vec<_>>().join("\n").trim().to_string();
+
+    if title.is_empty() {
+        return Err("Failed to generate PR title".into());
+    }
+
+    Ok((title, body))
+}
+
+/// Options for commit-message generation.
+#[derive(Clone, Copy)]
+pub struct CommitMessageOptions<'a> {
+    pub oneline: bool,
+    /// Prompt template, see [`crate::prompt`].
+    pub template: &'a str,
+    pub refinement: Refinement<'a>,
+    /// Print the message as it streams.
+    pub echo: bool,
+    /// Learned commit style, see [`build_prompt`].
+    pub style: &'a str,
+    /// Scopes allowed for the staged files, see [`build_prompt`].
+    pub scopes: &'a [String],
+}
+
+/// Options for PR content generation.
+#[derive(Clone, Copy)]
+pub struct PrContentOptions<'a> {
+    /// Prompt template, see [`crate::prompt`].
+    pub template: &'a str,
+    pub commits: &'a [String],
+    pub refinement: Refinement<'a>,
+}
+
+/// Streams commit message generation from the LLM.
+///
+/// Output is printed token-by-token as it streams, unless `echo` is off.
+/// Falls back to non-streaming parsing if the response doesn't use SSE format.
+pub async fn stream_commit_message(
+    api: &ApiClient,
+    model: &str,
+    diff: &str,
+    files: &str,
+    spinner: &ProgressBar,
+    options: CommitMessageOptions<'_>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let CommitMessageOptions {
+        oneline,
+        template,
+        refinement,
+        echo,
+        style,
+        scopes,
+    } = options;
+    let prompt = build_prompt(template, diff, files, oneline, style, scopes);
+    let request = ChatRequest::user(model, prompt, true).refine(refinement);
+
+    let full_message = stream_chat(api, request, spinner, echo).await?;
+
+    Ok(full_message.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn provider(kind: ProviderKind, base_url: Option<&str>) -> Box<dyn Provider> {
+        let config = Config {
+            provider: kind,
+            base_url: base_url.map(str::to_string),
+            ..Config::default()
+        };
+        create_provider(&config, Some("secret".to_string())).unwrap()
+    }
+
+    fn build(provider: &dyn Provider, request: &ChatRequest) -> reqwest::Request {
+        provider.request(&Client::new(), request).build().unwrap()
+    }
+
+    fn header<'a>(request: &'a reqwest::Request, name: &str) -> Option<&'a str> {
+        request.headers().get(name).map(|v| v.to_str().unwrap())
+    }
+
+    fn body(request: &reqwest::Request) -> Value {
+        serde_json::from_slice(request.body().unwrap().as_bytes().unwrap()).unwrap()
+    }
+
+    fn sampled(stream: bool) -> ChatRequest {
+        ChatRequest::user("m", "hi".to_string(), stream).refine(Refinement {
+            followups: &[],
+            sampling: Some(Sampling {
+                temperature: 1.0,
+                seed: 7,
+            }),
+        })
+    }
+
+    fn content(event: StreamEvent) -> Option<String> {
+        match event {
+            StreamEvent::Content(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn openrouter_request_asks_for_usage() {
+        let provider = provider(ProviderKind::OpenRouter, None);
+        let request = build(
+            provider.as_ref(),
+            &ChatRequest::user("m", "hi".into(), true),
+        );
+
+        assert_eq!(
+            request.url().as_str(),
+            "https://openrouter.ai/api/v1/chat/completions"
+        );
+        assert_eq!(header(&request, "authorization"), Some("Bearer secret"));
+        assert_eq!(header(&request, "x-title"), Some("Committer"));
+        assert_eq!(
+            body(&request),
+            json!({
+                "model": "m",
+                "messages": [{"role": "user", "content": "hi"}],
+                "stream": true,
+                "usage": {"include": true},
+            })
+        );
+    }
+
+    #[test]
+    fn openrouter_parses_models_with_context_lengths() {
+        let provider = provider(ProviderKind::OpenRouter, None);
+        let list = r#"{"data": [
+            {"id": "a/model", "context_length": 128000},
+            {"id": "b/model", "context_length": null}
+        ]}"#;
+
+        assert_eq!(
+            provider.parse_models(list).unwrap(),
+            vec![("a/model".to_string(), 128_000)]
+        );
+        let request = provider.models_request(&Client::new()).unwrap().build();
+        assert_eq!(
+            request.unwrap().url().as_str(),
+            "https://openrouter.ai/api/v1/models"
+        );
+    }
+
+    #[test]
+    fn openai_parses_stream_chunks() {
+        let provider = provider(ProviderKind::OpenRouter, None);
+
+        let chunk = r#"{"choices": [{"delta": {"content": "feat: "}}]}"#;
+        assert_eq!(
+            content(provider.parse_stream_data(chunk).unwrap()),
+            Some("feat: ".to_string())
+        );
+        let empty = r#"{"choices": [{"delta": {}}]}"#;
+        assert!(matches!(
+            provider.parse_stream_data(empty).unwrap(),
+            StreamEvent::Skip
+        ));
+        assert!(matches!(
+            provider.parse_stream_data("[DONE]").unwrap(),
+            StreamEvent::Done
+        ));
+        let error = r#"{"error": {"message": "overloaded"}}"#;
+        assert!(matches!(
+            provider.parse_stream_data(error).unwrap(),
+            StreamEvent::Error(message) if message == "overloaded"
+        ));
+        assert!(provider.parse_stream_data("not json").is_err());
+    }
+
+    #[test]
+    fn openai_parses_responses_and_usage() {
+        let provider = provider(ProviderKind::OpenRouter, None);
+        let response = r#"{
+            "choices": [{"message": {"content": "fix: typo"}}],
+            "usage": {"prompt_tokens": 12, "completion_tokens": 3, "cost": 0.5}
+        }"#;
+
+        assert_eq!(provider.parse_response(response).unwrap(), "fix: typo");
+        let usage = provider.parse_usage(response).unwrap();
+        assert_eq!((usage.prompt_tokens, usage.completion_tokens), (12, 3));
+        assert_eq!(usage.cost, Some(0.5));
+        assert!(provider
+            .parse_usage(r#"{"choices": [{"delta": {"content": "x"}}]}"#)
+            .is_none());
+        assert!(provider.parse_response(r#"{"error": "nope"}"#).is_err());
+    }
+
+    #[test]
+    fn openai_compatible_request_includes_stream_usage() {
+        let provider = provider(ProviderKind::OpenAi, Some("https://llm.example/v1/"));
+        let request = build(provider.as_ref(), &sampled(true));
+
+        assert_eq!(
+            request.url().as_str(),
+            "https://llm.example/v1/chat/completions"
+        );
+        assert_eq!(header(&request, "authorization"), Some("Bearer secret"));
+        assert_eq!(
+            body(&request),
+            json!({
+                "model": "m",
+                "messages": [{"role": "user", "content": "hi"}],
+                "stream": true,
+                "temperature": 1.0,
+                "seed": 7,
+                "stream_options": {"include_usage": true},
+            })
+        );
+
+        let request = build(
+            provider.as_ref(),
+            &ChatRequest::user("m", "hi".into(), false),
+        );
+        assert!(body(&request).get("stream_options").is_none());
+    }
+
+    #[test]
+    fn openai_provider_requires_base_url() {
+        let config = Config {
+            provider: ProviderKind::OpenAi,
+            ..Config::default()
+        };
+        let error = create_provider(&config, Some("secret".into()))
+            .err()
+            .unwrap();
+
+        assert!(error.to_string().contains("requires base_url"));
+        let empty = Config {
+            base_url: Some(String::new()),
+            ..config
+        };
+        assert!(create_provider(&empty, Some("secret".into())).is_err());
+    }
+
+    #[test]
+    fn treats_an_empty_base_url_as_unset() {
+        let provider = provider(ProviderKind::Ollama, Some(" "));
+
+        assert_eq!(provider.base_url(), OLLAMA_BASE_URL);
+    }
+
+    #[test]
+    fn llamacpp_request_needs_no_key() {
+        let config = Config {
+            provider: ProviderKind::LlamaCpp,
+            ..Config::default()
+        };
+        let provider = create_provider(&config, None).unwrap();
+        let mut chat = ChatRequest::user("m", "hi".into(), true);
+        chat.response_format = Some(ResponseFormat::json_schema(
+            "commit",
+            json!({"type": "object"}),
+        ));
+        let request = build(provider.as_ref(), &chat);
+
+        assert_eq!(provider.name(), "llamacpp");
+        assert_eq!(
+            request.url().as_str(),
+            "http://localhost:8080/v1/chat/completions"
+        );
+        assert_eq!(header(&request, "authorization"), None);
+        let body = body(&request);
+        assert!(body.get("stream_options").is_none());
+        assert_eq!(body["response_format"]["type"], "json_schema");
+        assert_eq!(
+            body["response_format"]["json_schema"]["schema"],
+            json!({"type": "object"})
+        );
+        assert!(provider.models_request(&Client::new()).is_none());
+    }
+
+    #[test]
+    fn llamacpp_reads_usage_from_the_final_chunk() {
+        let provider = provider(ProviderKind::LlamaCpp, None);
+        let last = r#"{"choices": [], "usage": {"prompt_tokens": 40, "completion_tokens": 9}}"#;
+
+        assert!(matches!(
+            provider.parse_stream_data(last).unwrap(),
+            StreamEvent::Skip
+        ));
+        let usage = provider.parse_usage(last).unwrap();
+        assert_eq!((usage.prompt_tokens, usage.completion_tokens), (40, 9));
+        assert_eq!(usage.cost, None);
+    }
+
+    #[test]
+    fn anthropic_request_uses_messages_api() {
+        let provider = provider(ProviderKind::Anthropic, None);
+        let request = build(provider.as_ref(), &sampled(true));
+
+        assert_eq!(
+            request.url().as_str(),
+            "https://api.anthropic.com/v1/messages"
+        );
+        assert_eq!(header(&request, "x-api-key"), Some("secret"));
+        assert_eq!(
+            header(&request, "anthropic-version"),
+            Some(ANTHROPIC_VERSION)
+        );
+        assert_eq!(header(&request, "authorization"), None);
+        // No seed: the Messages API doesn't take one
+        assert_eq!(
+            body(&request),
+            json!({
+                "model": "m",
+                "max_tokens": ANTHROPIC_MAX_TOKENS,
+                "messages": [{"role": "user", "content": "hi"}],
+                "stream": true,
+                "temperature": 1.0,
+            })
+        );
+    }
+
+    #[test]
+    fn anthropic_parses_stream_events() {
+        let provider = provider(ProviderKind::Anthropic, None);
+
+        let delta =
+            r#"{"type": "content_block_delta", "delta": {"type": "text_delta", "text": "docs"}}"#;
+        assert_eq!(
+            content(provider.parse_stream_data(delta).unwrap()),
+            Some("docs".to_string())
+        );
+        assert!(matches!(
+            provider.parse_stream_data(r#"{"type": "ping"}"#).unwrap(),
+            StreamEvent::Skip
+        ));
+        assert!(matches!(
+            provider
+                .parse_stream_data(r#"{"type": "message_stop"}"#)
+                .unwrap(),
+            StreamEvent::Done
+        ));
+        let error =
+            r#"{"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}}"#;
+        assert!(matches!(
+            provider.parse_stream_data(error).unwrap(),
+            StreamEvent::Error(message) if message == "Overloaded"
+        ));
+    }
+
+    #[test]
+    fn anthropic_parses_responses_and_usage() {
+        let provider = provider(ProviderKind::Anthropic, None);
+        let response = r#"{
+            "content": [{"type": "text", "text": "chore: "}, {"type": "text", "text": "bump"}],
+            "usage": {"input_tokens": 20, "output_tokens": 4}
+        }"#;
+
+        assert_eq!(provider.parse_response(response).unwrap(), "chore: bump");
+        let usage = provider.parse_usage(response).unwrap();
+        assert_eq!((usage.prompt_tokens, usage.completion_tokens), (20, 4));
+
+        let start = r#"{"type": "message_start", "message": {"usage": {"input_tokens": 33, "output_tokens": 1}}}"#;
+        assert_eq!(provider.parse_usage(start).unwrap().prompt_tokens, 33);
+        assert!(provider.parse_usage(r#"{"type": "ping"}"#).is_none());
+    }
+
+    #[test]
+    fn ollama_request_moves_sampling_into_options() {
+        let provider = provider(ProviderKind::Ollama, None);
+        let mut chat = sampled(false);
+        chat.response_format = Some(ResponseFormat::json_schema(
+            "commit",
+            json!({"type": "object"}),
+        ));
+        let request = build(provider.as_ref(), &chat);
+
+        assert_eq!(request.url().as_str(), "http://localhost:11434/api/chat");
+        assert_eq!(header(&request, "authorization"), Some("Bearer secret"));
+        assert_eq!(
+            body(&request),
+            json!({
+                "model": "m",
+                "messages": [{"role": "user", "content": "hi"}],
+                "stream": false,
+                "format": {"type": "object"},
+                "options": {"temperature": 1.0, "seed": 7},
+            })
+        );
+
+        let plain = build(
+            provider.as_ref(),
+            &ChatRequest::user("m", "hi".into(), true),
+        );
+        assert!(body(&plain).get("options").is_none());
+        assert!(body(&plain).get("format").is_none());
+    }
+
+    #[test]
+    fn ollama_parses_json_lines() {
+        let provider = provider(ProviderKind::Ollama, None);
+        assert_eq!(provider.stream_format(), StreamFormat::JsonLines);
+
+        let line = r#"{"message": {"role": "assistant", "content": "test"}, "done": false}"#;
+        assert_eq!(
+            content(provider.parse_stream_data(line).unwrap()),
+            Some("test".to_string())
+        );
+        let last = r#"{"message": {"role": "assistant", "content": ""}, "done": true, "prompt_eval_count": 50, "eval_count": 6}"#;
+        assert!(matches!(
+            provider.parse_stream_data(last).unwrap(),
+            StreamEvent::Done
+        ));
+        let usage = provider.parse_usage(last).unwrap();
+        assert_eq!((usage.prompt_tokens, usage.completion_tokens), (50, 6));
+        assert!(provider.parse_usage(line).is_none());
+        assert_eq!(provider.parse_response(line).unwrap(), "test");
+    }
+
+    #[test]
+    fn backs_off_exponentially_up_to_the_cap() {
+        let delays: Vec<_> = (1..=8).map(|attempt| retry_delay(attempt, None)).collect();
+
+        assert_eq!(
+            delays,
+            [0.5, 1.0, 2.0, 4.0, 8.0, 16.0, 30.0, 30.0].map(Duration::from_secs_f64)
+        );
+        assert_eq!(retry_delay(u32::MAX, None), RETRY_MAX_DELAY);
+    }
+
+    #[test]
+    fn prefers_retry_after_within_the_cap() {
+        let hint = Some(Duration::from_secs(3));
+        assert_eq!(retry_delay(1, hint), Duration::from_secs(3));
+        assert_eq!(retry_delay(5, hint), Duration::from_secs(3));
+        assert_eq!(
+            retry_delay(1, Some(Duration::from_secs(3_600))),
+            RETRY_MAX_DELAY
+        );
+    }
+
+    #[test]
+    fn parses_retry_after_seconds_and_dates() {
+        // Sun, 06 Nov 1994 08:49:37 GMT
+        let now = UNIX_EPOCH + Duration::from_secs(784_111_777);
+
+        assert_eq!(
+            parse_retry_after(" 120 ", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:50:07 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Sat, 01 Mar 2025 00:00:00 GMT", UNIX_EPOCH),
+            Some(Duration::from_secs(1_740_787_200))
+        );
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:00:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("-1", now), None);
+        assert_eq!(parse_retry_after("soon", now), None);
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:49:37 PST", now),
+            None
+        );
+        assert_eq!(
+            parse_retry_after("Sun, 06 Foo 1994 08:49:37 GMT", now),
+            None
+        );
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 25:49:37 GMT", now),
+            None
+        );
+    }
+
+    #[test]
+    fn classifies_failed_statuses() {
+        for status in [408, 429, 500, 502, 503, 504] {
+            let status = StatusCode::from_u16(status).unwrap();
+            assert_eq!(status_action(status), StatusAction::Retry, "{}", status);
+        }
+        assert_eq!(
+            status_action(StatusCode::BAD_REQUEST),
+            StatusAction::BadRequest
+        );
+        assert_eq!(status_action(StatusCode::UNAUTHORIZED), StatusAction::Abort);
+        assert_eq!(status_action(StatusCode::FORBIDDEN), StatusAction::Abort);
+        assert_eq!(
+            status_action(StatusCode::NOT_FOUND),
+            StatusAction::NextModel
+        );
+        assert_eq!(
+            status_action(StatusCode::NOT_IMPLEMENTED),
+            StatusAction::NextModel
+        );
+    }
+
+    #[test]
+    fn tries_fallback_models_after_the_requested_one() {
+        let fallbacks = ["b".to_string(), "a".to_string(), "c".to_string()];
+
+        assert_eq!(model_order("a", &fallbacks), ["a", "b", "c"]);
+        assert_eq!(model_order("z", &[]), ["z"]);
+    }
+
+    /// Answers one request per connection with the status and body `respond`
+    /// picks for the requested model, then returns the models requested.
+    fn serve(
+        connections: usize,
+        respond: fn(&str) -> (u16, &'static str),
+    ) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        use std::io::{BufRead, BufReader, Read};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let mut models = Vec::new();
+            for stream in listener.incoming().take(connections) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let request: Value = serde_json::from_slice(&body).unwrap();
+                let model = request["model"].as_str().unwrap().to_string();
+
+                let (status, reply) = respond(&model);
+                write!(
+                    stream,
+                    "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    reply.len(),
+                    reply
+                )
+                .unwrap();
+                models.push(model);
+            }
+            models
+        });
+        (base_url, server)
+    }
+
+    fn client(base_url: &str, fallback_models: &[&str]) -> ApiClient {
+        let config = Config {
+            provider: ProviderKind::OpenAi,
+            base_url: Some(base_url.to_string()),
+            max_retries: 0,
+            fallback_models: fallback_models.iter().map(|m| m.to_string()).collect(),
+            ..Config::default()
+        };
+        ApiClient::new(&config, Some("secret".into()), false).unwrap()
+    }
+
+    const REPLY: &str = r#"{"choices": [{"message": {"content": "fix: it"}}]}"#;
+
+    #[tokio::test]
+    async fn falls_through_to_the_next_fallback_model() {
+        let (base_url, server) = serve(3, |model| match model {
+            "backup" => (200, REPLY),
+            _ => (503, r#"{"error": {"message": "unavailable"}}"#),
+        });
+        let api = client(&base_url, &["primary", "flaky", "backup"]);
+
+        let reply = send_chat(&api, ChatRequest::user("primary", "hi".into(), false)).await;
+
+        assert_eq!(reply.unwrap(), "fix: it");
+        assert_eq!(server.join().unwrap(), ["primary", "flaky", "backup"]);
+    }
+
+    #[tokio::test]
+    async fn stops_at_authentication_failures() {
+        let (base_url, server) = serve(1, |_| (401, r#"{"error": "bad key"}"#));
+        let api = client(&base_url, &["backup"]);
+
+        let error = send_chat(&api, ChatRequest::user("primary", "hi".into(), false))
+            .await
+            .unwrap_err();
+
+        assert!(error.to_string().contains("401"));
+        assert_eq!(server.join().unwrap(), ["primary"]);
+    }
 }
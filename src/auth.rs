@@ -0,0 +1,406 @@
+//! API key storage and lookup.
+//!
+//! Keys are resolved per provider, first match wins:
+//!
+//! 1. The provider's environment variable (e.g. `OPENROUTER_API_KEY`)
+//! 2. `~/.config/committer/credentials.toml`, written by `committer auth login`
+//!    and readable only by the owner (mode 0600)
+//! 3. The Secret Service keyring, for keys stored with `auth login --keyring`
+//!
+//! The keyring is reached through `secret-tool` (libsecret), so no keyring
+//! library is linked and systems without it simply skip that step.
+
+use clap::ValueEnum;
+use console::style;
+use std::future::Future;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::cli::AuthAction;
+use crate::config::{config_path, Config, ProviderKind};
+
+/// Where a resolved API key came from.
+pub enum KeySource {
+    Env(&'static str),
+    CredentialsFile,
+    Keyring,
+}
+
+impl std::fmt::Display for KeySource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Env(var) => write!(f, "{}", var),
+            Self::CredentialsFile => write!(f, "credentials file"),
+            Self::Keyring => write!(f, "keyring"),
+        }
+    }
+}
+
+/// An API key and where it was found.
+pub struct ApiKey {
+    pub value: String,
+    pub source: KeySource,
+}
+
+/// Returns the path to the credentials file, next to the config file.
+pub fn credentials_path() -> PathBuf {
+    config_path()
+        .parent()
+        .map(|dir| dir.join("credentials.toml"))
+        .unwrap_or_else(|| PathBuf::from("credentials.toml"))
+}
+
+/// Finds the API key for `provider`: environment, credentials file, keyring.
+pub async fn resolve_api_key(provider: ProviderKind) -> Option<ApiKey> {
+    let env = std::env::var(provider.api_key_env()).ok();
+    resolve_from(provider, env, &credentials_path(), keyring_lookup(provider)).await
+}
+
+/// Resolves a key from the environment variable's value, the credentials
+/// file, and the keyring, which is only consulted when the others are empty.
+async fn resolve_from(
+    provider: ProviderKind,
+    env: Option<String>,
+    credentials: &Path,
+    keyring: impl Future<Output = Option<String>>,
+) -> Option<ApiKey> {
+    if let Some(value) = env.filter(|v| !v.is_empty()) {
+        return Some(ApiKey {
+            value,
+            source: KeySource::Env(provider.api_key_env()),
+        });
+    }
+
+    match read_credentials(credentials) {
+        Ok(credentials) => {
+            if let Some(value) = credentials
+                .get(&provider.to_string())
+                .and_then(|v| v.as_str())
+            {
+                return Some(ApiKey {
+                    value: value.to_string(),
+                    source: KeySource::CredentialsFile,
+                });
+            }
+        }
+        Err(e) => eprintln!(
+            "{} Could not read {}: {}",
+            style("⚠").yellow(),
+            credentials.display(),
+            e
+        ),
+    }
+
+    keyring.await.map(|value| ApiKey {
+        value,
+        source: KeySource::Keyring,
+    })
+}
+
+/// Reads the credentials file, mapping provider names to keys.
+fn read_credentials(path: &Path) -> Result<toml::Table, Box<dyn std::error::Error>> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => Ok(toml::from_str(&contents)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(toml::Table::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Writes the credentials file with owner-only permissions.
+fn write_credentials(
+    path: &Path,
+    credentials: &toml::Table,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+
+    // The mode above only applies to new files; tighten an existing one too
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    }
+
+    file.write_all(toml::to_string_pretty(credentials)?.as_bytes())?;
+    Ok(())
+}
+
+/// Stores `key` for `provider` in the credentials file at `path`.
+fn save_key(
+    path: &Path,
+    provider: ProviderKind,
+    key: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut credentials = read_credentials(path)?;
+    credentials.insert(provider.to_string(), toml::Value::String(key.to_string()));
+    write_credentials(path, &credentials)
+}
+
+/// Removes `provider`'s key from the credentials file at `path`, returning
+/// whether one was stored.
+fn remove_key(path: &Path, provider: ProviderKind) -> Result<bool, Box<dyn std::error::Error>> {
+    let mut credentials = read_credentials(path)?;
+    let removed = credentials.remove(&provider.to_string()).is_some();
+    if removed {
+        write_credentials(path, &credentials)?;
+    }
+    Ok(removed)
+}
+
+fn keyring_attributes(provider: ProviderKind) -> [String; 4] {
+    [
+        "service".to_string(),
+        "committer".to_string(),
+        "provider".to_string(),
+        provider.to_string(),
+    ]
+}
+
+/// Looks up a key in the keyring; `None` if absent or `secret-tool` is missing.
+async fn keyring_lookup(provider: ProviderKind) -> Option<String> {
+    let output = Command::new("secret-tool")
+        .arg("lookup")
+        .args(keyring_attributes(provider))
+        .stderr(Stdio::null())
+        .output()
+        .await
+        .ok()?;
+
+    let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !value.is_empty()).then_some(value)
+}
+
+async fn keyring_store(
+    provider: ProviderKind,
+    key: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut child = Command::new("secret-tool")
+        .arg("store")
+        .arg(format!("--label=committer {} API key", provider))
+        .args(keyring_attributes(provider))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            format!(
+                "secret-tool not available ({}); install libsecret-tools or omit --keyring",
+                e
+            )
+        })?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(key.as_bytes()).await?;
+    }
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("secret-tool store failed: {}", stderr.trim()).into());
+    }
+    Ok(())
+}
+
+/// Removes a key from the keyring, returning whether one was stored.
+async fn keyring_clear(provider: ProviderKind) -> bool {
+    if keyring_lookup(provider).await.is_none() {
+        return false;
+    }
+    Command::new("secret-tool")
+        .arg("clear")
+        .args(keyring_attributes(provider))
+        .status()
+        .await
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+/// Reads a key without echoing it, or from stdin when piped.
+fn read_key(provider: ProviderKind) -> Result<String, Box<dyn std::error::Error>> {
+    let key = if std::io::stdin().is_terminal() {
+        dialoguer::Password::new()
+            .with_prompt(format!("{} API key", provider))
+            .interact()?
+    } else {
+        let mut line = String::new();
+        std::io::stdin().lock().read_line(&mut line)?;
+        line
+    };
+
+    let key = key.trim().to_string();
+    if key.is_empty() {
+        return Err("No API key entered".into());
+    }
+    Ok(key)
+}
+
+/// Main handler for the `committer auth` subcommand.
+pub async fn handle_auth_command(
+    action: AuthAction,
+    config: &Config,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match action {
+        AuthAction::Login { provider, keyring } => {
+            let provider = provider.unwrap_or(config.provider);
+            let key = read_key(provider)?;
+
+            if keyring {
+                keyring_store(provider, &key).await?;
+                println!(
+                    "{} Saved {} key to the keyring",
+                    style("✓").green(),
+                    style(provider).cyan()
+                );
+            } else {
+                save_key(&credentials_path(), provider, &key)?;
+                println!(
+                    "{} Saved {} key to {}",
+                    style("✓").green(),
+                    style(provider).cyan(),
+                    credentials_path().display()
+                );
+            }
+
+            if std::env::var_os(provider.api_key_env()).is_some() {
+                println!(
+                    "  {} {} is set and takes priority over the stored key",
+                    style("→").dim(),
+                    provider.api_key_env()
+                );
+            }
+        }
+        AuthAction::Logout { provider } => {
+            let provider = provider.unwrap_or(config.provider);
+
+            let from_file = remove_key(&credentials_path(), provider)?;
+            let from_keyring = keyring_clear(provider).await;
+
+            if from_file || from_keyring {
+                println!(
+                    "{} Removed stored {} key",
+                    style("✓").green(),
+                    style(provider).cyan()
+                );
+            } else {
+                println!(
+                    "{} No stored key for {}",
+                    style("—").dim(),
+                    style(provider).cyan()
+                );
+            }
+        }
+        AuthAction::Status => {
+            for &provider in ProviderKind::value_variants() {
+                if provider == ProviderKind::Replay {
+                    continue;
+                }
+                let status = match resolve_api_key(provider).await {
+                    Some(key) => style(format!("[set via {}]", key.source)).green(),
+                    None if !provider.requires_api_key() => {
+                        style("[not required]".to_string()).dim()
+                    }
+                    None => style("[not set]".to_string()).red(),
+                };
+                let marker = if provider == config.provider {
+                    style("→").cyan()
+                } else {
+                    style(" ").dim()
+                };
+                println!(
+                    "{} {} {}",
+                    marker,
+                    style(format!("{}:", provider)).cyan(),
+                    status
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    async fn resolve(env: Option<&str>, path: &Path, keyring: Option<&str>) -> Option<String> {
+        let keyring = async { keyring.map(str::to_string) };
+        resolve_from(ProviderKind::OpenAi, env.map(str::to_string), path, keyring)
+            .await
+            .map(|key| format!("{} from {}", key.value, key.source))
+    }
+
+    #[tokio::test]
+    async fn prefers_env_then_file_then_keyring() {
+        let dir = TempDir::new("auth-resolve");
+        let path = dir.join("credentials.toml");
+
+        assert_eq!(
+            resolve(None, &path, Some("k")).await.unwrap(),
+            "k from keyring"
+        );
+        assert_eq!(resolve(None, &path, None).await, None);
+
+        save_key(&path, ProviderKind::OpenAi, "f").unwrap();
+        assert_eq!(
+            resolve(None, &path, Some("k")).await.unwrap(),
+            "f from credentials file"
+        );
+        assert_eq!(
+            resolve(Some(""), &path, Some("k")).await.unwrap(),
+            "f from credentials file"
+        );
+        assert_eq!(
+            resolve(Some("e"), &path, Some("k")).await.unwrap(),
+            "e from OPENAI_API_KEY"
+        );
+    }
+
+    #[test]
+    fn saves_loads_and_removes_keys() {
+        let dir = TempDir::new("auth-roundtrip");
+        let path = dir.join("config").join("credentials.toml");
+
+        save_key(&path, ProviderKind::OpenAi, "sk-one").unwrap();
+        save_key(&path, ProviderKind::Anthropic, "sk-two").unwrap();
+        let credentials = read_credentials(&path).unwrap();
+        assert_eq!(credentials["openai"].as_str(), Some("sk-one"));
+        assert_eq!(credentials["anthropic"].as_str(), Some("sk-two"));
+
+        assert!(remove_key(&path, ProviderKind::OpenAi).unwrap());
+        assert!(!remove_key(&path, ProviderKind::OpenAi).unwrap());
+        let credentials = read_credentials(&path).unwrap();
+        assert!(!credentials.contains_key("openai"));
+        assert_eq!(credentials["anthropic"].as_str(), Some("sk-two"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn credentials_are_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new("auth-mode");
+        let path = dir.join("credentials.toml");
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        save_key(&path, ProviderKind::OpenAi, "sk").unwrap();
+        assert_eq!(mode(&path), 0o600);
+
+        // An existing file is tightened too
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        save_key(&path, ProviderKind::OpenAi, "sk").unwrap();
+        assert_eq!(mode(&path), 0o600);
+    }
+}
@@ -13,10 +13,11 @@
 //! Examples: `feat/auth-login`, `fix/ui-button-style`, `refactor/api-client`
 
 use regex_lite::Regex;
-use reqwest::Client;
 use serde::Deserialize;
+use serde_json::json;
 
-use crate::api::{ChatRequest, Message, NonStreamResponse, OPENROUTER_API_URL};
+use crate::api::{send_chat, ApiClient, ChatRequest, Message, ResponseFormat};
+use crate::prompt::render;
 
 /// Branches that should never receive direct commits.
 pub const PROTECTED_BRANCHES: &[&str] =
@@ -131,152 +132,159 @@ pub fn generate_fallback_branch(commit_message: &str) -> String {
 ///
 /// Returns analysis with match status, reason, and suggested branch name.
 pub async fn analyze_branch_alignment(
-    client: &Client,
-    api_key: &str,
+    api: &ApiClient,
     model: &str,
+    template: &str,
     current_branch: &str,
     commit_message: &str,
     files_changed: &str,
     recent_commits: &str,
 ) -> Result<BranchAnalysis, Box<dyn std::error::Error>> {
-    let prompt = format!(
-        r#"You are a git branch analyzer. Determine if the current commit belongs on this branch.
-
-CURRENT BRANCH: {current_branch}
-
-RECENT COMMITS ON THIS BRANCH:
-{recent_commits}
-
-FILES BEING CHANGED IN THIS COMMIT:
-{files_changed}
-
-NEW COMMIT MESSAGE:
-{commit_message}
-
-ANALYSIS RULES:
-1. Protected branches (main, master, develop, dev, staging, production) - NEVER match, always suggest a feature branch
-2. The commit scope/module MUST relate to the branch name. Example: branch "feat/auth-login" should only have auth-related commits, NOT unrelated features like "feat(db): add migration"
-3. Different commit TYPES (feat, fix, refactor, docs, test) on the SAME feature are fine - e.g., feat/auth can have "feat(auth): add login" then "fix(auth): handle edge case" then "docs(auth): add comments"
-4. If the commit introduces a NEW scope/module not mentioned in the branch name, flag as MISMATCH
-5. Be STRICT: when in doubt, flag as mismatch. It's better to suggest a new branch than pollute an existing one with unrelated work
-
-BRANCH NAMING CONVENTION: <type>/<scope>-<short-description>
-Examples: feat/auth-refresh-token, fix/ui-chat-scroll, refactor/server-ws-reconnect
-
-Respond with ONLY valid JSON:
-- If matches: {{"matches": true, "reason": "brief explanation"}}
-- If mismatch: {{"matches": false, "reason": "brief explanation", "suggested_branch": "type/scope-description"}}"#
+    let prompt = render(
+        template,
+        &[
+            ("branch", current_branch),
+            ("commits", recent_commits),
+            ("files", files_changed),
+            ("message", commit_message),
+        ],
     );
 
-    let request = ChatRequest {
-        model: model.to_string(),
-        messages: vec![Message {
-            role: "user".to_string(),
-            content: prompt,
-        }],
-        stream: false,
-        provider: None,
+    let content = send_chat(api, branch_analysis_request(model, &prompt)).await?;
+    let error = match parse_branch_analysis(&content) {
+        Ok(analysis) => return Ok(analysis),
+        Err(e) => e,
     };
 
-    let response = client
-        .post(OPENROUTER_API_URL)
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .header("X-Title", "Committer")
-        .header("HTTP-Referer", "https://github.com/Nolanneff/commiter")
-        .json(&request)
-        .send()
-        .await?;
-
-    if !response.status().is_success() {
-        let status = response.status();
-        let body = response.text().await.unwrap_or_default();
-        return Err(format!("API error ({}): {}", status, body).into());
-    }
+    // Re-ask once, showing the model its unparseable reply
+    let mut request = branch_analysis_request(model, &prompt);
+    request.messages.push(Message {
+        role: "assistant".to_string(),
+        content,
+    });
+    request.messages.push(Message {
+        role: "user".to_string(),
+        content: format!(
+            "That reply was not valid JSON ({}). Respond again with ONLY the JSON object.",
+            error
+        ),
+    });
+
+    let content = send_chat(api, request).await?;
+    parse_branch_analysis(&content).map_err(|e| {
+        format!(
+            "Failed to parse branch analysis: {} - raw: {}",
+            e,
+            content.trim()
+        )
+        .into()
+    })
+}
 
-    let response_body: NonStreamResponse = response.json().await?;
-    let content = response_body
-        .choices
-        .first()
-        .map(|c| c.message.content.clone())
-        .unwrap_or_default();
+fn branch_analysis_request(model: &str, prompt: &str) -> ChatRequest {
+    let mut request = ChatRequest::user(model, prompt.to_string(), false);
+    request.response_format = Some(ResponseFormat::json_schema(
+        "branch_analysis",
+        json!({
+            "type": "object",
+            "properties": {
+                "matches": { "type": "boolean" },
+                "reason": { "type": "string" },
+                "suggested_branch": { "type": ["string", "null"] }
+            },
+            "required": ["matches", "reason", "suggested_branch"],
+            "additionalProperties": false
+        }),
+    ));
+    request
+}
 
+/// Parses a branch analysis reply.
+///
+/// Models without structured output support often wrap the JSON in code
+/// fences or prose, so the outermost `{...}` object is tried as a repair.
+pub fn parse_branch_analysis(content: &str) -> Result<BranchAnalysis, serde_json::Error> {
     let content = content.trim();
-    let content = content.strip_prefix("```json").unwrap_or(content);
-    let content = content.strip_prefix("```").unwrap_or(content);
-    let content = content.strip_suffix("```").unwrap_or(content);
-    let content = content.trim();
-
-    let analysis: BranchAnalysis = serde_json::from_str(content)
-        .map_err(|e| format!("Failed to parse branch analysis: {} - raw: {}", e, content))?;
+    match serde_json::from_str(content) {
+        Ok(analysis) => Ok(analysis),
+        Err(e) => match (content.find('{'), content.rfind('}')) {
+            (Some(start), Some(end)) if start < end => serde_json::from_str(&content[start..=end]),
+            _ => Err(e),
+        },
+    }
+}
 
-    Ok(analysis)
+/// Offline stand-in for [`analyze_branch_alignment`] when the LLM can't
+/// produce a usable analysis.
+///
+/// Only protected branches are flagged, with a name from
+/// [`generate_fallback_branch`]; anything else is assumed to match.
+pub fn fallback_branch_analysis(current_branch: &str, commit_message: &str) -> BranchAnalysis {
+    if PROTECTED_BRANCHES.contains(&current_branch) {
+        BranchAnalysis {
+            matches: false,
+            reason: format!("'{}' is a protected branch", current_branch),
+            suggested_branch: Some(generate_fallback_branch(commit_message)),
+        }
+    } else {
+        BranchAnalysis {
+            matches: true,
+            reason: "branch analysis unavailable; assuming current branch".to_string(),
+            suggested_branch: None,
+        }
+    }
 }
 
 /// Generates a branch name suggestion using LLM.
 ///
 /// Falls back to [`generate_fallback_branch`] on error.
 pub async fn generate_branch_suggestion(
-    client: &Client,
-    api_key: &str,
+    api: &ApiClient,
     model: &str,
+    template: &str,
     commit_message: &str,
-) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-    let prompt = format!(
-        r#"Given this commit message, suggest an appropriate git branch name.
+) -> Result<String, Box<dyn std::error::Error>> {
+    let prompt = render(template, &[("message", commit_message)]);
 
-COMMIT MESSAGE:
-{commit_message}
+    let content = send_chat(api, ChatRequest::user(model, prompt, false)).await?;
 
-BRANCH NAMING RULES:
-1. Use format: <type>/<scope>-<short-description>
-2. Type should match the commit type (feat, fix, docs, refactor, test, chore, etc.)
-3. Scope is the area/module being changed (auth, ui, server, api, etc.)
-4. Description should be kebab-case, concise (2-4 words)
-5. Keep the full branch name under 50 characters when possible
+    let branch_name = content.trim().to_string();
 
-BRANCH NAMING CONVENTION: <type>/<scope>-<short-description>
-Examples: feat/auth-refresh-token, fix/ui-chat-scroll, refactor/server-ws-reconnect
+    if branch_name.is_empty() {
+        return Err("Empty branch name returned".into());
+    }
 
-Respond with ONLY the branch name, nothing else."#
-    );
+    Ok(branch_name)
+}
 
-    let request = ChatRequest {
-        model: model.to_string(),
-        messages: vec![Message {
-            role: "user".to_string(),
-            content: prompt,
-        }],
-        stream: false,
-        provider: None,
-    };
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let response = client
-        .post(OPENROUTER_API_URL)
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .header("X-Title", "Committer")
-        .header("HTTP-Referer", "https://github.com/nolancui/committer")
-        .json(&request)
-        .send()
-        .await?;
+    #[test]
+    fn parses_fenced_and_wrapped_analysis() {
+        let fenced = "```json\n{\"matches\": true, \"reason\": \"same scope\"}\n```";
+        assert!(parse_branch_analysis(fenced).unwrap().matches);
 
-    if !response.status().is_success() {
-        return Err(format!("API request failed: {}", response.status()).into());
-    }
+        let wrapped = "Here you go: {\"matches\": false, \"reason\": \"new scope\", \"suggested_branch\": \"feat/db-migrations\"} Hope that helps";
+        let analysis = parse_branch_analysis(wrapped).unwrap();
+        assert_eq!(
+            analysis.suggested_branch.as_deref(),
+            Some("feat/db-migrations")
+        );
 
-    let response_body: NonStreamResponse = response.json().await?;
-    let content = response_body
-        .choices
-        .first()
-        .map(|c| c.message.content.clone())
-        .unwrap_or_default();
+        assert!(parse_branch_analysis("no json here").is_err());
+    }
 
-    let branch_name = content.trim().to_string();
+    #[test]
+    fn fallback_analysis_only_flags_protected_branches() {
+        let protected = fallback_branch_analysis("main", "feat(auth): add login flow");
+        assert!(!protected.matches);
+        assert_eq!(
+            protected.suggested_branch.as_deref(),
+            Some("feat/auth-login-flow")
+        );
 
-    if branch_name.is_empty() {
-        return Err("Empty branch name returned".into());
+        assert!(fallback_branch_analysis("feat/auth-login", "feat(auth): add login flow").matches);
     }
-
-    Ok(branch_name)
 }
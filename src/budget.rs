@@ -0,0 +1,302 @@
+//! Token-aware diff budgeting.
+//!
+//! Large diffs are cut down to fit the model's context window, measured in
+//! estimated tokens (about four characters each). Rather than keeping the
+//! first files and dropping the rest, the budget is spent by priority:
+//!
+//! 1. Every file's header, so the model sees what changed everywhere
+//! 2. The smallest hunk of each file
+//! 3. The remaining hunks, smallest first
+//!
+//! Omitted hunks are replaced by a marker with their line counts, and files
+//! that don't fit at all are counted at the end.
+//!
+//! The context window comes from `context_window` in the config, then the
+//! provider's model list (see [`crate::models`]), then
+//! [`DEFAULT_CONTEXT_WINDOW`]. `max_diff_tokens` caps the budget either way.
+
+use crate::api::ApiClient;
+use crate::config::Config;
+use crate::models::context_window;
+
+/// Rough number of characters per token for code and diffs.
+pub const CHARS_PER_TOKEN: usize = 4;
+
+/// Context window assumed when neither the config nor the provider gives one.
+pub const DEFAULT_CONTEXT_WINDOW: u64 = 128_000;
+
+/// Tokens kept free for the model's reply.
+pub const RESPONSE_RESERVE_TOKENS: usize = 4_096;
+
+/// Tokens set aside per file for omission markers.
+const MARKER_TOKENS: usize = 20;
+
+/// Estimates the number of tokens in `text`.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Computes how many tokens the diff may use.
+///
+/// `prompt_tokens` covers everything else in the prompt. The result is
+/// capped at `max_diff_tokens` so very large windows don't mean very large
+/// bills.
+pub fn diff_token_budget(context_window: u64, max_diff_tokens: u64, prompt_tokens: usize) -> usize {
+    let available = (context_window as usize)
+        .saturating_sub(prompt_tokens)
+        .saturating_sub(RESPONSE_RESERVE_TOKENS);
+    available.min(max_diff_tokens as usize)
+}
+
+/// Works out how many tokens the diff may use for `model`, given the rest of
+/// the `prompt` it goes into: the template and the other values rendered
+/// into it, such as the file list.
+pub async fn resolve_diff_budget(
+    api: &ApiClient,
+    config: &Config,
+    model: &str,
+    prompt: &[&str],
+    verbose: bool,
+) -> usize {
+    let window = match config.context_window {
+        Some(window) => window,
+        None => context_window(api, model, verbose)
+            .await
+            .unwrap_or(DEFAULT_CONTEXT_WINDOW),
+    };
+    let prompt_tokens = prompt.iter().map(|text| estimate_tokens(text)).sum();
+    let budget = diff_token_budget(window, config.max_diff_tokens, prompt_tokens);
+    if verbose {
+        eprintln!(
+            "— Diff budget: ~{} tokens ({} context window)",
+            budget, window
+        );
+    }
+    budget
+}
+
+/// One file's section of a unified diff.
+struct FileDiff<'a> {
+    /// Everything before the first hunk (`diff --git`, `index`, `---`/`+++`).
+    header: &'a str,
+    hunks: Vec<&'a str>,
+}
+
+/// Splits `text` before each line starting with `marker`, keeping newlines
+/// with the preceding piece.
+fn split_before<'a>(text: &'a str, marker: &str) -> Vec<&'a str> {
+    let needle = format!("\n{}", marker);
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    for (i, _) in text.match_indices(&needle) {
+        pieces.push(&text[start..=i]);
+        start = i + 1;
+    }
+    pieces.push(&text[start..]);
+    pieces.retain(|piece| !piece.is_empty());
+    pieces
+}
+
+/// Splits a unified diff into per-file sections, each starting with its
+/// `diff --git` line.
+pub fn split_files(diff: &str) -> Vec<&str> {
+    split_before(diff, "diff --git ")
+}
+
+fn parse_file(section: &str) -> FileDiff<'_> {
+    let mut pieces = split_before(section, "@@ ");
+    let header = if section.starts_with("@@ ") {
+        ""
+    } else {
+        pieces.remove(0)
+    };
+    FileDiff {
+        header,
+        hunks: pieces,
+    }
+}
+
+/// Counts added and removed lines in a hunk.
+fn line_counts(hunk: &str) -> (usize, usize) {
+    hunk.lines().skip(1).fold((0, 0), |(added, removed), line| {
+        match line.as_bytes().first() {
+            Some(b'+') => (added + 1, removed),
+            Some(b'-') => (added, removed + 1),
+            _ => (added, removed),
+        }
+    })
+}
+
+/// Cuts `diff` down to roughly `budget` tokens, spending it by priority.
+///
+/// Diffs that already fit are returned unchanged.
+pub fn fit_diff(diff: &str, budget: usize, verbose: bool) -> String {
+    let total_tokens = estimate_tokens(diff);
+    if total_tokens <= budget {
+        return diff.to_string();
+    }
+
+    let files: Vec<FileDiff> = split_files(diff).into_iter().map(parse_file).collect();
+    let mut remaining = budget;
+
+    // 1. Headers, in diff order, until they no longer fit
+    let mut kept_files = 0;
+    for file in &files {
+        let cost = estimate_tokens(file.header) + MARKER_TOKENS;
+        if cost > remaining {
+            break;
+        }
+        remaining -= cost;
+        kept_files += 1;
+    }
+
+    // 2. The smallest hunk of each kept file, then 3. everything else by size
+    let mut kept_hunks: Vec<Vec<bool>> = files
+        .iter()
+        .map(|file| vec![false; file.hunks.len()])
+        .collect();
+    let mut smallest_first: Vec<(usize, usize, usize)> = Vec::new();
+    let mut rest: Vec<(usize, usize, usize)> = Vec::new();
+    for (f, file) in files.iter().enumerate().take(kept_files) {
+        let mut hunks: Vec<(usize, usize, usize)> = file
+            .hunks
+            .iter()
+            .enumerate()
+            .map(|(h, hunk)| (estimate_tokens(hunk), f, h))
+            .collect();
+        hunks.sort();
+        let mut hunks = hunks.into_iter();
+        smallest_first.extend(hunks.next());
+        rest.extend(hunks);
+    }
+    smallest_first.sort();
+    rest.sort();
+    for (cost, f, h) in smallest_first.into_iter().chain(rest) {
+        if cost <= remaining {
+            remaining -= cost;
+            kept_hunks[f][h] = true;
+        }
+    }
+
+    let mut result = String::new();
+    let mut omitted_hunks = 0;
+    for (file, kept) in files.iter().zip(&kept_hunks).take(kept_files) {
+        result.push_str(file.header);
+        let mut omitted = 0;
+        let (mut added, mut removed) = (0, 0);
+        for (hunk, &keep) in file.hunks.iter().zip(kept) {
+            if keep {
+                result.push_str(hunk);
+            } else {
+                omitted += 1;
+                let (a, r) = line_counts(hunk);
+                added += a;
+                removed += r;
+            }
+        }
+        if omitted > 0 {
+            if !result.ends_with('\n') {
+                result.push('\n');
+            }
+            result.push_str(&format!(
+                "[... {} of {} hunks omitted (+{} -{} lines) ...]\n",
+                omitted,
+                file.hunks.len(),
+                added,
+                removed
+            ));
+            omitted_hunks += omitted;
+        }
+    }
+
+    let omitted_files = files.len() - kept_files;
+    if omitted_files > 0 {
+        result.push_str(&format!(
+            "\n[... {} more files omitted to fit the context budget ...]\n",
+            omitted_files
+        ));
+    }
+
+    if verbose {
+        eprintln!(
+            "— Diff budgeted: ~{} of ~{} tokens kept ({} hunks, {} files omitted)",
+            estimate_tokens(&result),
+            total_tokens,
+            omitted_hunks,
+            omitted_files
+        );
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_diff(name: &str, hunk_sizes: &[usize]) -> String {
+        let mut diff = format!("diff --git a/{0} b/{0}\n--- a/{0}\n+++ b/{0}\n", name);
+        for (i, &size) in hunk_sizes.iter().enumerate() {
+            diff.push_str(&format!("@@ -{0},1 +{0},1 @@\n", i + 1));
+            for _ in 0..size {
+                diff.push_str("+added line of code\n");
+            }
+        }
+        diff
+    }
+
+    #[test]
+    fn leaves_small_diffs_alone() {
+        let diff = file_diff("a.rs", &[2]);
+        assert_eq!(fit_diff(&diff, 10_000, false), diff);
+    }
+
+    #[test]
+    fn keeps_small_hunks_from_every_file_before_large_ones() {
+        let diff = [file_diff("big.rs", &[400, 3]), file_diff("small.rs", &[2])].concat();
+
+        let fitted = fit_diff(&diff, 300, false);
+
+        assert!(fitted.contains("diff --git a/big.rs"));
+        assert!(fitted.contains("diff --git a/small.rs"));
+        assert!(fitted.contains("@@ -2,1 +2,1 @@"));
+        assert_eq!(fitted.matches("+added line of code").count(), 5);
+        assert!(fitted.contains("[... 1 of 2 hunks omitted (+400 -0 lines) ...]"));
+        assert!(estimate_tokens(&fitted) <= 300);
+    }
+
+    #[test]
+    fn counts_files_whose_headers_do_not_fit() {
+        let diff: String = (0..50)
+            .map(|i| file_diff(&format!("f{}.rs", i), &[1]))
+            .collect();
+
+        let fitted = fit_diff(&diff, 200, false);
+
+        assert!(fitted.contains("diff --git a/f0.rs"));
+        assert!(fitted.contains("more files omitted to fit the context budget"));
+    }
+
+    #[test]
+    fn reserves_room_for_prompt_and_reply() {
+        assert_eq!(diff_token_budget(8_192, 75_000, 1_000), 3_096);
+        assert_eq!(diff_token_budget(1_000_000, 75_000, 1_000), 75_000);
+        assert_eq!(diff_token_budget(2_048, 75_000, 1_000), 0);
+    }
+
+    #[tokio::test]
+    async fn counts_rendered_values_against_the_budget() {
+        let config = Config {
+            context_window: Some(10_000),
+            ..Config::default()
+        };
+        let api = ApiClient::new(&config, Some("key".into()), false).unwrap();
+        let template = "Files:\n{files}\n\nDiff:\n{diff}";
+        let files = "src/file.rs\n".repeat(400);
+
+        let template_only = resolve_diff_budget(&api, &config, "m", &[template], false).await;
+        let with_files = resolve_diff_budget(&api, &config, "m", &[template, &files], false).await;
+
+        assert_eq!(template_only - with_files, 1_200);
+    }
+}
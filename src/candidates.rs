@@ -0,0 +1,190 @@
+//! Several alternative commit messages to choose from.
+//!
+//! `committer --candidates 3` asks for three messages at once and shows them
+//! in a picker; the chosen one then goes through the usual commit prompt.
+//! Candidates come in turn from `candidate_models` when it's set, and from
+//! the selected model otherwise. Repeats of a model are asked with a fresh
+//! seed and a raised temperature so they differ.
+
+use console::style;
+use futures::stream::{self, StreamExt};
+use indicatif::{ProgressBar, ProgressStyle};
+
+use crate::api::{stream_commit_message, ApiClient, CommitMessageOptions, Sampling};
+use crate::lint::{fix, LintRules};
+use crate::ui::prompt_candidate;
+
+/// Candidate requests in flight at once.
+const MAX_CONCURRENT_CANDIDATES: usize = 4;
+
+/// A generated message and the model that wrote it.
+pub struct Candidate {
+    pub model: String,
+    pub message: String,
+}
+
+/// The model for each of `count` candidates, and whether it repeats an
+/// earlier candidate's model.
+pub fn assign_models<'a>(
+    models: &'a [String],
+    model: &'a str,
+    count: usize,
+) -> Vec<(&'a str, bool)> {
+    let models: Vec<&str> = if models.is_empty() {
+        vec![model]
+    } else {
+        models.iter().map(String::as_str).collect()
+    };
+    (0..count)
+        .map(|i| (models[i % models.len()], i >= models.len()))
+        .collect()
+}
+
+/// Generates a message per entry of `assigned` (see [`assign_models`])
+/// concurrently, without streaming them to the terminal.
+///
+/// Duplicates are dropped, and so are failed candidates after a warning;
+/// it's an error only when none succeed. With `rules`, each message is
+/// lint-fixed.
+pub async fn generate_candidates(
+    api: &ApiClient,
+    assigned: &[(&str, bool)],
+    diff: &str,
+    files: &str,
+    options: CommitMessageOptions<'_>,
+    rules: Option<&LintRules>,
+) -> Result<Vec<Candidate>, Box<dyn std::error::Error>> {
+    let spinner = ProgressBar::new_spinner();
+    spinner.set_style(
+        ProgressStyle::default_spinner()
+            .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"])
+            .template("{spinner:.cyan} {msg}")
+            .unwrap(),
+    );
+    spinner.set_message(format!(
+        "Generating {} candidate messages...",
+        assigned.len()
+    ));
+    spinner.enable_steady_tick(std::time::Duration::from_millis(80));
+
+    let results: Vec<_> = stream::iter(assigned.iter().enumerate().map(
+        |(i, &(model, repeat))| async move {
+            let mut options = CommitMessageOptions {
+                echo: false,
+                ..options
+            };
+            if repeat {
+                // Requests start within the same instant, so spread the seeds
+                let sampling = Sampling::varied();
+                options.refinement.sampling = Some(Sampling {
+                    seed: sampling.seed.wrapping_add(i as u32),
+                    ..sampling
+                });
+            }
+            let result =
+                stream_commit_message(api, model, diff, files, &ProgressBar::hidden(), options)
+                    .await;
+            (model, result)
+        },
+    ))
+    .buffered(MAX_CONCURRENT_CANDIDATES)
+    .collect()
+    .await;
+    spinner.finish_and_clear();
+
+    let mut candidates: Vec<Candidate> = Vec::new();
+    let mut first_error = None;
+    for (model, result) in results {
+        match result {
+            Ok(message) if !message.is_empty() => {
+                let message = match rules {
+                    Some(rules) => fix(&message, rules),
+                    None => message,
+                };
+                if !candidates.iter().any(|c| c.message == message) {
+                    candidates.push(Candidate {
+                        model: model.to_string(),
+                        message,
+                    });
+                }
+            }
+            Ok(_) => println!(
+                "{} {} returned an empty message",
+                style("⚠").yellow(),
+                model
+            ),
+            Err(e) => {
+                println!("{} {} failed: {}", style("⚠").yellow(), model, e);
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+
+    if candidates.is_empty() {
+        return Err(first_error.unwrap_or_else(|| "No candidate messages generated".into()));
+    }
+    Ok(candidates)
+}
+
+/// Prints every candidate, numbered.
+pub fn print_candidates(candidates: &[Candidate]) {
+    for (i, candidate) in candidates.iter().enumerate() {
+        println!();
+        println!(
+            "{} {}",
+            style(format!("[{}]", i + 1)).cyan().bold(),
+            style(&candidate.model).dim()
+        );
+        println!("{}", candidate.message);
+    }
+}
+
+/// Generates candidates and returns the one to commit.
+///
+/// Interactive runs pick from a list, and `None` means the user cancelled;
+/// otherwise the first candidate is used.
+pub async fn choose_candidate(
+    api: &ApiClient,
+    assigned: &[(&str, bool)],
+    diff: &str,
+    files: &str,
+    options: CommitMessageOptions<'_>,
+    rules: Option<&LintRules>,
+    interactive: bool,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let mut candidates = generate_candidates(api, assigned, diff, files, options, rules).await?;
+    print_candidates(&candidates);
+    println!();
+
+    if !interactive {
+        println!("{} Using candidate 1", style("→").dim());
+        return Ok(Some(candidates.swap_remove(0).message));
+    }
+
+    let Some(index) = prompt_candidate(&candidates) else {
+        return Ok(None);
+    };
+    let message = candidates.swap_remove(index).message;
+    println!();
+    println!("{}", message);
+    Ok(Some(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeats_models_with_varied_sampling() {
+        assert_eq!(
+            assign_models(&[], "m", 3),
+            vec![("m", false), ("m", true), ("m", true)]
+        );
+
+        let models = vec!["a".to_string(), "b".to_string()];
+        assert_eq!(
+            assign_models(&models, "m", 3),
+            vec![("a", false), ("b", false), ("a", true)]
+        );
+    }
+}
@@ -0,0 +1,265 @@
+//! Structured descriptions of file changes that raw diff text describes badly.
+//!
+//! Some entries in a diff are noise to the model: binary files only say
+//! "Binary files differ", deleted files repeat their whole content, and
+//! submodule bumps are two opaque SHAs. [`describe_changes`] rewrites those
+//! sections as a short bracketed note after the `diff --git` line:
+//!
+//! ```text
+//! diff --git a/logo.png b/logo.png
+//! [binary file modified: logo.png]
+//! diff --git a/old.rs b/new.rs
+//! [renamed: old.rs -> new.rs, 100% similar, content unchanged]
+//! diff --git a/vendor/lib b/vendor/lib
+//! [submodule vendor/lib updated: 1a2b3c4..5d6e7f8]
+//! ```
+//!
+//! Renames, copies, and mode changes that also edit content keep their hunks
+//! below the note. Ordinary modifications and new text files pass through
+//! unchanged.
+
+use crate::budget::split_files;
+use crate::git::extract_filename_from_diff_header;
+
+/// Mode git records for submodule entries.
+const SUBMODULE_MODE: &str = "160000";
+
+/// What the extended header and hunks of one file section say about it.
+#[derive(Default)]
+struct Section<'a> {
+    first_line: &'a str,
+    path: &'a str,
+    new_file: bool,
+    deleted: bool,
+    binary: bool,
+    submodule: bool,
+    /// `(kind, from, to, similarity)` for renames and copies.
+    moved: Option<(&'static str, &'a str, &'a str, Option<&'a str>)>,
+    old_mode: Option<&'a str>,
+    new_mode: Option<&'a str>,
+    /// Hunks, from the first `@@` line on.
+    hunks: &'a str,
+}
+
+fn parse_section(text: &str) -> Section<'_> {
+    let mut section = Section {
+        first_line: text.lines().next().unwrap_or(""),
+        path: extract_filename_from_diff_header(text).unwrap_or(""),
+        ..Section::default()
+    };
+    let (mut from, mut to, mut similarity, mut kind) = (None, None, None, "renamed");
+
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        if line.starts_with("@@ ") {
+            section.hunks = &text[offset..];
+            break;
+        }
+        offset += line.len();
+        let line = line.trim_end();
+
+        if let Some(mode) = line.strip_prefix("new file mode ") {
+            section.new_file = true;
+            section.submodule |= mode == SUBMODULE_MODE;
+        } else if let Some(mode) = line.strip_prefix("deleted file mode ") {
+            section.deleted = true;
+            section.submodule |= mode == SUBMODULE_MODE;
+        } else if let Some(mode) = line.strip_prefix("old mode ") {
+            section.old_mode = Some(mode);
+        } else if let Some(mode) = line.strip_prefix("new mode ") {
+            section.new_mode = Some(mode);
+        } else if let Some(index) = line.strip_prefix("index ") {
+            section.submodule |= index.ends_with(SUBMODULE_MODE);
+        } else if let Some(score) = line.strip_prefix("similarity index ") {
+            similarity = Some(score);
+        } else if let Some(path) = line.strip_prefix("rename from ") {
+            from = Some(path);
+        } else if let Some(path) = line.strip_prefix("rename to ") {
+            to = Some(path);
+        } else if let Some(path) = line.strip_prefix("copy from ") {
+            (from, kind) = (Some(path), "copied");
+        } else if let Some(path) = line.strip_prefix("copy to ") {
+            to = Some(path);
+        } else if line.starts_with("Binary files ") || line == "GIT binary patch" {
+            section.binary = true;
+        }
+    }
+
+    if let (Some(from), Some(to)) = (from, to) {
+        section.moved = Some((kind, from, to, similarity));
+        section.path = to;
+    }
+    section
+}
+
+/// Short SHAs from a submodule hunk's `-Subproject commit` and
+/// `+Subproject commit` lines.
+fn submodule_range(hunks: &str) -> (Option<&str>, Option<&str>) {
+    let commit = |prefix: &str| {
+        hunks.lines().find_map(|line| {
+            line.strip_prefix(prefix)
+                .map(|sha| &sha[..sha.len().min(7)])
+        })
+    };
+    (commit("-Subproject commit "), commit("+Subproject commit "))
+}
+
+/// Builds the note for a section, and whether its hunks should follow.
+fn describe(section: &Section) -> Option<(String, bool)> {
+    let path = section.path;
+
+    if section.submodule {
+        let note = match submodule_range(section.hunks) {
+            (Some(from), Some(to)) => format!("[submodule {} updated: {}..{}]", path, from, to),
+            (None, Some(to)) => format!("[submodule {} added at {}]", path, to),
+            (Some(from), None) => format!("[submodule {} removed, was at {}]", path, from),
+            (None, None) => format!("[submodule {} changed]", path),
+        };
+        return Some((note, false));
+    }
+
+    if section.binary {
+        let action = if section.new_file {
+            "added"
+        } else if section.deleted {
+            "deleted"
+        } else {
+            "modified"
+        };
+        let note = match section.moved {
+            Some((kind, from, to, _)) => format!("[binary file {}: {} -> {}]", kind, from, to),
+            None => format!("[binary file {}: {}]", action, path),
+        };
+        return Some((note, false));
+    }
+
+    if section.deleted {
+        let lines = section
+            .hunks
+            .lines()
+            .filter(|line| line.starts_with('-'))
+            .count();
+        return Some((format!("[deleted: {}, {} lines]", path, lines), false));
+    }
+
+    let mut notes = Vec::new();
+    if let Some((kind, from, to, similarity)) = section.moved {
+        let mut note = format!("[{}: {} -> {}", kind, from, to);
+        if let Some(similarity) = similarity {
+            note.push_str(&format!(", {} similar", similarity));
+        }
+        if section.hunks.is_empty() {
+            note.push_str(", content unchanged");
+        }
+        note.push(']');
+        notes.push(note);
+    }
+    if let (Some(old), Some(new)) = (section.old_mode, section.new_mode) {
+        let executable = match (old.ends_with("755"), new.ends_with("755")) {
+            (false, true) => ", now executable",
+            (true, false) => ", no longer executable",
+            _ => "",
+        };
+        notes.push(format!("[mode changed: {} -> {}{}]", old, new, executable));
+    }
+
+    (!notes.is_empty()).then(|| (notes.join("\n"), true))
+}
+
+/// Replaces binary, deleted, renamed, mode-changed, and submodule sections
+/// of a diff with structured notes.
+pub fn describe_changes(diff: &str) -> String {
+    let mut result = String::with_capacity(diff.len());
+    for text in split_files(diff) {
+        if !text.starts_with("diff --git ") {
+            result.push_str(text);
+            continue;
+        }
+        let section = parse_section(text);
+        match describe(&section) {
+            Some((note, keep_hunks)) => {
+                result.push_str(section.first_line);
+                result.push('\n');
+                result.push_str(&note);
+                result.push('\n');
+                if keep_hunks {
+                    result.push_str(section.hunks);
+                }
+            }
+            None => result.push_str(text),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_binary_deleted_and_submodule_changes() {
+        let diff = "diff --git a/logo.png b/logo.png\n\
+            index 1a2b3c4..5d6e7f8 100644\n\
+            Binary files a/logo.png and b/logo.png differ\n\
+            diff --git a/old.txt b/old.txt\n\
+            deleted file mode 100644\n\
+            index 1a2b3c4..0000000\n\
+            --- a/old.txt\n\
+            +++ /dev/null\n\
+            @@ -1,2 +0,0 @@\n\
+            -one\n\
+            -two\n\
+            diff --git a/vendor/lib b/vendor/lib\n\
+            index 1a2b3c4..5d6e7f8 160000\n\
+            --- a/vendor/lib\n\
+            +++ b/vendor/lib\n\
+            @@ -1 +1 @@\n\
+            -Subproject commit 1a2b3c4d5e6f7a8b9c0d1a2b3c4d5e6f7a8b9c0d\n\
+            +Subproject commit 5d6e7f8a9b0c1d2e3f4a5d6e7f8a9b0c1d2e3f4a\n";
+
+        assert_eq!(
+            describe_changes(diff),
+            "diff --git a/logo.png b/logo.png\n\
+            [binary file modified: logo.png]\n\
+            diff --git a/old.txt b/old.txt\n\
+            [deleted: old.txt, 2 lines]\n\
+            diff --git a/vendor/lib b/vendor/lib\n\
+            [submodule vendor/lib updated: 1a2b3c4..5d6e7f8]\n"
+        );
+    }
+
+    #[test]
+    fn notes_renames_and_mode_changes_above_hunks() {
+        let diff = "diff --git a/a.rs b/b.rs\n\
+            similarity index 100%\n\
+            rename from a.rs\n\
+            rename to b.rs\n\
+            diff --git a/run.sh b/run.sh\n\
+            old mode 100644\n\
+            new mode 100755\n\
+            index 1a2b3c4..5d6e7f8\n\
+            --- a/run.sh\n\
+            +++ b/run.sh\n\
+            @@ -1 +1 @@\n\
+            -echo hi\n\
+            +echo hello\n\
+            diff --git a/src/main.rs b/src/main.rs\n\
+            index 1a2b3c4..5d6e7f8 100644\n\
+            --- a/src/main.rs\n\
+            +++ b/src/main.rs\n\
+            @@ -1 +1 @@\n\
+            -fn main() {}\n\
+            +fn main() { run() }\n";
+
+        let described = describe_changes(diff);
+
+        assert!(described.starts_with(
+            "diff --git a/a.rs b/b.rs\n\
+            [renamed: a.rs -> b.rs, 100% similar, content unchanged]\n\
+            diff --git a/run.sh b/run.sh\n\
+            [mode changed: 100644 -> 100755, now executable]\n\
+            @@ -1 +1 @@\n"
+        ));
+        assert!(described.ends_with(&diff[diff.find("diff --git a/src").unwrap()..]));
+    }
+}
@@ -9,8 +9,17 @@
 //! - `config`: Manage persistent configuration
 //! - `pr`: Generate and create a pull request
 //! - `clean`: Remove fully merged local branches
+//! - `usage`: Show recorded token usage and cost
+//! - `prompt`: Inspect prompt templates
+//! - `auth`: Store and inspect API keys
+//! - `lint`: Check a commit message file against conventional-commit rules
+//! - `split`: Split staged changes into several focused commits
+//! - `hook`: Install the `prepare-commit-msg` hook for plain `git commit`
 
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+
+use crate::config::ProviderKind;
 
 /// Main CLI structure for Committer.
 #[derive(Parser)]
@@ -51,6 +60,10 @@ pub struct Cli {
     /// Show detailed operation logs (excluded files, truncation, etc.)
     #[arg(short = 'v', long)]
     pub verbose: bool,
+
+    /// Generate N alternative messages and pick one
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u8).range(1..=10))]
+    pub candidates: Option<u8>,
 }
 
 /// Available subcommands.
@@ -65,6 +78,27 @@ pub enum Commands {
     Pr(PrArgs),
     /// Analyze and clean up branches
     Clean(CleanArgs),
+    /// Show token usage and cost totals
+    Usage(UsageArgs),
+    /// Inspect prompt templates
+    Prompt {
+        #[command(subcommand)]
+        action: PromptAction,
+    },
+    /// Store and inspect API keys
+    Auth {
+        #[command(subcommand)]
+        action: AuthAction,
+    },
+    /// Split staged changes into several focused commits
+    Split(SplitArgs),
+    /// Check a commit message file (e.g. from a commit-msg hook)
+    Lint(LintArgs),
+    /// Generate messages for plain `git commit` through a git hook
+    Hook {
+        #[command(subcommand)]
+        action: HookAction,
+    },
 }
 
 /// Arguments for the `pr` subcommand.
@@ -95,6 +129,37 @@ pub struct PrArgs {
     pub model: Option<String>,
 }
 
+/// Arguments for the `split` subcommand.
+#[derive(Parser)]
+pub struct SplitArgs {
+    /// Create the proposed commits without confirmation
+    #[arg(short = 'y', long)]
+    pub yes: bool,
+
+    /// Show the proposed commits, don't create them
+    #[arg(short, long)]
+    pub dry_run: bool,
+
+    /// Show detailed operation logs
+    #[arg(short = 'v', long)]
+    pub verbose: bool,
+
+    /// Override model for this run
+    #[arg(short, long)]
+    pub model: Option<String>,
+}
+
+/// Arguments for the `lint` subcommand.
+#[derive(Parser)]
+pub struct LintArgs {
+    /// Commit message file, e.g. .git/COMMIT_EDITMSG
+    pub file: PathBuf,
+
+    /// Rewrite the file with fixable problems corrected
+    #[arg(long)]
+    pub fix: bool,
+}
+
 /// Arguments for the `clean` subcommand.
 #[derive(Parser)]
 pub struct CleanArgs {
@@ -107,29 +172,123 @@ pub struct CleanArgs {
     pub verbose: bool,
 }
 
+/// Arguments for the `usage` subcommand.
+#[derive(Parser)]
+pub struct UsageArgs {
+    /// Group totals by day, model, or repository
+    #[arg(long, value_enum, default_value_t = UsageGroup::Day)]
+    pub by: UsageGroup,
+
+    /// Only include the last N days
+    #[arg(long)]
+    pub days: Option<u32>,
+}
+
+/// How `committer usage` groups its totals.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum UsageGroup {
+    Day,
+    Model,
+    Repo,
+}
+
+/// Prompt subcommand actions.
+#[derive(Subcommand)]
+pub enum PromptAction {
+    /// Print the effective template for a prompt
+    Show {
+        /// Which prompt to show
+        #[arg(value_enum)]
+        kind: PromptKind,
+    },
+}
+
+/// The prompts committer sends, each backed by an overridable template.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum PromptKind {
+    /// Commit message generation
+    Commit,
+    /// Pull request title and description
+    Pr,
+    /// Branch alignment analysis
+    Branch,
+    /// Branch name suggestion
+    BranchName,
+    /// Summary of one part of a very large diff
+    Summary,
+    /// Grouping staged changes into focused commits
+    Split,
+}
+
+/// Authentication subcommand actions.
+#[derive(Subcommand)]
+pub enum AuthAction {
+    /// Store an API key (read without echo, or from stdin)
+    Login {
+        /// Provider the key is for (default: the configured provider)
+        #[arg(short, long, value_enum)]
+        provider: Option<ProviderKind>,
+
+        /// Store the key in the Secret Service keyring instead of the credentials file
+        #[arg(long)]
+        keyring: bool,
+    },
+    /// Remove a stored API key
+    Logout {
+        /// Provider whose key to remove (default: the configured provider)
+        #[arg(short, long, value_enum)]
+        provider: Option<ProviderKind>,
+    },
+    /// Show where each provider's API key comes from
+    Status,
+}
+
+/// Hook subcommand actions.
+#[derive(Subcommand)]
+pub enum HookAction {
+    /// Install the prepare-commit-msg hook in this repository
+    Install,
+    /// Remove the hook, restoring any hook it chained
+    Uninstall,
+    /// Generate into a commit message file (run by the hook)
+    Run {
+        /// Commit message file
+        file: PathBuf,
+        /// Source of the message: message, template, merge, squash, or commit
+        source: Option<String>,
+        /// Commit object name, for amends and -c/-C
+        sha: Option<String>,
+    },
+}
+
 /// Configuration subcommand actions.
 #[derive(Subcommand)]
 pub enum ConfigAction {
     /// Show current configuration
     Show,
-    /// Set auto-commit behavior
-    AutoCommit {
-        /// true or false
-        value: String,
+    /// Check config files for errors
+    Validate,
+    /// List every setting with its type and description
+    List {
+        /// Show default values instead of the current ones
+        #[arg(long)]
+        defaults: bool,
     },
-    /// Auto-commit after creating branch via 'b' option
-    CommitAfterBranch {
-        /// true or false
-        value: String,
+    /// Print the effective value of a setting
+    Get {
+        /// Setting name (see `config list`)
+        key: String,
     },
-    /// Set default model
-    Model {
-        /// Model identifier (e.g., x-ai/grok-4.1-fast:free)
+    /// Set a value in the global config file
+    Set {
+        /// Setting name (see `config list`)
+        key: String,
+        /// New value; lists are comma-separated
         value: String,
     },
-    /// Enable verbose operation logs by default
-    Verbose {
-        /// true or false
-        value: String,
+    /// Remove a setting from the global config file, restoring its default
+    Unset {
+        /// Setting name (see `config list`)
+        key: String,
     },
 }
@@ -4,29 +4,134 @@
 //! `~/.config/committer/config.toml`. It provides:
 //!
 //! - [`Config`] struct with all user preferences
-//! - Functions to [`load_config`] and [`save_config`]
-//! - API key retrieval via [`get_api_key`]
+//! - Functions to [`load_layered_config`] and [`save_global_table`]
+//! - LLM backend selection via [`ProviderKind`]
+//!
+//! API keys are not part of the config; see [`crate::auth`].
+//!
+//! # Layers
+//!
+//! The effective configuration is built from these layers, later ones
+//! overriding earlier ones key by key:
+//!
+//! 1. Built-in defaults
+//! 2. The global file (`~/.config/committer/config.toml`)
+//! 3. `.committer.toml` at the repository root
+//! 4. `COMMITTER_<KEY>` environment variables (e.g. `COMMITTER_MODEL`)
+//!
+//! `committer config set` only ever writes the global file, so settings
+//! changed there never pick up repo or environment overrides. The keys and
+//! their types are listed in [`crate::settings::SETTINGS`].
 //!
 //! # Example
 //!
 //! ```no_run
-//! use committer::config::{load_config, save_config};
+//! use committer::config::{load_global_table, save_global_table};
 //!
-//! let mut config = load_config();
-//! config.auto_commit = true;
-//! save_config(&config).unwrap();
+//! let mut table = load_global_table().unwrap();
+//! table.insert("auto_commit".to_string(), toml::Value::Boolean(true));
+//! save_global_table(&table).unwrap();
 //! ```
 
+use clap::ValueEnum;
+use console::style;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::git::get_repo_root;
+use crate::settings::SETTINGS;
 
 /// Default LLM model used for commit message generation.
 pub const DEFAULT_MODEL: &str = "google/gemini-3-flash-preview";
 
+/// LLM backend used for all API requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+#[value(rename_all = "lower")]
+pub enum ProviderKind {
+    /// OpenRouter (default), which proxies most hosted models.
+    #[default]
+    OpenRouter,
+    /// Any server implementing the OpenAI chat completions API.
+    OpenAi,
+    /// Anthropic's native Messages API.
+    Anthropic,
+    /// A local Ollama server using its native chat API.
+    Ollama,
+    /// A local llama.cpp server using its OpenAI-compatible API.
+    LlamaCpp,
+    /// Serves recorded responses from `fixtures_dir` instead of calling an API.
+    Replay,
+}
+
+impl ProviderKind {
+    /// Environment variable holding the API key for this provider.
+    pub fn api_key_env(&self) -> &'static str {
+        match self {
+            Self::OpenRouter => "OPENROUTER_API_KEY",
+            Self::OpenAi => "OPENAI_API_KEY",
+            Self::Anthropic => "ANTHROPIC_API_KEY",
+            Self::Ollama => "OLLAMA_API_KEY",
+            Self::LlamaCpp => "LLAMACPP_API_KEY",
+            Self::Replay => "COMMITTER_API_KEY",
+        }
+    }
+
+    /// Whether requests can't be made without an API key.
+    ///
+    /// Local servers run without authentication by default, so the key is
+    /// only sent when one is set.
+    pub fn requires_api_key(&self) -> bool {
+        !matches!(self, Self::Ollama | Self::LlamaCpp | Self::Replay)
+    }
+}
+
+impl std::fmt::Display for ProviderKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OpenRouter => write!(f, "openrouter"),
+            Self::OpenAi => write!(f, "openai"),
+            Self::Anthropic => write!(f, "anthropic"),
+            Self::Ollama => write!(f, "ollama"),
+            Self::LlamaCpp => write!(f, "llamacpp"),
+            Self::Replay => write!(f, "replay"),
+        }
+    }
+}
+
+/// How much context surrounds each change in the diff.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffContext {
+    /// Three lines around each change.
+    Lines,
+    /// The whole enclosing function, within a per-file cap.
+    Function,
+    /// Function context for files with small hunks, lines otherwise.
+    #[default]
+    Auto,
+}
+
+/// What to do when the diff contains a possible secret.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretScanMode {
+    /// Report matches but send the diff unchanged.
+    Warn,
+    /// Replace matches before sending.
+    #[default]
+    Redact,
+    /// Report matches and exit without sending.
+    Block,
+}
+
 /// User configuration for Committer.
 ///
 /// All fields have sensible defaults and are optional in the config file.
+/// Unknown keys are rejected, so a misspelled one isn't silently ignored.
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     /// Skip confirmation prompts and commit automatically.
     #[serde(default)]
@@ -36,63 +141,552 @@ pub struct Config {
     #[serde(default)]
     pub commit_after_branch: bool,
 
-    /// LLM model identifier for OpenRouter (e.g., "anthropic/claude-sonnet-4").
+    /// LLM model identifier (e.g., "anthropic/claude-sonnet-4" on OpenRouter).
     #[serde(default = "default_model")]
     pub model: String,
 
+    /// Backend that receives API requests.
+    #[serde(default)]
+    pub provider: ProviderKind,
+
+    /// Override for the provider's API base URL (e.g., an internal gateway).
+    ///
+    /// Required for the `openai` provider.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+
     /// Enable detailed logging of operations.
     #[serde(default)]
     pub verbose: bool,
+
+    /// Print a token and cost summary after each commit or PR.
+    #[serde(default = "default_true")]
+    pub show_usage: bool,
+
+    /// Seconds to wait for a connection to the API.
+    #[serde(default = "default_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+
+    /// Seconds to wait for each read from the API before giving up.
+    #[serde(default = "default_read_timeout_secs")]
+    pub read_timeout_secs: u64,
+
+    /// Retries per model for timeouts, rate limits, and gateway errors.
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+
+    /// Models tried in order when the primary model keeps failing.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fallback_models: Vec<String>,
+
+    /// Models asked in turn for `--candidates` messages. The selected model
+    /// is used for every candidate when empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub candidate_models: Vec<String>,
+
+    /// Directory of numbered response fixtures for the `replay` provider,
+    /// and where `record_fixtures` saves them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fixtures_dir: Option<PathBuf>,
+
+    /// Save every API request and response into `fixtures_dir`.
+    #[serde(default)]
+    pub record_fixtures: bool,
+
+    /// Extra gitignore-style patterns for files left out of the diff.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+
+    /// Context window of the model in tokens, overriding the provider's
+    /// model list.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_window: Option<u64>,
+
+    /// Upper bound on the tokens spent on the diff, however large the
+    /// context window.
+    #[serde(default = "default_max_diff_tokens")]
+    pub max_diff_tokens: u64,
+
+    /// Diffs larger than this many estimated tokens are summarized part by
+    /// part before the message is written. Off when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summarize_above_tokens: Option<u64>,
+
+    /// Context around each change: lines, function, or auto (per file).
+    #[serde(default)]
+    pub diff_context: DiffContext,
+
+    /// What to do with possible secrets in the diff: warn, redact, or block.
+    #[serde(default)]
+    pub secret_scan: SecretScanMode,
+
+    /// Regexes for secret scan false positives, matched against the value
+    /// or its file path.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub secret_allowlist: Vec<String>,
+
+    /// Learn the commit style from recent history and follow it.
+    #[serde(default = "default_true")]
+    pub learn_style: bool,
+
+    /// How many recent commits the style is learned from.
+    #[serde(default = "default_learn_style_commits")]
+    pub learn_style_commits: u32,
+
+    /// Derive allowed scopes from the workspace layout and staged paths.
+    #[serde(default = "default_true")]
+    pub infer_scopes: bool,
+
+    /// Scopes for paths, as `path=scope` entries. Paths may use globs and
+    /// take precedence over the detected layout.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scope_map: Vec<String>,
+
+    /// Check messages against conventional-commit rules before committing.
+    #[serde(default = "default_true")]
+    pub lint: bool,
+
+    /// Commit types the linter accepts.
+    #[serde(default = "default_lint_types")]
+    pub lint_types: Vec<String>,
+
+    /// Scopes the linter accepts. Any scope is allowed when empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lint_scopes: Vec<String>,
+
+    /// Longest subject line the linter accepts, in characters.
+    #[serde(default = "default_lint_subject_max_length")]
+    pub lint_subject_max_length: u32,
+
+    /// Body lines longer than this are wrapped. `0` disables the check.
+    #[serde(default = "default_lint_body_max_line_length")]
+    pub lint_body_max_line_length: u32,
 }
 
 fn default_model() -> String {
     DEFAULT_MODEL.to_string()
 }
 
+fn default_connect_timeout_secs() -> u64 {
+    10
+}
+
+fn default_read_timeout_secs() -> u64 {
+    60
+}
+
+fn default_max_retries() -> u32 {
+    2
+}
+
+fn default_max_diff_tokens() -> u64 {
+    75_000
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_lint_types() -> Vec<String> {
+    crate::lint::DEFAULT_TYPES
+        .iter()
+        .map(|t| t.to_string())
+        .collect()
+}
+
+fn default_learn_style_commits() -> u32 {
+    50
+}
+
+fn default_lint_subject_max_length() -> u32 {
+    72
+}
+
+fn default_lint_body_max_line_length() -> u32 {
+    100
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
             auto_commit: false,
             commit_after_branch: false,
             model: default_model(),
+            provider: ProviderKind::default(),
+            base_url: None,
             verbose: false,
+            show_usage: true,
+            connect_timeout_secs: default_connect_timeout_secs(),
+            read_timeout_secs: default_read_timeout_secs(),
+            max_retries: default_max_retries(),
+            fallback_models: Vec::new(),
+            candidate_models: Vec::new(),
+            fixtures_dir: None,
+            record_fixtures: false,
+            exclude: Vec::new(),
+            context_window: None,
+            max_diff_tokens: default_max_diff_tokens(),
+            summarize_above_tokens: None,
+            diff_context: DiffContext::default(),
+            secret_scan: SecretScanMode::default(),
+            secret_allowlist: Vec::new(),
+            learn_style: true,
+            learn_style_commits: default_learn_style_commits(),
+            infer_scopes: true,
+            scope_map: Vec::new(),
+            lint: true,
+            lint_types: default_lint_types(),
+            lint_scopes: Vec::new(),
+            lint_subject_max_length: default_lint_subject_max_length(),
+            lint_body_max_line_length: default_lint_body_max_line_length(),
         }
     }
 }
 
 /// Returns the path to the configuration file.
 ///
-/// Typically `~/.config/committer/config.toml` on Linux/macOS.
+/// Typically `~/.config/committer/config.toml` on Linux/macOS. The
+/// `COMMITTER_CONFIG` environment variable overrides it, which keeps tests
+/// and scripted runs isolated from the user's settings.
 pub fn config_path() -> PathBuf {
+    if let Some(path) = std::env::var_os("COMMITTER_CONFIG") {
+        return PathBuf::from(path);
+    }
     dirs::config_dir()
         .unwrap_or_else(|| PathBuf::from("."))
         .join("committer")
         .join("config.toml")
 }
 
-/// Loads configuration from disk, returning defaults if file doesn't exist.
-pub fn load_config() -> Config {
-    let path = config_path();
-    if path.exists() {
-        let contents = std::fs::read_to_string(&path).unwrap_or_default();
-        toml::from_str(&contents).unwrap_or_default()
-    } else {
-        Config::default()
+/// Name of the per-repository config file, at the repository root.
+pub const REPO_CONFIG_FILE: &str = ".committer.toml";
+
+/// Where a configuration value came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigLayer {
+    Default,
+    Global,
+    Repo,
+    /// Set by the named environment variable.
+    Env(String),
+}
+
+impl std::fmt::Display for ConfigLayer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Default => write!(f, "default"),
+            Self::Global => write!(f, "global"),
+            Self::Repo => write!(f, "repo"),
+            Self::Env(var) => write!(f, "{}", var),
+        }
+    }
+}
+
+/// The effective configuration along with the layer each key came from.
+pub struct LayeredConfig {
+    pub config: Config,
+    /// The repository's `.committer.toml`, if there is one.
+    pub repo_path: Option<PathBuf>,
+    sources: HashMap<String, ConfigLayer>,
+}
+
+impl LayeredConfig {
+    /// Returns the layer that set `key`.
+    pub fn source(&self, key: &str) -> ConfigLayer {
+        self.sources
+            .get(key)
+            .cloned()
+            .unwrap_or(ConfigLayer::Default)
+    }
+}
+
+/// Returns the path of the per-repository config file for `repo_root`.
+pub fn repo_config_path(repo_root: &Path) -> PathBuf {
+    repo_root.join(REPO_CONFIG_FILE)
+}
+
+/// A config file that exists but can't be used.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The file couldn't be read.
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The file isn't valid TOML, or a value has the wrong type.
+    Parse {
+        path: PathBuf,
+        /// 1-based line of the offending value, when known.
+        line: usize,
+        /// 1-based column of the offending value, when known.
+        column: usize,
+        message: String,
+    },
+    /// The files and environment are valid on their own but not together.
+    Merge { message: String },
+}
+
+impl ConfigError {
+    fn parse(path: &Path, contents: &str, error: toml::de::Error) -> Self {
+        let (line, column) = match error.span() {
+            Some(span) => {
+                let before = &contents[..span.start.min(contents.len())];
+                let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+                (
+                    before.matches('\n').count() + 1,
+                    before[line_start..].chars().count() + 1,
+                )
+            }
+            None => (0, 0),
+        };
+        Self::Parse {
+            path: path.to_path_buf(),
+            line,
+            column,
+            message: error.message().trim().replace('\n', "; "),
+        }
     }
 }
 
-/// Saves configuration to disk, creating parent directories if needed.
-pub fn save_config(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Read { path, source } => {
+                write!(f, "could not read {}: {}", path.display(), source)
+            }
+            Self::Parse {
+                path,
+                line: 0,
+                message,
+                ..
+            } => write!(f, "{}: {}", path.display(), message),
+            Self::Parse {
+                path,
+                line,
+                column,
+                message,
+            } => write!(f, "{}:{}:{}: {}", path.display(), line, column, message),
+            Self::Merge { message } => write!(f, "invalid configuration: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Reads the global config file as a table of the keys it sets.
+///
+/// Editing this table rather than a full [`Config`] keeps unset keys out of
+/// the file, so they keep following the built-in defaults.
+pub fn load_global_table() -> Result<toml::Table, ConfigError> {
+    read_table(&config_path())
+}
+
+/// Reports a config file that can't be used and exits.
+pub fn exit_with_config_error(error: &ConfigError) -> ! {
+    println!("{} Invalid configuration", style("✗").red());
+    println!("  {} {}", style("→").dim(), error);
+    println!(
+        "  {} Fix it, then run 'committer config validate'",
+        style("→").dim()
+    );
+    std::process::exit(1);
+}
+
+/// Keys a repository's `.committer.toml` may not set.
+///
+/// A cloned repository is untrusted: these decide where the API key and
+/// diff are sent, or whether secrets are redacted first.
+pub const REPO_FORBIDDEN_KEYS: &[&str] = &[
+    "provider",
+    "base_url",
+    "fixtures_dir",
+    "record_fixtures",
+    "secret_scan",
+    "secret_allowlist",
+];
+
+/// Removes the keys in [`REPO_FORBIDDEN_KEYS`] from a repository config
+/// table, returning the ones it set.
+fn strip_forbidden_repo_keys(table: &mut toml::Table) -> Vec<String> {
+    let forbidden: Vec<String> = table
+        .keys()
+        .filter(|key| REPO_FORBIDDEN_KEYS.contains(&key.as_str()))
+        .cloned()
+        .collect();
+    for key in &forbidden {
+        table.remove(key);
+    }
+    forbidden
+}
+
+/// Loads the global file, the repository's `.committer.toml`, and
+/// `COMMITTER_*` environment overrides, merged key by key.
+///
+/// Keys the repository file may not set are ignored with a warning.
+pub async fn load_layered_config() -> Result<LayeredConfig, ConfigError> {
+    let mut table = toml::Table::new();
+    let mut sources = HashMap::new();
+
+    for (key, value) in read_table(&config_path())? {
+        sources.insert(key.clone(), ConfigLayer::Global);
+        table.insert(key, value);
+    }
+
+    let repo_path = get_repo_root()
+        .await
+        .ok()
+        .map(|root| repo_config_path(&root))
+        .filter(|path| path.exists());
+    if let Some(path) = &repo_path {
+        let mut repo_table = read_table(path)?;
+        for key in strip_forbidden_repo_keys(&mut repo_table) {
+            eprintln!(
+                "{} Ignoring '{}' in {}: only the global config or environment can set it",
+                style("⚠").yellow(),
+                key,
+                path.display()
+            );
+        }
+        for (key, value) in repo_table {
+            sources.insert(key.clone(), ConfigLayer::Repo);
+            table.insert(key, value);
+        }
+    }
+
+    for setting in SETTINGS {
+        let var = format!("COMMITTER_{}", setting.key.to_uppercase());
+        let Ok(raw) = std::env::var(&var) else {
+            continue;
+        };
+        match setting.parse(&raw) {
+            Ok(value) => {
+                table.insert(setting.key.to_string(), value);
+                sources.insert(setting.key.to_string(), ConfigLayer::Env(var));
+            }
+            Err(e) => eprintln!("{} Ignoring {}: {}", style("⚠").yellow(), var, e),
+        }
+    }
+
+    let config = toml::Value::Table(table)
+        .try_into()
+        .map_err(|e: toml::de::Error| ConfigError::Merge {
+            message: e.message().trim().replace('\n', "; "),
+        })?;
+    Ok(LayeredConfig {
+        config,
+        repo_path,
+        sources,
+    })
+}
+
+/// Reads a config file as a table, checking it against [`Config`].
+///
+/// A missing file is an empty table.
+fn read_table(path: &Path) -> Result<toml::Table, ConfigError> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(toml::Table::new()),
+        Err(source) => {
+            return Err(ConfigError::Read {
+                path: path.to_path_buf(),
+                source,
+            })
+        }
+    };
+
+    // Deserializing into Config first reports type errors with their location
+    toml::from_str::<Config>(&contents)
+        .and_then(|_| toml::from_str::<toml::Table>(&contents))
+        .map_err(|e| ConfigError::parse(path, &contents, e))
+}
+
+/// Checks the global and repository config files.
+///
+/// Returns each file that exists with its error, if any.
+pub async fn validate_config_files() -> Vec<(PathBuf, Option<ConfigError>)> {
+    let mut paths = vec![config_path()];
+    if let Ok(root) = get_repo_root().await {
+        paths.push(repo_config_path(&root));
+    }
+
+    paths
+        .into_iter()
+        .filter(|path| path.exists())
+        .map(|path| {
+            let error = read_table(&path).err();
+            (path, error)
+        })
+        .collect()
+}
+
+/// Writes the global config file, creating parent directories if needed.
+///
+/// Refuses to overwrite an existing file that doesn't parse, so a typo
+/// never costs the user the rest of their settings.
+pub fn save_global_table(table: &toml::Table) -> Result<(), Box<dyn std::error::Error>> {
     let path = config_path();
+    if let Err(e) = read_table(&path) {
+        return Err(format!("Refusing to overwrite unparseable config ({})", e).into());
+    }
     if let Some(parent) = path.parent() {
         std::fs::create_dir_all(parent)?;
     }
-    let contents = toml::to_string_pretty(config)?;
+    let contents = toml::to_string_pretty(table)?;
     std::fs::write(&path, contents)?;
     Ok(())
 }
 
-/// Retrieves the OpenRouter API key from the `OPENROUTER_API_KEY` environment variable.
-pub fn get_api_key() -> Option<String> {
-    std::env::var("OPENROUTER_API_KEY").ok()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn repo_file_cannot_redirect_requests_or_disable_secret_scan() {
+        let mut table: toml::Table = toml::from_str(
+            "model = \"a\"\nbase_url = \"http://evil.example\"\nsecret_scan = \"warn\"\n",
+        )
+        .unwrap();
+
+        let mut ignored = strip_forbidden_repo_keys(&mut table);
+        ignored.sort();
+
+        assert_eq!(ignored, vec!["base_url", "secret_scan"]);
+        assert_eq!(table.len(), 1);
+        assert!(table.contains_key("model"));
+    }
+
+    #[test]
+    fn reports_parse_errors_with_location() {
+        let dir = TempDir::new("config");
+        let path = dir.join("config.toml");
+        std::fs::write(&path, "model = \"a\"\nmax_retries = \"three\"\n").unwrap();
+
+        let error = read_table(&path).unwrap_err();
+
+        match error {
+            ConfigError::Parse { line, column, .. } => assert_eq!((line, column), (2, 15)),
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        let dir = TempDir::new("config-unknown");
+        let path = dir.join("config.toml");
+        std::fs::write(&path, "verbose = true\nmodle = \"a\"\n").unwrap();
+
+        match read_table(&path).unwrap_err() {
+            ConfigError::Parse {
+                line,
+                column,
+                message,
+                ..
+            } => {
+                assert_eq!((line, column), (2, 1));
+                assert!(message.contains("unknown field `modle`"), "{}", message);
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+    }
 }
@@ -0,0 +1,131 @@
+//! Per-file choice between plain and function-context diffs.
+//!
+//! A plain diff shows three lines around each change, which often isn't
+//! enough to tell what a hunk is part of. `git diff --function-context`
+//! widens each hunk to its whole enclosing function instead, which helps
+//! for small edits and wastes tokens on large ones. Both diffs are taken
+//! and merged file by file according to `diff_context`:
+//!
+//! - `lines`: plain diffs only
+//! - `function`: function context wherever it stays under the cap
+//! - `auto` (default): function context for files whose hunks are all
+//!   small, plain diffs otherwise
+//!
+//! Either way a file keeps its plain diff when function context would make
+//! it larger than [`FUNCTION_CONTEXT_MAX_TOKENS`]. Hunk headers keep git's
+//! enclosing symbol names (`@@ -10,4 +10,6 @@ fn parse_config`) in both
+//! modes.
+
+use std::collections::HashMap;
+
+use crate::budget::{estimate_tokens, split_files};
+use crate::config::DiffContext;
+use crate::git::extract_filename_from_diff_header;
+
+/// Largest change, in added plus removed lines, of a hunk that `auto`
+/// treats as small.
+pub const AUTO_MAX_HUNK_LINES: usize = 20;
+
+/// Largest function-context section kept for a single file.
+pub const FUNCTION_CONTEXT_MAX_TOKENS: usize = 2_000;
+
+/// Added plus removed lines of each hunk in a file section.
+fn hunk_sizes(section: &str) -> Vec<usize> {
+    let mut sizes = Vec::new();
+    let mut in_hunk = false;
+    for line in section.lines() {
+        if line.starts_with("@@ ") {
+            sizes.push(0);
+            in_hunk = true;
+        } else if in_hunk && (line.starts_with('+') || line.starts_with('-')) {
+            if let Some(size) = sizes.last_mut() {
+                *size += 1;
+            }
+        }
+    }
+    sizes
+}
+
+/// Whether a file should use its function-context section.
+fn wants_function_context(mode: DiffContext, plain: &str, function: &str) -> bool {
+    let small_hunks = || {
+        let sizes = hunk_sizes(plain);
+        !sizes.is_empty() && sizes.iter().all(|&size| size <= AUTO_MAX_HUNK_LINES)
+    };
+    let within_cap = || estimate_tokens(function) <= FUNCTION_CONTEXT_MAX_TOKENS;
+
+    match mode {
+        DiffContext::Lines => false,
+        DiffContext::Function => within_cap(),
+        DiffContext::Auto => small_hunks() && within_cap(),
+    }
+}
+
+/// Merges a plain and a function-context diff of the same changes, picking
+/// one section per file. Returns the diff and how many files use function
+/// context.
+pub fn merge_function_context(plain: &str, function: &str, mode: DiffContext) -> (String, usize) {
+    let function_sections: HashMap<&str, &str> = split_files(function)
+        .into_iter()
+        .filter_map(|section| Some((extract_filename_from_diff_header(section)?, section)))
+        .collect();
+
+    let mut merged = String::with_capacity(plain.len());
+    let mut widened = 0;
+    for section in split_files(plain) {
+        let function_section = extract_filename_from_diff_header(section)
+            .and_then(|path| function_sections.get(path))
+            .filter(|function_section| wants_function_context(mode, section, function_section));
+        match function_section {
+            Some(function_section) => {
+                merged.push_str(function_section);
+                widened += 1;
+            }
+            None => merged.push_str(section),
+        }
+    }
+    (merged, widened)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn section(path: &str, changed: usize, context: usize) -> String {
+        let mut diff = format!(
+            "diff --git a/{0} b/{0}\n--- a/{0}\n+++ b/{0}\n@@ -1,{1} +1,{1} @@ fn run()\n",
+            path,
+            changed + context
+        );
+        diff.push_str(&" context\n".repeat(context));
+        diff.push_str(&"+changed\n".repeat(changed));
+        diff
+    }
+
+    #[test]
+    fn widens_only_files_with_small_hunks_in_auto_mode() {
+        let plain = [section("small.rs", 2, 3), section("large.rs", 40, 3)].concat();
+        let function = [section("small.rs", 2, 30), section("large.rs", 40, 30)].concat();
+
+        let (merged, widened) = merge_function_context(&plain, &function, DiffContext::Auto);
+
+        assert_eq!(widened, 1);
+        assert!(merged.starts_with(&section("small.rs", 2, 30)));
+        assert!(merged.ends_with(&section("large.rs", 40, 3)));
+    }
+
+    #[test]
+    fn keeps_plain_sections_over_the_cap() {
+        let plain = section("big_fn.rs", 2, 3);
+        let function = section("big_fn.rs", 2, 2_000);
+
+        let (merged, widened) = merge_function_context(&plain, &function, DiffContext::Function);
+
+        assert_eq!(widened, 0);
+        assert_eq!(merged, plain);
+        assert_eq!(
+            merge_function_context(&plain, &plain, DiffContext::Lines).1,
+            0
+        );
+    }
+}
@@ -0,0 +1,351 @@
+//! Rules for leaving files out of the diff sent to the LLM.
+//!
+//! Patterns use gitignore syntax and are collected from three places, in
+//! order:
+//!
+//! 1. [`DEFAULT_EXCLUDES`]: lock files, minified code, build directories
+//! 2. The `exclude` list in the config
+//! 3. `.committerignore` at the repository root
+//!
+//! The last matching pattern wins, so a later `!Cargo.lock` brings back a
+//! file excluded by default. Files marked `linguist-generated` or `-diff` in
+//! `.gitattributes` are excluded too, unless a negated pattern names them.
+//!
+//! # Pattern Syntax
+//!
+//! - `*` and `?` match within a path segment, `**` across segments
+//! - `[abc]`, `[a-z]`, `[!a-z]` match one character from a set
+//! - A pattern without a `/` matches the file or directory name at any depth
+//! - A leading or inner `/` anchors the pattern to the repository root
+//! - A trailing `/` only matches directories
+//! - `!` negates; `#` starts a comment
+
+use std::collections::HashSet;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::git::get_repo_root;
+
+/// Name of the per-repository ignore file, at the repository root.
+pub const IGNORE_FILE: &str = ".committerignore";
+
+/// Patterns excluded from diffs to reduce noise.
+pub const DEFAULT_EXCLUDES: &[&str] = &[
+    // Lock files
+    "Cargo.lock",
+    "package-lock.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "composer.lock",
+    "Gemfile.lock",
+    "poetry.lock",
+    "bun.lockb",
+    "uv.lock",
+    // Minified/generated
+    "*.min.js",
+    "*.min.css",
+    "*.map",
+    // Build directories (safety net if staged)
+    "target/",
+    "node_modules/",
+    "dist/",
+    "build/",
+    ".next/",
+    "__pycache__/",
+];
+
+/// A single gitignore-style pattern.
+struct Rule {
+    pattern: String,
+    negated: bool,
+    dir_only: bool,
+    anchored: bool,
+}
+
+impl Rule {
+    /// Parses one line of an ignore file; `None` for blanks and comments.
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let anchored = line.contains('/');
+        let pattern = line.strip_prefix('/').unwrap_or(line);
+        if pattern.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            pattern: pattern.to_string(),
+            negated,
+            dir_only,
+            anchored,
+        })
+    }
+
+    fn matches(&self, candidate: &str) -> bool {
+        let text = if self.anchored {
+            candidate
+        } else {
+            candidate.rsplit('/').next().unwrap_or(candidate)
+        };
+        glob_match(self.pattern.as_bytes(), text.as_bytes())
+    }
+}
+
+/// Decides which files in a diff are sent to the LLM.
+pub struct DiffFilter {
+    rules: Vec<Rule>,
+}
+
+impl DiffFilter {
+    /// Builds a filter from the given pattern lines, in precedence order.
+    pub fn from_patterns<'a>(patterns: impl IntoIterator<Item = &'a str>) -> Self {
+        Self {
+            rules: patterns.into_iter().filter_map(Rule::parse).collect(),
+        }
+    }
+
+    /// Builds the filter for the current repository: built-in defaults, then
+    /// `config_patterns`, then `.committerignore`.
+    pub async fn load(config_patterns: &[String]) -> Self {
+        let ignore_file = match get_repo_root().await {
+            Ok(root) => std::fs::read_to_string(root.join(IGNORE_FILE)).unwrap_or_default(),
+            Err(_) => String::new(),
+        };
+
+        Self::from_patterns(
+            DEFAULT_EXCLUDES
+                .iter()
+                .copied()
+                .chain(config_patterns.iter().map(String::as_str))
+                .chain(ignore_file.lines()),
+        )
+    }
+
+    /// Returns the decision of the last matching pattern, if any matched.
+    ///
+    /// `Some(true)` means excluded, `Some(false)` explicitly included.
+    fn decide(&self, path: &str) -> Option<bool> {
+        // A path is matched as a file, and each of its parent directories as a directory
+        let dirs: Vec<&str> = path.match_indices('/').map(|(i, _)| &path[..i]).collect();
+
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| {
+                dirs.iter().any(|dir| rule.matches(dir)) || (!rule.dir_only && rule.matches(path))
+            })
+            .map(|rule| !rule.negated)
+    }
+
+    /// Whether `path` should be left out, given whether `.gitattributes`
+    /// marks it as generated.
+    pub fn is_excluded(&self, path: &str, generated: bool) -> bool {
+        self.decide(path).unwrap_or(generated)
+    }
+}
+
+/// Returns the paths that `.gitattributes` marks as `linguist-generated`
+/// or `-diff`.
+///
+/// Paths are relative to the repository root, as in `git diff` output.
+/// Errors (e.g. outside a repository) yield an empty set.
+pub async fn generated_paths(paths: &[&str]) -> HashSet<String> {
+    let mut generated = HashSet::new();
+    if paths.is_empty() {
+        return generated;
+    }
+    let Ok(root) = get_repo_root().await else {
+        return generated;
+    };
+
+    let Ok(mut child) = Command::new("git")
+        .args(["check-attr", "-z", "--stdin", "linguist-generated", "diff"])
+        .current_dir(&root)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+    else {
+        return generated;
+    };
+
+    if let Some(mut stdin) = child.stdin.take() {
+        let input: Vec<u8> = paths
+            .iter()
+            .flat_map(|path| path.bytes().chain(std::iter::once(0)))
+            .collect();
+        if stdin.write_all(&input).await.is_err() {
+            return generated;
+        }
+    }
+    let Ok(output) = child.wait_with_output().await else {
+        return generated;
+    };
+
+    // With -z, output is a flat list of path, attribute, value triples
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let fields: Vec<&str> = stdout.split('\0').collect();
+    for triple in fields.chunks_exact(3) {
+        let marked = matches!(
+            (triple[1], triple[2]),
+            ("linguist-generated", "set" | "true") | ("diff", "unset")
+        );
+        if marked {
+            generated.insert(triple[0].to_string());
+        }
+    }
+    generated
+}
+
+/// Matches `text` against a gitignore-style glob.
+pub fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) if rest.first() == Some(&b'*') => {
+            let rest = &rest[1..];
+            match rest.strip_prefix(b"/") {
+                // `**/` matches zero or more whole directories
+                Some(after) => {
+                    glob_match(after, text)
+                        || text
+                            .iter()
+                            .enumerate()
+                            .filter(|(_, &c)| c == b'/')
+                            .any(|(i, _)| glob_match(after, &text[i + 1..]))
+                }
+                None => (0..=text.len()).any(|i| glob_match(rest, &text[i..])),
+            }
+        }
+        Some((b'*', rest)) => {
+            let segment_end = text.iter().position(|&c| c == b'/').unwrap_or(text.len());
+            (0..=segment_end).any(|i| glob_match(rest, &text[i..]))
+        }
+        Some((b'?', rest)) => match text.split_first() {
+            Some((&c, text)) if c != b'/' => glob_match(rest, text),
+            _ => false,
+        },
+        Some((b'[', rest)) => match (parse_class(rest), text.split_first()) {
+            (Some((matches, after)), Some((&c, text))) => {
+                c != b'/' && matches(c) && glob_match(after, text)
+            }
+            (Some(_), None) => false,
+            // No closing bracket: treat `[` literally
+            (None, _) => text.first() == Some(&b'[') && glob_match(rest, &text[1..]),
+        },
+        Some((b'\\', rest)) if !rest.is_empty() => {
+            text.first() == Some(&rest[0]) && glob_match(&rest[1..], &text[1..])
+        }
+        Some((&c, rest)) => text.first() == Some(&c) && glob_match(rest, &text[1..]),
+    }
+}
+
+/// Parses a `[...]` class body, returning a matcher and the rest of the pattern.
+fn parse_class(pattern: &[u8]) -> Option<(impl Fn(u8) -> bool + '_, &[u8])> {
+    let (negated, body_start) = match pattern.first() {
+        Some(b'!' | b'^') => (true, 1),
+        _ => (false, 0),
+    };
+    // A `]` right after the opening bracket is a literal member
+    let close = pattern
+        .iter()
+        .enumerate()
+        .skip(body_start + 1)
+        .find(|(_, &c)| c == b']')
+        .map(|(i, _)| i)?;
+    let body = &pattern[body_start..close];
+
+    let matches = move |c: u8| {
+        let mut found = false;
+        let mut i = 0;
+        while i < body.len() {
+            if i + 2 < body.len() && body[i + 1] == b'-' {
+                found |= (body[i]..=body[i + 2]).contains(&c);
+                i += 3;
+            } else {
+                found |= body[i] == c;
+                i += 1;
+            }
+        }
+        found != negated
+    };
+    Some((matches, &pattern[close + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn excluded(patterns: &[&str], path: &str) -> bool {
+        DiffFilter::from_patterns(patterns.iter().copied()).is_excluded(path, false)
+    }
+
+    #[test]
+    fn matches_globs() {
+        assert!(glob_match(b"*.pb.go", b"user.pb.go"));
+        assert!(!glob_match(b"*.go", b"api/user.go"));
+        assert!(glob_match(b"api/**/*.snap", b"api/a/b/view.snap"));
+        assert!(glob_match(b"api/**/*.snap", b"api/view.snap"));
+        assert!(glob_match(b"file[0-9].txt", b"file7.txt"));
+        assert!(!glob_match(b"file[!0-9].txt", b"file7.txt"));
+        assert!(glob_match(b"?.rs", b"a.rs"));
+    }
+
+    #[test]
+    fn applies_default_excludes() {
+        let filter = DiffFilter::from_patterns(DEFAULT_EXCLUDES.iter().copied());
+
+        assert!(filter.is_excluded("Cargo.lock", false));
+        assert!(filter.is_excluded("web/package-lock.json", false));
+        assert!(filter.is_excluded("app/static/app.min.js", false));
+        assert!(filter.is_excluded("target/debug/build.rs", false));
+        assert!(filter.is_excluded("crates/x/node_modules/y/index.js", false));
+        assert!(!filter.is_excluded("src/build.rs", false));
+        assert!(!filter.is_excluded("src/main.rs", false));
+    }
+
+    #[test]
+    fn anchors_patterns_with_slashes() {
+        assert!(excluded(&["/gen"], "gen/a.rs"));
+        assert!(!excluded(&["/gen"], "src/gen/a.rs"));
+        assert!(excluded(&["gen/"], "src/gen/a.rs"));
+        assert!(!excluded(&["gen/"], "src/gen"));
+        assert!(excluded(&["proto/*.pb.rs"], "proto/user.pb.rs"));
+        assert!(!excluded(&["proto/*.pb.rs"], "src/proto/user.pb.rs"));
+    }
+
+    #[test]
+    fn last_matching_pattern_wins() {
+        let patterns = [
+            "*.lock",
+            "!schema.lock",
+            "snapshots/",
+            "!snapshots/keep.snap",
+        ];
+
+        assert!(excluded(&patterns, "Cargo.lock"));
+        assert!(!excluded(&patterns, "db/schema.lock"));
+        assert!(excluded(&patterns, "tests/snapshots/a.snap"));
+        assert!(!excluded(&patterns, "snapshots/keep.snap"));
+    }
+
+    #[test]
+    fn negation_overrides_generated_attribute() {
+        let filter = DiffFilter::from_patterns(["!api.pb.go"]);
+
+        assert!(!filter.is_excluded("api.pb.go", true));
+        assert!(filter.is_excluded("other.pb.go", true));
+        assert!(!filter.is_excluded("main.go", false));
+    }
+}
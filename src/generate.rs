@@ -0,0 +1,179 @@
+//! Commit message generation shared by the commit and PR flows.
+
+use console::Term;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::io::Write;
+
+use crate::api::{
+    push_feedback, stream_commit_message, ApiClient, CommitMessageOptions, Message, Refinement,
+    Sampling,
+};
+use crate::lint::{review_message, LintRules, Review};
+use crate::ui::{prompt_commit, CommitAction};
+
+/// Generates a commit message and, when `rules` are given, lints it,
+/// regenerating for as long as the user asks to.
+///
+/// An empty message is returned as is; callers decide what it means.
+pub async fn generate_reviewed_message(
+    api: &ApiClient,
+    model: &str,
+    diff: &str,
+    files: &str,
+    mut options: CommitMessageOptions<'_>,
+    rules: Option<&LintRules>,
+    interactive: bool,
+) -> Result<String, Box<dyn std::error::Error>> {
+    loop {
+        let message = generate_commit_message(api, model, diff, files, options).await?;
+        let Some(rules) = rules.filter(|_| !message.is_empty()) else {
+            return Ok(message);
+        };
+        match review_message(message, rules, true, interactive) {
+            Review::Ready(message) => return Ok(message),
+            Review::Regenerate => options.refinement.sampling = Some(Sampling::varied()),
+        }
+    }
+}
+
+/// Streams a commit message under a spinner.
+pub async fn generate_commit_message(
+    api: &ApiClient,
+    model: &str,
+    diff: &str,
+    files: &str,
+    options: CommitMessageOptions<'_>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let term = Term::stdout();
+    let _ = term.hide_cursor();
+
+    let spinner = ProgressBar::new_spinner();
+    spinner.set_style(
+        ProgressStyle::default_spinner()
+            .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"])
+            .template("{spinner:.cyan} Generating commit message...")
+            .unwrap(),
+    );
+    spinner.enable_steady_tick(std::time::Duration::from_millis(80));
+
+    // Ensure spinner renders before starting API call
+    std::io::stdout().flush().ok();
+
+    let message_result = stream_commit_message(api, model, diff, files, &spinner, options).await;
+
+    let _ = term.show_cursor();
+    let message = message_result?;
+    if message.is_empty() {
+        spinner.finish_and_clear();
+    }
+    Ok(message)
+}
+
+/// Where the commit prompt left off in [`CommitRefiner::refine`].
+pub enum Refined {
+    /// Commit with this message, already reviewed.
+    Commit(String),
+    Cancel,
+    /// Create a branch for this message, then refine again.
+    CreateBranch(String),
+    /// The model returned an empty message.
+    Empty,
+}
+
+/// The commit prompt's loop of editing, regenerating, and feedback, shared
+/// by the commit and PR flows.
+///
+/// Feedback given in one [`refine`](Self::refine) call carries over to the
+/// next.
+pub struct CommitRefiner<'a> {
+    api: &'a ApiClient,
+    model: &'a str,
+    diff: &'a str,
+    files: &'a str,
+    options: CommitMessageOptions<'a>,
+    rules: Option<&'a LintRules>,
+    /// Earlier replies and feedback, sent along when refining
+    followups: Vec<Message>,
+}
+
+impl<'a> CommitRefiner<'a> {
+    pub fn new(
+        api: &'a ApiClient,
+        model: &'a str,
+        diff: &'a str,
+        files: &'a str,
+        options: CommitMessageOptions<'a>,
+        rules: Option<&'a LintRules>,
+    ) -> Self {
+        Self {
+            api,
+            model,
+            diff,
+            files,
+            options,
+            rules,
+            followups: Vec::new(),
+        }
+    }
+
+    /// Prompts with `message` until the user commits, cancels, or asks for
+    /// a branch (offered only with `show_branch_option`).
+    pub async fn refine(
+        &mut self,
+        mut message: String,
+        show_branch_option: bool,
+    ) -> Result<Refined, Box<dyn std::error::Error>> {
+        loop {
+            if message.is_empty() {
+                return Ok(Refined::Empty);
+            }
+            match prompt_commit(&message, show_branch_option) {
+                CommitAction::Commit(edited) => {
+                    // Edited messages get the same checks as generated ones
+                    if let Some(rules) = self.rules.filter(|_| edited != message) {
+                        match review_message(edited.clone(), rules, true, true) {
+                            Review::Ready(reviewed) if reviewed != edited => {
+                                message = reviewed;
+                                continue;
+                            }
+                            Review::Ready(_) => {}
+                            Review::Regenerate => {
+                                message = self.generate(Some(Sampling::varied())).await?;
+                                continue;
+                            }
+                        }
+                    }
+                    return Ok(Refined::Commit(edited));
+                }
+                CommitAction::Regenerate => {
+                    message = self.generate(Some(Sampling::varied())).await?;
+                }
+                CommitAction::Feedback(previous, instruction) => {
+                    push_feedback(&mut self.followups, &previous, &instruction);
+                    message = self.generate(None).await?;
+                }
+                CommitAction::Cancel => return Ok(Refined::Cancel),
+                CommitAction::CreateBranch(message) => return Ok(Refined::CreateBranch(message)),
+            }
+        }
+    }
+
+    /// Generates a message with the follow-ups so far; `sampling` varies a
+    /// regenerated one.
+    async fn generate(
+        &self,
+        sampling: Option<Sampling>,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let options = CommitMessageOptions {
+            refinement: Refinement {
+                followups: &self.followups,
+                sampling,
+            },
+            ..self.options
+        };
+        generate_reviewed_message(
+            self.api, self.model, self.diff, self.files, options, self.rules, true,
+        )
+        .await
+    }
+}
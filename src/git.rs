@@ -4,27 +4,39 @@
 //!
 //! - **Diff retrieval**: [`get_git_diff`], [`get_branch_diff`]
 //! - **Diff filtering**: Excludes lock files, minified code, build artifacts
-//! - **Diff truncation**: Limits size to stay within LLM token limits
-//! - **Status queries**: [`get_staged_files`], [`get_uncommitted_changes`]
+//! - **Status queries**: [`get_staged_files`], [`get_staged_paths`], [`get_uncommitted_changes`], [`get_repo_root`], [`get_git_path`], [`get_comment_char`]
 //! - **Branch operations**: [`get_current_branch`], [`create_and_switch_branch`]
-//! - **Commit operations**: [`run_git_commit`], [`stage_all_changes`]
+//! - **Commit operations**: [`run_git_commit`], [`stage_all_changes`], [`get_commit_messages`]
 //! - **Push operations**: [`push_branch_with_spinner`]
 //!
 //! # Diff Filtering
 //!
-//! Files matching [`EXCLUDED_FROM_DIFF`] patterns are automatically removed
-//! from diffs to reduce noise and token usage. This includes lock files,
-//! minified code, and build directories.
+//! Files excluded by a [`DiffFilter`] are removed from diffs to reduce noise
+//! and token usage: lock files, minified code, and build directories by
+//! default, plus configured and `.committerignore` patterns and files
+//! `.gitattributes` marks as generated. See [`crate::exclude`].
+//!
+//! Binary files, deletions, renames, mode changes, and submodule bumps are
+//! then replaced by short structured notes. See [`crate::changes`].
 //!
 //! # Size Limits
 //!
-//! Diffs are truncated at [`MAX_DIFF_CHARS`] (300KB) to stay within LLM
-//! context limits while preserving file headers for context.
+//! The diffs returned here are filtered but not size-limited. Before they
+//! are sent, [`crate::summarize::prepare_diff`] fits them to a token budget
+//! derived from the model's context window (see [`crate::budget`]), or
+//! summarizes them part by part when they are very large.
 
 use console::{style, Term};
 use indicatif::{ProgressBar, ProgressStyle};
+use std::collections::HashSet;
+use std::path::PathBuf;
 use tokio::process::Command;
 
+use crate::changes::describe_changes;
+use crate::config::DiffContext;
+use crate::context::merge_function_context;
+use crate::exclude::{generated_paths, DiffFilter};
+
 /// Checks if git is installed and accessible.
 ///
 /// Returns an error with a helpful message if git is not found.
@@ -53,56 +65,20 @@ pub async fn check_git_installed() -> Result<(), Box<dyn std::error::Error>> {
     }
 }
 
-/// File patterns excluded from diffs to reduce noise.
-pub const EXCLUDED_FROM_DIFF: &[&str] = &[
-    // Lock files
-    "Cargo.lock",
-    "package-lock.json",
-    "yarn.lock",
-    "pnpm-lock.yaml",
-    "composer.lock",
-    "Gemfile.lock",
-    "poetry.lock",
-    "bun.lockb",
-    "uv.lock",
-    // Minified/generated
-    ".min.js",
-    ".min.css",
-    ".map",
-    // Build directories (safety net if staged)
-    "target/",
-    "node_modules/",
-    "dist/",
-    "build/",
-    ".next/",
-    "__pycache__/",
-];
-
-/// Maximum diff size in characters before truncation.
+/// Returns the subset of `paths` that `filter` leaves out of the diff.
 ///
-/// Set to 300KB to stay within typical LLM context limits while leaving
-/// room for the prompt and response.
-pub const MAX_DIFF_CHARS: usize = 300_000;
-
-/// Checks if a file should be excluded from the diff based on [`EXCLUDED_FROM_DIFF`] patterns.
-pub fn should_exclude_from_diff(filename: &str) -> bool {
-    EXCLUDED_FROM_DIFF.iter().any(|pattern| {
-        if pattern.ends_with('/') {
-            // Directory pattern - check if file is inside this directory
-            let dir_name = pattern.trim_end_matches('/');
-            filename.contains(&format!("/{}/", dir_name))
-                || filename.starts_with(&format!("{}/", dir_name))
-        } else if pattern.starts_with('.') {
-            // Extension pattern
-            filename.ends_with(pattern)
-        } else {
-            // Exact filename match
-            filename.ends_with(pattern) || filename.ends_with(&format!("/{}", pattern))
-        }
-    })
+/// Consults `.gitattributes` for generated files in one `git check-attr` call.
+pub async fn excluded_paths(paths: &[&str], filter: &DiffFilter) -> HashSet<String> {
+    let generated = generated_paths(paths).await;
+    paths
+        .iter()
+        .filter(|path| filter.is_excluded(path, generated.contains(**path)))
+        .map(|path| path.to_string())
+        .collect()
 }
 
-fn extract_filename_from_diff_header(header: &str) -> Option<&str> {
+/// Extracts the path from a per-file diff section's `diff --git` line.
+pub fn extract_filename_from_diff_header(header: &str) -> Option<&str> {
     header
         .lines()
         .next()
@@ -110,44 +86,40 @@ fn extract_filename_from_diff_header(header: &str) -> Option<&str> {
         .and_then(|rest| rest.split(" b/").next())
 }
 
-/// Removes excluded files from a diff based on [`EXCLUDED_FROM_DIFF`] patterns.
+/// Removes files excluded by `filter` from a diff.
 ///
 /// In verbose mode, prints excluded files to stderr.
-pub fn filter_excluded_diffs(diff: &str, verbose: bool) -> String {
+pub async fn filter_excluded_diffs(diff: &str, filter: &DiffFilter, verbose: bool) -> String {
     if diff.is_empty() {
         return diff.to_string();
     }
 
-    let mut chunks: Vec<&str> = diff.split("\ndiff --git ").collect();
-    if chunks.is_empty() {
-        return diff.to_string();
-    }
-
-    let first = chunks.remove(0);
-    let mut file_diffs: Vec<String> = vec![];
-    let mut excluded_files: Vec<String> = vec![];
-
-    if !first.is_empty() {
-        if let Some(filename) = extract_filename_from_diff_header(&format!("diff --git {}", first))
-        {
-            if should_exclude_from_diff(filename) {
-                excluded_files.push(filename.to_string());
+    // Split into per-file chunks, each starting with its "diff --git" header
+    let chunks: Vec<String> = diff
+        .split("\ndiff --git ")
+        .enumerate()
+        .map(|(i, chunk)| {
+            if i == 0 {
+                chunk.to_string()
             } else {
-                file_diffs.push(first.to_string());
+                format!("\ndiff --git {}", chunk)
             }
-        } else {
-            file_diffs.push(first.to_string());
-        }
-    }
+        })
+        .collect();
+    let filenames: Vec<Option<&str>> = chunks
+        .iter()
+        .map(|chunk| extract_filename_from_diff_header(chunk.trim_start_matches('\n')))
+        .collect();
 
-    for chunk in chunks {
-        let full_header = format!("diff --git {}", chunk);
-        if let Some(filename) = extract_filename_from_diff_header(&full_header) {
-            if should_exclude_from_diff(filename) {
-                excluded_files.push(filename.to_string());
-            } else {
-                file_diffs.push(format!("\ndiff --git {}", chunk));
-            }
+    let paths: Vec<&str> = filenames.iter().flatten().copied().collect();
+    let excluded = excluded_paths(&paths, filter).await;
+
+    let mut file_diffs: Vec<&str> = vec![];
+    let mut excluded_files: Vec<&str> = vec![];
+    for (chunk, filename) in chunks.iter().zip(&filenames) {
+        match filename {
+            Some(filename) if excluded.contains(*filename) => excluded_files.push(filename),
+            _ => file_diffs.push(chunk),
         }
     }
 
@@ -161,137 +133,158 @@ pub fn filter_excluded_diffs(diff: &str, verbose: bool) -> String {
     file_diffs.join("")
 }
 
-/// Truncates a diff to fit within token limits while preserving useful context.
-/// Keeps the beginning (file headers, context) and end (recent changes).
-pub fn truncate_diff(diff: &str, verbose: bool) -> String {
-    if diff.len() <= MAX_DIFF_CHARS {
-        return diff.to_string();
-    }
+async fn run_git_diff(args: &[&str]) -> Result<String, Box<dyn std::error::Error>> {
+    let output = Command::new("git").args(args).output().await?;
 
-    // Split into file chunks to truncate more intelligently
-    let mut chunks: Vec<&str> = diff.split("\ndiff --git ").collect();
-
-    if chunks.is_empty() {
-        // Fallback: simple truncation with middle cut
-        let keep_each = MAX_DIFF_CHARS / 2;
-        let start = &diff[..keep_each];
-        let end = &diff[diff.len() - keep_each..];
-        if verbose {
-            eprintln!(
-                "— Diff truncated: {} chars removed (fallback mode)",
-                diff.len() - MAX_DIFF_CHARS
-            );
-        }
-        return format!(
-            "{}\n\n[... {} characters truncated ...]\n\n{}",
-            start,
-            diff.len() - MAX_DIFF_CHARS,
-            end
-        );
-    }
-
-    // Reconstruct with "diff --git " prefix for all but first chunk
-    let first = chunks.remove(0);
-    let mut file_diffs: Vec<String> = vec![first.to_string()];
-    for chunk in chunks {
-        file_diffs.push(format!("diff --git {}", chunk));
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("git diff failed: {}", stderr).into());
     }
 
-    // Try to fit as many complete file diffs as possible
-    let mut result = String::new();
-    let mut total_len = 0;
-    let mut included = 0;
-
-    for file_diff in &file_diffs {
-        let chunk_len = file_diff.len();
-        // Reserve space for truncation notice
-        if total_len + chunk_len + 200 > MAX_DIFF_CHARS {
-            break;
-        }
-        result.push_str(file_diff);
-        result.push('\n');
-        total_len += chunk_len + 1;
-        included += 1;
-    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
 
-    if included < file_diffs.len() {
-        if verbose {
-            eprintln!(
-                "— Diff truncated: showing {}/{} files ({} KB limit)",
-                included,
-                file_diffs.len(),
-                MAX_DIFF_CHARS / 1024
-            );
+/// Runs `git diff` with `args` and prepares the result for the LLM.
+///
+/// Takes a function-context diff too unless `context` is `lines`, merges
+/// the two per file with [`merge_function_context`], then applies
+/// [`filter_excluded_diffs`] and [`describe_changes`].
+async fn diff_for_llm(
+    args: &[&str],
+    filter: &DiffFilter,
+    context: DiffContext,
+    verbose: bool,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let diff = if context == DiffContext::Lines {
+        run_git_diff(args).await?
+    } else {
+        let function_args: Vec<&str> = args
+            .iter()
+            .copied()
+            .chain(std::iter::once("--function-context"))
+            .collect();
+        let (plain, function) = tokio::join!(run_git_diff(args), run_git_diff(&function_args));
+        let (merged, widened) = merge_function_context(&plain?, &function?, context);
+        if verbose && widened > 0 {
+            eprintln!("— Function context: {} files", widened);
         }
-        result.push_str(&format!(
-            "\n[... diff truncated: showing {}/{} files to fit context limit ...]\n",
-            included,
-            file_diffs.len()
-        ));
-    }
+        merged
+    };
 
-    result
+    let filtered = filter_excluded_diffs(&diff, filter, verbose).await;
+    Ok(describe_changes(&filtered))
 }
 
-/// Retrieves the git diff, filtered and truncated for LLM consumption.
+/// Retrieves the git diff, prepared for LLM consumption.
 ///
-/// Applies [`filter_excluded_diffs`] and [`truncate_diff`] automatically.
+/// Context follows `context` per file (see [`crate::context`]), and
+/// [`filter_excluded_diffs`] and [`describe_changes`] are applied
+/// automatically.
 pub async fn get_git_diff(
     staged_only: bool,
+    filter: &DiffFilter,
+    context: DiffContext,
     verbose: bool,
 ) -> Result<String, Box<dyn std::error::Error>> {
-    let args = if staged_only {
-        vec!["diff", "--staged"]
+    let args: &[&str] = if staged_only {
+        &["diff", "--staged"]
     } else {
-        vec!["diff", "HEAD"]
+        &["diff", "HEAD"]
     };
+    diff_for_llm(args, filter, context, verbose).await
+}
 
-    let output = Command::new("git").args(&args).output().await?;
+/// One entry of `git diff --raw` output.
+struct RawEntry<'a> {
+    status: &'a str,
+    old_mode: &'a str,
+    new_mode: &'a str,
+    /// Source path of a rename or copy.
+    from: Option<&'a str>,
+    path: &'a str,
+}
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("git diff failed: {}", stderr).into());
-    }
+fn parse_raw_entry(line: &str) -> Option<RawEntry<'_>> {
+    // :<old mode> <new mode> <old sha> <new sha> <status>\t<path>[\t<new path>]
+    let (meta, paths) = line.strip_prefix(':')?.split_once('\t')?;
+    let fields: Vec<&str> = meta.split(' ').collect();
+    let [old_mode, new_mode, _, _, status] = fields[..] else {
+        return None;
+    };
+    let (from, path) = match paths.split_once('\t') {
+        Some((from, to)) => (Some(from), to),
+        None => (None, paths),
+    };
+    Some(RawEntry {
+        status,
+        old_mode,
+        new_mode,
+        from,
+        path,
+    })
+}
+
+/// Formats `git diff --raw` output as a status and path per line.
+///
+/// Renames and copies show both paths, and submodules, mode changes, and
+/// files excluded by `filter` are annotated. Returns the lines and how many
+/// were excluded.
+async fn annotate_changed_files(raw_output: &str, filter: &DiffFilter) -> (Vec<String>, usize) {
+    let entries: Vec<Option<RawEntry>> = raw_output.lines().map(parse_raw_entry).collect();
+    let paths: Vec<&str> = entries.iter().flatten().map(|entry| entry.path).collect();
+    let excluded = excluded_paths(&paths, filter).await;
+    let mut excluded_count = 0;
+
+    let annotated = raw_output
+        .lines()
+        .zip(&entries)
+        .map(|(line, entry)| {
+            let Some(entry) = entry else {
+                return line.to_string();
+            };
+            let mut annotated = match entry.from {
+                Some(from) => format!("{}\t{} -> {}", entry.status, from, entry.path),
+                None => format!("{}\t{}", entry.status, entry.path),
+            };
+            if entry.old_mode == "160000" || entry.new_mode == "160000" {
+                annotated.push_str(" [submodule]");
+            } else if entry.old_mode != entry.new_mode
+                && entry.old_mode != "000000"
+                && entry.new_mode != "000000"
+            {
+                annotated.push_str(&format!(" [mode {} -> {}]", entry.old_mode, entry.new_mode));
+            }
+            if excluded.contains(entry.path) {
+                excluded_count += 1;
+                annotated.push_str(" [excluded from diff]");
+            }
+            annotated
+        })
+        .collect();
 
-    let diff = String::from_utf8_lossy(&output.stdout).to_string();
-    let filtered_diff = filter_excluded_diffs(&diff, verbose);
-    Ok(truncate_diff(&filtered_diff, verbose))
+    (annotated, excluded_count)
 }
 
-/// Returns a list of staged files with their status (M/A/D).
+/// Returns a list of staged files with their status (M/A/D/R).
 ///
-/// Excluded files are annotated with `[excluded from diff]`.
-pub async fn get_staged_files(verbose: bool) -> Result<String, Box<dyn std::error::Error>> {
+/// Renames show both paths; submodules, mode changes, and excluded files
+/// are annotated (e.g. `[excluded from diff]`).
+pub async fn get_staged_files(
+    filter: &DiffFilter,
+    verbose: bool,
+) -> Result<String, Box<dyn std::error::Error>> {
     let output = Command::new("git")
-        .args(["diff", "--staged", "--name-status"])
+        .args(["diff", "--staged", "--raw"])
         .output()
         .await?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("git diff --name-status failed: {}", stderr).into());
+        return Err(format!("git diff --raw failed: {}", stderr).into());
     }
 
     let raw_output = String::from_utf8_lossy(&output.stdout).to_string();
-    let mut excluded_count = 0;
-
-    let annotated: Vec<String> = raw_output
-        .lines()
-        .map(|line| {
-            let parts: Vec<&str> = line.splitn(2, '\t').collect();
-            if parts.len() == 2 {
-                let filename = parts[1];
-                if should_exclude_from_diff(filename) {
-                    excluded_count += 1;
-                    format!("{}\t{} [excluded from diff]", parts[0], filename)
-                } else {
-                    line.to_string()
-                }
-            } else {
-                line.to_string()
-            }
-        })
-        .collect();
+    let (annotated, excluded_count) = annotate_changed_files(&raw_output, filter).await;
 
     if verbose {
         let total = annotated.len();
@@ -304,6 +297,25 @@ pub async fn get_staged_files(verbose: bool) -> Result<String, Box<dyn std::erro
     Ok(annotated.join("\n"))
 }
 
+/// Returns the paths of staged files, relative to the repository root.
+pub async fn get_staged_paths() -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let output = Command::new("git")
+        .args(["diff", "--staged", "--name-only", "-z"])
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("git diff --name-only failed: {}", stderr).into());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .split('\0')
+        .filter(|path| !path.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
 /// Creates a git commit with the given message.
 pub async fn run_git_commit(message: &str) -> Result<(), Box<dyn std::error::Error>> {
     let output = Command::new("git")
@@ -331,6 +343,62 @@ pub async fn stage_all_changes() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Returns the absolute path of the repository's top-level directory.
+pub async fn get_repo_root() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--show-toplevel"])
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("git rev-parse failed: {}", stderr).into());
+    }
+
+    Ok(PathBuf::from(
+        String::from_utf8_lossy(&output.stdout).trim(),
+    ))
+}
+
+/// Resolves a path inside `.git`, e.g. `hooks`, honoring settings such as
+/// `core.hooksPath` and linked worktrees.
+pub async fn get_git_path(name: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--git-path", name])
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("git rev-parse failed: {}", stderr).into());
+    }
+
+    Ok(PathBuf::from(
+        String::from_utf8_lossy(&output.stdout).trim(),
+    ))
+}
+
+/// Returns the prefix of comment lines in commit message files:
+/// `core.commentChar`, or `#` when it's unset or `auto`.
+pub async fn get_comment_char() -> String {
+    let output = Command::new("git")
+        .args(["config", "--get", "core.commentChar"])
+        .output()
+        .await;
+
+    match output {
+        Ok(o) if o.status.success() => {
+            let value = String::from_utf8_lossy(&o.stdout).trim().to_string();
+            if value.is_empty() || value == "auto" {
+                "#".to_string()
+            } else {
+                value
+            }
+        }
+        _ => "#".to_string(),
+    }
+}
+
 /// Returns the name of the current git branch.
 pub async fn get_current_branch() -> Result<String, Box<dyn std::error::Error>> {
     let output = Command::new("git")
@@ -375,6 +443,30 @@ pub async fn get_recent_commits(limit: usize) -> Result<String, Box<dyn std::err
     Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
 }
 
+/// Returns the full messages of the last `limit` non-merge commits, newest
+/// first. Empty when there is no history yet.
+pub async fn get_commit_messages(limit: usize) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let output = Command::new("git")
+        .args([
+            "log",
+            &format!("-{}", limit),
+            "--no-merges",
+            "--format=%B%x00",
+        ])
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        return Ok(Vec::new());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .split('\0')
+        .map(|message| message.trim().to_string())
+        .filter(|message| !message.is_empty())
+        .collect())
+}
+
 /// Check if a branch exists and has a merge base with HEAD
 pub async fn branch_has_merge_base(branch: &str) -> bool {
     let output = Command::new("git")
@@ -554,24 +646,16 @@ pub async fn push_branch_with_spinner(branch: &str) -> Result<(), Box<dyn std::e
     Ok(())
 }
 
-/// Returns the diff between the base branch and HEAD (for PR generation).
+/// Returns the diff between the base branch and HEAD (for PR generation),
+/// prepared like [`get_git_diff`].
 pub async fn get_branch_diff(
     base: &str,
+    filter: &DiffFilter,
+    context: DiffContext,
     verbose: bool,
 ) -> Result<String, Box<dyn std::error::Error>> {
-    let output = Command::new("git")
-        .args(["diff", &format!("{}...HEAD", base)])
-        .output()
-        .await?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("git diff failed: {}", stderr).into());
-    }
-
-    let diff = String::from_utf8_lossy(&output.stdout).to_string();
-    let filtered_diff = filter_excluded_diffs(&diff, verbose);
-    Ok(truncate_diff(&filtered_diff, verbose))
+    let range = format!("{}...HEAD", base);
+    diff_for_llm(&["diff", &range], filter, context, verbose).await
 }
 
 /// Returns commit subjects between base branch and HEAD.
@@ -595,41 +679,25 @@ pub async fn get_branch_commits(base: &str) -> Result<Vec<String>, Box<dyn std::
     Ok(commits)
 }
 
-/// Returns files changed between base branch and HEAD with status.
+/// Returns files changed between base branch and HEAD with status,
+/// annotated like [`get_staged_files`].
 pub async fn get_pr_changed_files(
     base: &str,
+    filter: &DiffFilter,
     verbose: bool,
 ) -> Result<String, Box<dyn std::error::Error>> {
     let output = Command::new("git")
-        .args(["diff", "--name-status", &format!("{}...HEAD", base)])
+        .args(["diff", "--raw", &format!("{}...HEAD", base)])
         .output()
         .await?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("git diff --name-status failed: {}", stderr).into());
+        return Err(format!("git diff --raw failed: {}", stderr).into());
     }
 
     let raw_output = String::from_utf8_lossy(&output.stdout).to_string();
-    let mut excluded_count = 0;
-
-    let annotated: Vec<String> = raw_output
-        .lines()
-        .map(|line| {
-            let parts: Vec<&str> = line.splitn(2, '\t').collect();
-            if parts.len() == 2 {
-                let filename = parts[1];
-                if should_exclude_from_diff(filename) {
-                    excluded_count += 1;
-                    format!("{}\t{} [excluded from diff]", parts[0], filename)
-                } else {
-                    line.to_string()
-                }
-            } else {
-                line.to_string()
-            }
-        })
-        .collect();
+    let (annotated, excluded_count) = annotate_changed_files(&raw_output, filter).await;
 
     if verbose && excluded_count > 0 {
         eprintln!(
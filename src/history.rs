@@ -0,0 +1,534 @@
+//! Commit style learned from repository history.
+//!
+//! The built-in commit prompt asks for conventional commits, which is wrong
+//! for repositories that use Gitmoji, ticket prefixes, or plain imperative
+//! sentences. Before generating, the last `learn_style_commits` messages are
+//! sampled to work out the convention they follow: types and scopes in use,
+//! body style, subject casing, and trailers. That profile is added to the
+//! commit prompt as `{style}`, with a few real messages as examples.
+//!
+//! Repositories with fewer than five commits have no profile. Profiles are
+//! cached for a day in the repository's git directory, at
+//! `.git/committer/style.json`.
+
+use regex_lite::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::LazyLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::Config;
+use crate::git::{get_commit_messages, get_git_path};
+use crate::lint::{LintRules, DEFAULT_TYPES, IGNORED_PREFIXES};
+
+/// Fewest commits a profile is learned from.
+const MIN_COMMITS: usize = 5;
+
+/// Real messages included in the prompt.
+const MAX_EXAMPLES: usize = 3;
+
+/// Longer messages aren't used as examples.
+const MAX_EXAMPLE_CHARS: usize = 600;
+
+/// How long a cached profile is trusted, in seconds.
+const CACHE_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// How commit subjects are written.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum Convention {
+    /// `type(scope): description`
+    Conventional,
+    /// A leading emoji or `:shortcode:`.
+    Gitmoji,
+    /// A leading ticket ID such as `PROJ-123`.
+    TicketPrefix,
+    /// A plain sentence.
+    Plain,
+}
+
+/// How commit bodies are written.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum BodyStyle {
+    /// Most commits have no body.
+    None,
+    Bullets,
+    Paragraphs,
+}
+
+/// The commit conventions a repository follows.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StyleProfile {
+    pub convention: Convention,
+    /// Conventional types, most used first.
+    pub types: Vec<String>,
+    /// Conventional scopes used more than once, most used first.
+    pub scopes: Vec<String>,
+    pub body: BodyStyle,
+    /// Whether subjects start with a capital letter after any prefix.
+    pub capitalized: bool,
+    /// Trailer keys on at least a third of commits, e.g. `Signed-off-by`.
+    pub trailers: Vec<String>,
+    pub examples: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StyleCache {
+    learned_at: u64,
+    commits: u32,
+    profile: Option<StyleProfile>,
+}
+
+/// A conventional-commit subject: type, optional scope, description.
+static CONVENTIONAL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^([A-Za-z]+)(?:\(([^)]+)\))?!?: (.*)$").unwrap());
+
+/// A Gitmoji shortcode such as `:bug:`.
+static SHORTCODE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^:[a-z0-9_+-]+:\s*").unwrap());
+
+/// A ticket key such as `PROJ-12` or `[PROJ-12]`.
+static TICKET: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\[?[A-Z][A-Z0-9]+-\d+\]?:?\s+").unwrap());
+
+/// A trailer line such as `Signed-off-by: A <a@x>`.
+static TRAILER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^([A-Za-z][A-Za-z0-9-]*): \S").unwrap());
+
+/// Splits a subject into its convention and the description after any
+/// prefix. A conventional subject also yields its type and scope.
+fn classify_subject(subject: &str) -> (Convention, &str, Option<(String, Option<String>)>) {
+    if let Some(m) = SHORTCODE.find(subject) {
+        return (Convention::Gitmoji, &subject[m.end()..], None);
+    }
+    if let Some(first) = subject.chars().next() {
+        if !first.is_ascii() && !first.is_alphanumeric() {
+            let rest = subject[first.len_utf8()..].trim_start_matches(|c: char| !c.is_ascii());
+            return (Convention::Gitmoji, rest.trim_start(), None);
+        }
+    }
+    if let Some(m) = TICKET.find(subject) {
+        return (Convention::TicketPrefix, &subject[m.end()..], None);
+    }
+    if let Some(caps) = CONVENTIONAL.captures(subject) {
+        let commit_type = caps.get(1).map(|m| m.as_str()).unwrap_or_default();
+        let known = DEFAULT_TYPES.contains(&commit_type.to_lowercase().as_str());
+        if known || commit_type.chars().all(|c| c.is_ascii_lowercase()) {
+            let scope = caps.get(2).map(|m| m.as_str().to_string());
+            let description = caps.get(3).map(|m| m.as_str()).unwrap_or_default();
+            return (
+                Convention::Conventional,
+                description,
+                Some((commit_type.to_lowercase(), scope)),
+            );
+        }
+    }
+    (Convention::Plain, subject, None)
+}
+
+/// Splits the body of `message` into its text and trailer keys.
+fn split_body(message: &str) -> (String, Vec<String>) {
+    let body = message
+        .split_once("\n\n")
+        .map(|(_, body)| body.trim())
+        .unwrap_or("");
+
+    let mut paragraphs: Vec<&str> = body.split("\n\n").collect();
+    let mut keys = Vec::new();
+    if let Some(last) = paragraphs.last() {
+        let lines: Vec<&str> = last.lines().collect();
+        if !lines.is_empty() && lines.iter().all(|line| TRAILER.is_match(line)) {
+            keys = lines
+                .iter()
+                .filter_map(|line| TRAILER.captures(line))
+                .filter_map(|caps| caps.get(1).map(|m| m.as_str().to_string()))
+                .collect();
+            paragraphs.pop();
+        }
+    }
+    (paragraphs.join("\n\n").trim().to_string(), keys)
+}
+
+/// Items of `counts` seen at least `min` times, most seen first.
+fn by_frequency(counts: HashMap<String, usize>, min: usize) -> Vec<String> {
+    let mut items: Vec<(String, usize)> = counts.into_iter().filter(|(_, n)| *n >= min).collect();
+    items.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    items.into_iter().map(|(item, _)| item).collect()
+}
+
+/// Works out the style of `messages`, newest first.
+///
+/// Returns `None` when there are too few messages to tell.
+pub fn infer_style(messages: &[String]) -> Option<StyleProfile> {
+    let messages: Vec<&String> = messages
+        .iter()
+        .filter(|message| !IGNORED_PREFIXES.iter().any(|p| message.starts_with(p)))
+        .collect();
+    if messages.len() < MIN_COMMITS {
+        return None;
+    }
+
+    let mut conventions: HashMap<Convention, usize> = HashMap::new();
+    let mut types: HashMap<String, usize> = HashMap::new();
+    let mut scopes: HashMap<String, usize> = HashMap::new();
+    let mut trailers: HashMap<String, usize> = HashMap::new();
+    let (mut capitalized, mut lowercase) = (0, 0);
+    let (mut bodies, mut bulleted) = (0, 0);
+    let mut classified = Vec::new();
+
+    for message in &messages {
+        let subject = message.lines().next().unwrap_or_default().trim();
+        let (convention, description, header) = classify_subject(subject);
+        *conventions.entry(convention).or_default() += 1;
+        if let Some((commit_type, scope)) = header {
+            *types.entry(commit_type).or_default() += 1;
+            if let Some(scope) = scope {
+                *scopes.entry(scope).or_default() += 1;
+            }
+        }
+        match description.chars().find(|c| c.is_alphabetic()) {
+            Some(c) if c.is_uppercase() => capitalized += 1,
+            Some(_) => lowercase += 1,
+            None => {}
+        }
+
+        let (body, keys) = split_body(message);
+        if !body.is_empty() {
+            bodies += 1;
+            let bullet = |line: &str| {
+                let line = line.trim_start();
+                line.starts_with("- ") || line.starts_with("* ")
+            };
+            if body.lines().any(bullet) {
+                bulleted += 1;
+            }
+        }
+        for key in keys {
+            *trailers.entry(key).or_default() += 1;
+        }
+        classified.push((convention, body));
+    }
+
+    // Ties go to the more structured convention: max_by_key keeps the last
+    // of equal items
+    let convention = [
+        Convention::Conventional,
+        Convention::Gitmoji,
+        Convention::TicketPrefix,
+        Convention::Plain,
+    ]
+    .into_iter()
+    .rev()
+    .max_by_key(|c| conventions.get(c).copied().unwrap_or(0))?;
+
+    let body = if bodies * 3 < messages.len() {
+        BodyStyle::None
+    } else if bulleted * 2 >= bodies {
+        BodyStyle::Bullets
+    } else {
+        BodyStyle::Paragraphs
+    };
+
+    // Recent messages in the repository's convention, ones whose body
+    // matches the usual style first
+    let mut candidates: Vec<(bool, String)> = messages
+        .iter()
+        .zip(&classified)
+        .filter(|(message, (c, _))| *c == convention && message.len() <= MAX_EXAMPLE_CHARS)
+        .map(|(message, (_, text))| {
+            let subject = message.lines().next().unwrap_or_default().trim();
+            let typical = text.is_empty() == (body == BodyStyle::None);
+            if text.is_empty() {
+                (typical, subject.to_string())
+            } else {
+                (typical, format!("{}\n\n{}", subject, text))
+            }
+        })
+        .collect();
+    candidates.sort_by_key(|(typical, _)| !typical);
+    let examples = candidates
+        .into_iter()
+        .take(MAX_EXAMPLES)
+        .map(|(_, example)| example)
+        .collect();
+
+    Some(StyleProfile {
+        convention,
+        types: if convention == Convention::Conventional {
+            by_frequency(types, 1)
+        } else {
+            Vec::new()
+        },
+        scopes: if convention == Convention::Conventional {
+            by_frequency(scopes, 2)
+        } else {
+            Vec::new()
+        },
+        body,
+        capitalized: capitalized > lowercase,
+        trailers: by_frequency(trailers, messages.len().div_ceil(3)),
+        examples,
+    })
+}
+
+impl StyleProfile {
+    /// Whether commits follow `type(scope): description`, so lint rules apply.
+    pub fn is_conventional(&self) -> bool {
+        self.convention == Convention::Conventional
+    }
+
+    /// Prompt text describing the style, for the `{style}` placeholder.
+    pub fn guidance(&self) -> String {
+        let mut lines = vec![
+            "REPOSITORY STYLE (learned from recent commits; where it differs from the rules above, follow this):".to_string(),
+        ];
+        lines.push(match self.convention {
+            Convention::Conventional => {
+                "- Subjects use conventional commits: type(scope): description".to_string()
+            }
+            Convention::Gitmoji => {
+                "- Subjects start with a gitmoji (an emoji or :shortcode:), not type(scope):"
+                    .to_string()
+            }
+            Convention::TicketPrefix => "- Subjects start with a ticket ID such as PROJ-123, not type(scope): - keep the prefix format, but leave the ID out rather than invent one".to_string(),
+            Convention::Plain => {
+                "- Subjects are plain imperative sentences, without a type(scope): prefix"
+                    .to_string()
+            }
+        });
+        if !self.types.is_empty() {
+            lines.push(format!("- Types in use: {}", self.types.join(", ")));
+        }
+        if !self.scopes.is_empty() {
+            lines.push(format!("- Scopes in use: {}", self.scopes.join(", ")));
+        }
+        lines.push(if self.capitalized {
+            "- The description starts with a capital letter".to_string()
+        } else {
+            "- The description starts with a lowercase letter".to_string()
+        });
+        lines.push(
+            match self.body {
+                BodyStyle::None => {
+                    "- Most commits have only a subject line; add a body only for complex changes"
+                }
+                BodyStyle::Bullets => "- Bodies are bullet lists",
+                BodyStyle::Paragraphs => "- Bodies are short prose paragraphs, not bullet lists",
+            }
+            .to_string(),
+        );
+        if !self.trailers.is_empty() {
+            lines.push(format!(
+                "- Commits often end with these trailers: {} (leave them out unless the value is known)",
+                self.trailers.join(", ")
+            ));
+        }
+        if !self.examples.is_empty() {
+            lines.push(String::new());
+            lines.push("Recent commits from this repository:".to_string());
+            for example in &self.examples {
+                lines.push("---".to_string());
+                lines.push(example.clone());
+            }
+            lines.push("---".to_string());
+        }
+        format!("\n{}\n", lines.join("\n"))
+    }
+}
+
+/// The lint rules for a repository with `profile`, or `None` when linting is
+/// off or the repository doesn't use conventional commits.
+///
+/// Types the repository already uses are allowed too, unless `lint_types`
+/// was customized.
+pub fn lint_rules_for(config: &Config, profile: Option<&StyleProfile>) -> Option<LintRules> {
+    if !config.lint || !profile.is_none_or(StyleProfile::is_conventional) {
+        return None;
+    }
+    let mut rules = LintRules::from_config(config);
+    let default_types = rules
+        .types
+        .iter()
+        .map(String::as_str)
+        .eq(DEFAULT_TYPES.iter().copied());
+    if let Some(profile) = profile.filter(|_| default_types) {
+        for commit_type in &profile.types {
+            if !rules.types.contains(commit_type) {
+                rules.types.push(commit_type.clone());
+            }
+        }
+    }
+    Some(rules)
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn read_cache(path: &Path, commits: u32) -> Option<StyleCache> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    let cache: StyleCache = serde_json::from_str(&contents).ok()?;
+    (cache.commits == commits && now_secs().saturating_sub(cache.learned_at) < CACHE_TTL_SECS)
+        .then_some(cache)
+}
+
+fn write_cache(path: &Path, cache: &StyleCache) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_string(cache)?)?;
+    Ok(())
+}
+
+/// Learns the current repository's commit style, from the cache when fresh.
+///
+/// `None` when `learn_style` is off or the history is too short.
+pub async fn load_style(config: &Config, verbose: bool) -> Option<StyleProfile> {
+    if !config.learn_style {
+        return None;
+    }
+    let commits = config.learn_style_commits;
+    let path = get_git_path("committer/style.json").await.ok()?;
+    if let Some(cache) = read_cache(&path, commits) {
+        if verbose {
+            eprintln!(
+                "— Commit style from cache: {}",
+                describe(cache.profile.as_ref())
+            );
+        }
+        return cache.profile;
+    }
+
+    let messages = get_commit_messages(commits as usize)
+        .await
+        .unwrap_or_default();
+    let cache = StyleCache {
+        learned_at: now_secs(),
+        commits,
+        profile: infer_style(&messages),
+    };
+    if verbose {
+        eprintln!(
+            "— Commit style from {} commits: {}",
+            messages.len(),
+            describe(cache.profile.as_ref())
+        );
+    }
+    if let Err(e) = write_cache(&path, &cache) {
+        if verbose {
+            eprintln!("— Could not cache commit style: {}", e);
+        }
+    }
+    cache.profile
+}
+
+fn describe(profile: Option<&StyleProfile>) -> String {
+    match profile {
+        Some(profile) => format!("{:?}", profile.convention).to_lowercase(),
+        None => "not enough history".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(list: &[&str]) -> Vec<String> {
+        list.iter().map(|m| m.to_string()).collect()
+    }
+
+    #[test]
+    fn learns_conventional_scopes_and_trailers() {
+        let profile = infer_style(&messages(&[
+            "feat(api): add login\n\n- Accept tokens\n\nSigned-off-by: A <a@x>",
+            "fix(api): handle timeouts\n\nSigned-off-by: A <a@x>",
+            "fix(cli): exit on error",
+            "Merge branch 'main'",
+            "docs: describe setup",
+            "fix(cli): trim input",
+            "Add changelog",
+        ]))
+        .unwrap();
+
+        assert_eq!(profile.convention, Convention::Conventional);
+        assert_eq!(profile.types, vec!["fix", "docs", "feat"]);
+        assert_eq!(profile.scopes, vec!["api", "cli"]);
+        assert_eq!(profile.body, BodyStyle::None);
+        assert!(!profile.capitalized);
+        assert_eq!(profile.trailers, vec!["Signed-off-by"]);
+        assert_eq!(profile.examples[0], "fix(api): handle timeouts");
+    }
+
+    #[test]
+    fn recognizes_other_conventions() {
+        let gitmoji = infer_style(&messages(&[
+            "✨ Add search",
+            ":bug: Fix crash on empty input",
+            "🔥 Remove dead code",
+            "📝 Update docs",
+            "fix: typo",
+        ]))
+        .unwrap();
+        assert_eq!(gitmoji.convention, Convention::Gitmoji);
+        assert!(gitmoji.capitalized);
+        assert!(gitmoji.types.is_empty());
+        assert!(!gitmoji.is_conventional());
+
+        let tickets = infer_style(&messages(&[
+            "PROJ-12 Add search\n\nSearch titles and bodies.",
+            "[PROJ-13] Fix crash\n\nGuard against empty input.",
+            "PROJ-14: Remove dead code",
+            "Update docs",
+            "PROJ-15 Bump version",
+        ]))
+        .unwrap();
+        assert_eq!(tickets.convention, Convention::TicketPrefix);
+        assert_eq!(tickets.body, BodyStyle::Paragraphs);
+
+        assert!(infer_style(&messages(&["Add a", "Add b", "Add c"])).is_none());
+    }
+
+    #[test]
+    fn lint_rules_follow_the_learned_style() {
+        let profile = infer_style(&messages(&[
+            "feat: add search",
+            "release: 1.2.0",
+            "release: 1.3.0",
+            "fix: trim input",
+            "docs: describe setup",
+        ]))
+        .unwrap();
+        assert!(!DEFAULT_TYPES.contains(&"release"));
+        let config = Config::default();
+        let rules = lint_rules_for(&config, Some(&profile)).unwrap();
+        assert!(rules.types.iter().any(|t| t == "release"));
+        assert_eq!(rules.types.len(), DEFAULT_TYPES.len() + 1);
+
+        // Explicit types are left alone
+        let config = Config {
+            lint_types: vec!["feat".to_string(), "fix".to_string()],
+            ..Config::default()
+        };
+        let rules = lint_rules_for(&config, Some(&profile)).unwrap();
+        assert_eq!(rules.types, vec!["feat", "fix"]);
+
+        let gitmoji = infer_style(&messages(&[
+            "✨ Add a",
+            "🐛 Fix b",
+            "📝 Update c",
+            "🔥 Remove d",
+            "✨ Add e",
+        ]))
+        .unwrap();
+        assert!(lint_rules_for(&Config::default(), Some(&gitmoji)).is_none());
+        let config = Config {
+            lint: false,
+            ..Config::default()
+        };
+        assert!(lint_rules_for(&config, None).is_none());
+    }
+}
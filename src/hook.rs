@@ -0,0 +1,383 @@
+//! `prepare-commit-msg` hook integration.
+//!
+//! `committer hook install` writes a `prepare-commit-msg` hook so that plain
+//! `git commit` opens the editor with a generated message already filled in:
+//!
+//! ```bash
+//! committer hook install     # In the repository
+//! git add .
+//! git commit                 # Editor opens with the generated message
+//! ```
+//!
+//! An existing hook is moved aside to `prepare-commit-msg.chained` and run
+//! after committer, so both keep working; `committer hook uninstall` puts it
+//! back. The hook never blocks a commit: if generation fails, git continues
+//! with an empty message.
+//!
+//! # When the Hook Generates
+//!
+//! Only for a fresh message with nothing in it yet. Merges, squashes,
+//! amends and `-c`/`-C` reuse, `-m`/`-F` messages, templates, and commits
+//! made during a rebase are left alone.
+
+use console::style;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::path::{Path, PathBuf};
+
+use crate::api::{build_prompt, send_chat, ApiClient, ChatRequest};
+use crate::auth::resolve_api_key;
+use crate::cli::HookAction;
+use crate::config::Config;
+use crate::exclude::DiffFilter;
+use crate::git::{get_comment_char, get_git_diff, get_git_path, get_staged_files};
+use crate::history::{lint_rules_for, load_style, StyleProfile};
+use crate::lint::{fix, split_message_file};
+use crate::prompt::PromptTemplates;
+use crate::scopes::infer_scopes;
+use crate::secrets::guard_diff;
+use crate::summarize::prepare_diff;
+use crate::usage::Ledger;
+
+const HOOK_NAME: &str = "prepare-commit-msg";
+
+/// Where an existing hook is moved when committer's is installed.
+const CHAINED_HOOK_NAME: &str = "prepare-commit-msg.chained";
+
+/// Identifies hooks written by `committer hook install`.
+const HOOK_MARKER: &str = "# Installed by committer";
+
+const HOOK_SCRIPT: &str = r#"#!/bin/sh
+# Installed by committer. Remove with 'committer hook uninstall'.
+committer hook run "$@" || true
+
+chained="$(dirname "$0")/prepare-commit-msg.chained"
+if [ -x "$chained" ]; then
+    exec "$chained" "$@"
+fi
+"#;
+
+/// Result of installing the hook.
+#[derive(Debug, PartialEq)]
+pub enum Installed {
+    New,
+    Updated,
+    /// An existing hook was moved aside and is chained.
+    Chained,
+}
+
+/// Whether a hook's contents carry committer's marker. Hooks may be any
+/// executable, so they're searched as bytes.
+fn is_committer_hook(contents: &[u8]) -> bool {
+    contents
+        .windows(HOOK_MARKER.len())
+        .any(|window| window == HOOK_MARKER.as_bytes())
+}
+
+/// Writes committer's hook into `hooks_dir`, chaining any existing hook.
+pub fn install_hook(hooks_dir: &Path) -> Result<Installed, Box<dyn std::error::Error>> {
+    let hook = hooks_dir.join(HOOK_NAME);
+    let chained = hooks_dir.join(CHAINED_HOOK_NAME);
+
+    let outcome = match std::fs::read(&hook) {
+        Ok(existing) if is_committer_hook(&existing) => Installed::Updated,
+        Ok(_) => {
+            if chained.exists() {
+                return Err(format!(
+                    "Both {} and {} exist; remove one first",
+                    hook.display(),
+                    chained.display()
+                )
+                .into());
+            }
+            std::fs::rename(&hook, &chained)?;
+            Installed::Chained
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Installed::New,
+        Err(e) => return Err(format!("Could not read {}: {}", hook.display(), e).into()),
+    };
+
+    std::fs::create_dir_all(hooks_dir)?;
+    std::fs::write(&hook, HOOK_SCRIPT)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&hook, std::fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(outcome)
+}
+
+/// Result of uninstalling the hook.
+#[derive(Debug, PartialEq)]
+pub enum Uninstalled {
+    Removed,
+    /// The chained hook was moved back into place.
+    Restored,
+    NotInstalled,
+    /// The hook in place wasn't written by committer, so it was kept.
+    Foreign,
+}
+
+/// Removes committer's hook from `hooks_dir`, restoring a chained hook.
+pub fn uninstall_hook(hooks_dir: &Path) -> Result<Uninstalled, Box<dyn std::error::Error>> {
+    let hook = hooks_dir.join(HOOK_NAME);
+    let chained = hooks_dir.join(CHAINED_HOOK_NAME);
+
+    match std::fs::read(&hook) {
+        Ok(existing) if is_committer_hook(&existing) => {
+            std::fs::remove_file(&hook)?;
+            if chained.exists() {
+                std::fs::rename(&chained, &hook)?;
+                Ok(Uninstalled::Restored)
+            } else {
+                Ok(Uninstalled::Removed)
+            }
+        }
+        Ok(_) => Ok(Uninstalled::Foreign),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Uninstalled::NotInstalled),
+        Err(e) => Err(format!("Could not read {}: {}", hook.display(), e).into()),
+    }
+}
+
+/// Whether git's `prepare-commit-msg` source argument leaves the message to
+/// committer. It is empty for a plain `git commit`.
+fn wants_generated_message(source: Option<&str>) -> bool {
+    // message (-m/-F), template, merge, squash, commit (--amend, -c, -C)
+    source.is_none_or(str::is_empty)
+}
+
+async fn rebase_in_progress() -> bool {
+    for dir in ["rebase-merge", "rebase-apply"] {
+        if let Ok(path) = get_git_path(dir).await {
+            if path.exists() {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// Generates a message into the commit message file, keeping git's comments
+/// below it. Returns without changes when the hook shouldn't generate.
+async fn run_hook(
+    file: PathBuf,
+    source: Option<String>,
+    config: &Config,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let verbose = config.verbose;
+    if !wants_generated_message(source.as_deref()) || rebase_in_progress().await {
+        if verbose {
+            eprintln!(
+                "— Hook: leaving message alone (source: {})",
+                source.as_deref().unwrap_or("none")
+            );
+        }
+        return Ok(());
+    }
+
+    let contents = std::fs::read_to_string(&file)?;
+    if !split_message_file(&contents, &get_comment_char().await)
+        .0
+        .is_empty()
+    {
+        return Ok(());
+    }
+
+    let api_key = resolve_api_key(config.provider).await.map(|key| key.value);
+    if api_key.is_none() && config.provider.requires_api_key() {
+        eprintln!(
+            "{} committer: no API key found, skipping message generation",
+            style("⚠").yellow()
+        );
+        return Ok(());
+    }
+
+    let diff_filter = DiffFilter::load(&config.exclude).await;
+    let (diff_result, files_result) = tokio::join!(
+        get_git_diff(true, &diff_filter, config.diff_context, verbose),
+        get_staged_files(&diff_filter, verbose)
+    );
+    let diff = diff_result?;
+    let files = files_result?;
+    if diff.trim().is_empty() {
+        return Ok(());
+    }
+
+    let api = ApiClient::new(config, api_key, verbose)?.with_ledger(Ledger::open("commit").await);
+    let prompts = PromptTemplates::load().await;
+    let model = &config.model;
+
+    let diff = guard_diff(diff, config);
+    let diff = prepare_diff(
+        &api,
+        config,
+        model,
+        &diff,
+        &[&prompts.commit, &files],
+        &prompts.summary,
+        verbose,
+    )
+    .await?;
+
+    let spinner = ProgressBar::new_spinner();
+    spinner.set_style(
+        ProgressStyle::default_spinner()
+            .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"])
+            .template("{spinner:.cyan} Generating commit message...")
+            .unwrap(),
+    );
+    spinner.enable_steady_tick(std::time::Duration::from_millis(80));
+
+    let profile = load_style(config, verbose).await;
+    let conventional = profile.as_ref().is_none_or(StyleProfile::is_conventional);
+    let guidance = profile.as_ref().map(StyleProfile::guidance);
+    let scopes = if conventional {
+        infer_scopes(config, &diff_filter, verbose).await
+    } else {
+        Vec::new()
+    };
+    let prompt = build_prompt(
+        &prompts.commit,
+        &diff,
+        &files,
+        false,
+        guidance.as_deref().unwrap_or_default(),
+        &scopes,
+    );
+    let result = send_chat(&api, ChatRequest::user(model, prompt, false)).await;
+    spinner.finish_and_clear();
+
+    let mut message = result?.trim().to_string();
+    if message.is_empty() {
+        return Ok(());
+    }
+    if let Some(rules) = lint_rules_for(config, profile.as_ref()) {
+        message = fix(&message, &rules);
+    }
+
+    std::fs::write(&file, format!("{}\n{}", message, contents))?;
+    Ok(())
+}
+
+/// Main handler for `committer hook`.
+pub async fn handle_hook_command(
+    action: HookAction,
+    config: &Config,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match action {
+        HookAction::Install => {
+            let hooks_dir = get_git_path("hooks").await?;
+            match install_hook(&hooks_dir)? {
+                Installed::New => println!(
+                    "{} Installed {} hook",
+                    style("✓").green(),
+                    style(HOOK_NAME).cyan()
+                ),
+                Installed::Updated => println!(
+                    "{} Updated {} hook",
+                    style("✓").green(),
+                    style(HOOK_NAME).cyan()
+                ),
+                Installed::Chained => {
+                    println!(
+                        "{} Installed {} hook",
+                        style("✓").green(),
+                        style(HOOK_NAME).cyan()
+                    );
+                    println!(
+                        "  {} Existing hook moved to {} and still runs after committer",
+                        style("→").dim(),
+                        CHAINED_HOOK_NAME
+                    );
+                }
+            }
+            println!(
+                "  {} Plain 'git commit' now opens with a generated message",
+                style("→").dim()
+            );
+        }
+        HookAction::Uninstall => {
+            let hooks_dir = get_git_path("hooks").await?;
+            match uninstall_hook(&hooks_dir)? {
+                Uninstalled::Removed => println!(
+                    "{} Removed {} hook",
+                    style("✓").green(),
+                    style(HOOK_NAME).cyan()
+                ),
+                Uninstalled::Restored => println!(
+                    "{} Removed {} hook and restored the previous one",
+                    style("✓").green(),
+                    style(HOOK_NAME).cyan()
+                ),
+                Uninstalled::NotInstalled => {
+                    println!("{} No committer hook installed", style("✓").green())
+                }
+                Uninstalled::Foreign => {
+                    println!(
+                        "{} {} hook wasn't installed by committer",
+                        style("✗").red(),
+                        HOOK_NAME
+                    );
+                    println!("  {} Left it in place", style("→").dim());
+                    std::process::exit(1);
+                }
+            }
+        }
+        HookAction::Run { file, source, .. } => {
+            run_hook(file, source, config).await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn chains_and_restores_existing_hook() {
+        let dir = TempDir::new("hook-chain");
+        let existing = "#!/bin/sh\necho ticket >> \"$1\"\n";
+        std::fs::write(dir.join(HOOK_NAME), existing).unwrap();
+
+        assert_eq!(install_hook(&dir).unwrap(), Installed::Chained);
+        assert_eq!(install_hook(&dir).unwrap(), Installed::Updated);
+        assert_eq!(
+            std::fs::read_to_string(dir.join(CHAINED_HOOK_NAME)).unwrap(),
+            existing
+        );
+
+        assert_eq!(uninstall_hook(&dir).unwrap(), Uninstalled::Restored);
+        assert_eq!(
+            std::fs::read_to_string(dir.join(HOOK_NAME)).unwrap(),
+            existing
+        );
+        assert!(!dir.join(CHAINED_HOOK_NAME).exists());
+        assert_eq!(uninstall_hook(&dir).unwrap(), Uninstalled::Foreign);
+    }
+
+    #[test]
+    fn chains_non_utf8_hook() {
+        let dir = TempDir::new("hook-binary");
+        let existing: &[u8] = b"\x7fELF\x02\x01\xff\xfe";
+        std::fs::write(dir.join(HOOK_NAME), existing).unwrap();
+
+        assert_eq!(install_hook(&dir).unwrap(), Installed::Chained);
+        assert_eq!(
+            std::fs::read(dir.join(CHAINED_HOOK_NAME)).unwrap(),
+            existing
+        );
+        assert_eq!(uninstall_hook(&dir).unwrap(), Uninstalled::Restored);
+        assert_eq!(std::fs::read(dir.join(HOOK_NAME)).unwrap(), existing);
+        assert_eq!(uninstall_hook(&dir).unwrap(), Uninstalled::Foreign);
+    }
+
+    #[test]
+    fn generates_only_for_plain_commits() {
+        assert!(wants_generated_message(None));
+        assert!(wants_generated_message(Some("")));
+        for source in ["message", "template", "merge", "squash", "commit"] {
+            assert!(!wants_generated_message(Some(source)));
+        }
+    }
+}
@@ -0,0 +1,586 @@
+//! Commit message linting against conventional-commit rules.
+//!
+//! Generated and edited messages are checked before committing, and
+//! `committer lint <file>` checks a message file on its own, e.g. from a
+//! `commit-msg` hook:
+//!
+//! ```bash
+//! # .git/hooks/commit-msg
+//! exec committer lint "$1"
+//! ```
+//!
+//! # Rules
+//!
+//! | Rule                   | Checks                                         | Fixable |
+//! |------------------------|------------------------------------------------|---------|
+//! | `header-format`        | Subject reads `type(scope): description`       | no      |
+//! | `type-case`            | Type is lowercase                              | yes     |
+//! | `type-enum`            | Type is in `lint_types`                        | aliases |
+//! | `scope-enum`           | Scope is in `lint_scopes`, when set            | no      |
+//! | `subject-empty`        | Description is present                         | no      |
+//! | `subject-full-stop`    | Subject doesn't end with a period              | yes     |
+//! | `header-max-length`    | Subject fits `lint_subject_max_length`         | no      |
+//! | `body-leading-blank`   | A blank line separates subject and body        | yes     |
+//! | `body-bullet-style`    | Bullets use `-`, not `*` or `•`                | yes     |
+//! | `body-no-headers`      | No markdown headers                            | yes     |
+//! | `body-max-line-length` | Body lines fit `lint_body_max_line_length`     | yes     |
+//!
+//! Merge, revert, `fixup!`, and `squash!` messages are not checked.
+
+use console::style;
+use regex_lite::Regex;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use crate::cli::LintArgs;
+use crate::config::Config;
+use crate::git::get_comment_char;
+use crate::history::{lint_rules_for, load_style};
+use crate::ui::{prompt_lint_action, LintAction};
+
+/// Conventional commit types accepted by default, matching the commit prompt.
+pub const DEFAULT_TYPES: &[&str] = &[
+    "feat", "fix", "refactor", "perf", "style", "docs", "test", "chore", "build", "ci", "deps",
+    "config", "security", "revert",
+];
+
+/// Common misspellings of types, fixed automatically when the target type
+/// is allowed.
+const TYPE_ALIASES: &[(&str, &str)] = &[
+    ("feature", "feat"),
+    ("features", "feat"),
+    ("bugfix", "fix"),
+    ("hotfix", "fix"),
+    ("bug", "fix"),
+    ("doc", "docs"),
+    ("tests", "test"),
+    ("testing", "test"),
+    ("refactoring", "refactor"),
+    ("performance", "perf"),
+    ("dep", "deps"),
+    ("dependencies", "deps"),
+];
+
+/// Messages git or tooling generates, which aren't checked.
+pub const IGNORED_PREFIXES: &[&str] = &["Merge ", "Revert \"", "fixup! ", "squash! ", "amend! "];
+
+/// The rule settings, taken from the config.
+pub struct LintRules {
+    pub types: Vec<String>,
+    pub scopes: Vec<String>,
+    pub subject_max_length: usize,
+    /// `0` disables the check.
+    pub body_max_line_length: usize,
+}
+
+impl LintRules {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            types: config.lint_types.clone(),
+            scopes: config.lint_scopes.clone(),
+            subject_max_length: config.lint_subject_max_length as usize,
+            body_max_line_length: config.lint_body_max_line_length as usize,
+        }
+    }
+
+    fn alias_for(&self, commit_type: &str) -> Option<&'static str> {
+        TYPE_ALIASES
+            .iter()
+            .find(|(alias, _)| *alias == commit_type)
+            .map(|(_, target)| *target)
+            .filter(|target| self.types.iter().any(|t| t == target))
+    }
+}
+
+/// A rule a message breaks.
+pub struct Violation {
+    pub rule: &'static str,
+    pub message: String,
+    pub fixable: bool,
+}
+
+/// The parts of a `type(scope)!: subject` header.
+struct Header<'a> {
+    commit_type: &'a str,
+    scope: Option<&'a str>,
+    breaking: bool,
+    subject: &'a str,
+}
+
+/// A `type(scope)!: subject` header.
+static HEADER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^([A-Za-z]+)(?:\(([^)]*)\))?(!)?: ?(.*)$").unwrap());
+
+fn parse_header(header: &str) -> Option<Header<'_>> {
+    let caps = HEADER.captures(header)?;
+    Some(Header {
+        commit_type: caps.get(1)?.as_str(),
+        scope: caps.get(2).map(|m| m.as_str()),
+        breaking: caps.get(3).is_some(),
+        subject: caps.get(4).map(|m| m.as_str()).unwrap_or(""),
+    })
+}
+
+fn is_bullet(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start();
+    trimmed
+        .strip_prefix("* ")
+        .or_else(|| trimmed.strip_prefix("• "))
+}
+
+fn is_markdown_header(line: &str) -> bool {
+    let hashes = line.len() - line.trim_start_matches('#').len();
+    hashes > 0 && line[hashes..].starts_with(' ')
+}
+
+/// Checks `message` against `rules`.
+pub fn lint(message: &str, rules: &LintRules) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    let message = message.trim();
+    if IGNORED_PREFIXES
+        .iter()
+        .any(|prefix| message.starts_with(prefix))
+    {
+        return violations;
+    }
+    let mut violation = |rule, message: String, fixable| {
+        violations.push(Violation {
+            rule,
+            message,
+            fixable,
+        })
+    };
+
+    let lines: Vec<&str> = message.lines().collect();
+    let header_line = lines.first().copied().unwrap_or("");
+
+    match parse_header(header_line) {
+        None => violation(
+            "header-format",
+            "Subject should read \"type(scope): description\"".to_string(),
+            false,
+        ),
+        Some(header) => {
+            let lower = header.commit_type.to_lowercase();
+            if header.commit_type != lower {
+                violation(
+                    "type-case",
+                    format!("Type '{}' should be lowercase", header.commit_type),
+                    true,
+                );
+            }
+            if !rules.types.contains(&lower) {
+                let alias = rules.alias_for(&lower);
+                let message = match alias {
+                    Some(target) => format!("Type '{}' should be '{}'", lower, target),
+                    None => format!("Type '{}' is not one of {}", lower, rules.types.join(", ")),
+                };
+                violation("type-enum", message, alias.is_some());
+            }
+            if let Some(scope) = header.scope {
+                if !rules.scopes.is_empty() {
+                    for part in scope.split(',').map(str::trim) {
+                        if !rules.scopes.iter().any(|s| s == part) {
+                            violation(
+                                "scope-enum",
+                                format!(
+                                    "Scope '{}' is not one of {}",
+                                    part,
+                                    rules.scopes.join(", ")
+                                ),
+                                false,
+                            );
+                        }
+                    }
+                }
+            }
+            if header.subject.trim().is_empty() {
+                violation("subject-empty", "Description is missing".to_string(), false);
+            }
+            if header.subject.trim_end().ends_with('.') {
+                violation(
+                    "subject-full-stop",
+                    "Subject should not end with a period".to_string(),
+                    true,
+                );
+            }
+        }
+    }
+
+    let length = header_line.chars().count();
+    if length > rules.subject_max_length {
+        violation(
+            "header-max-length",
+            format!(
+                "Subject is {} characters (max {})",
+                length, rules.subject_max_length
+            ),
+            false,
+        );
+    }
+
+    if lines.len() > 1 && !lines[1].trim().is_empty() {
+        violation(
+            "body-leading-blank",
+            "Add a blank line between subject and body".to_string(),
+            true,
+        );
+    }
+
+    for (index, line) in lines.iter().enumerate().skip(1) {
+        let number = index + 1;
+        if is_bullet(line).is_some() {
+            violation(
+                "body-bullet-style",
+                format!("Line {}: use \"-\" for bullets", number),
+                true,
+            );
+        }
+        if is_markdown_header(line) {
+            violation(
+                "body-no-headers",
+                format!("Line {}: markdown headers don't belong in commits", number),
+                true,
+            );
+        }
+        let length = line.chars().count();
+        if rules.body_max_line_length > 0
+            && length > rules.body_max_line_length
+            && line.contains(' ')
+        {
+            violation(
+                "body-max-line-length",
+                format!(
+                    "Line {} is {} characters (max {})",
+                    number, length, rules.body_max_line_length
+                ),
+                true,
+            );
+        }
+    }
+
+    violations
+}
+
+/// Wraps `line` at `width`, indenting continuation lines under a bullet.
+fn wrap_line(line: &str, width: usize) -> Vec<String> {
+    let indent = if line.trim_start().starts_with("- ") {
+        " ".repeat(line.len() - line.trim_start().len() + 2)
+    } else {
+        String::new()
+    };
+
+    let mut wrapped = Vec::new();
+    let mut current = String::new();
+    for word in line.split(' ') {
+        let candidate_length = current.chars().count() + 1 + word.chars().count();
+        if !current.trim().is_empty() && candidate_length > width {
+            wrapped.push(std::mem::take(&mut current));
+            current = format!("{}{}", indent, word);
+        } else if current.is_empty() && wrapped.is_empty() {
+            current = word.to_string();
+        } else {
+            current.push(' ');
+            current.push_str(word);
+        }
+    }
+    wrapped.push(current);
+    wrapped
+}
+
+/// Applies every fixable rule to `message`.
+///
+/// Unfixable problems (e.g. an overlong subject) are left for the caller to
+/// report or regenerate.
+pub fn fix(message: &str, rules: &LintRules) -> String {
+    let message = message.trim();
+    let mut lines: Vec<String> = message.lines().map(str::to_string).collect();
+    if lines.is_empty() {
+        return String::new();
+    }
+
+    if let Some(header) = parse_header(&lines[0]) {
+        let mut commit_type = header.commit_type.to_lowercase();
+        if !rules.types.contains(&commit_type) {
+            if let Some(target) = rules.alias_for(&commit_type) {
+                commit_type = target.to_string();
+            }
+        }
+        let scope = header
+            .scope
+            .map(|scope| format!("({})", scope))
+            .unwrap_or_default();
+        let bang = if header.breaking { "!" } else { "" };
+        let subject = header.subject.trim().trim_end_matches('.');
+        lines[0] = format!("{}{}{}: {}", commit_type, scope, bang, subject);
+    }
+
+    let mut body: Vec<String> = Vec::new();
+    for line in &lines[1..] {
+        if is_markdown_header(line) {
+            continue;
+        }
+        let line = match is_bullet(line) {
+            Some(text) => {
+                let indent = &line[..line.len() - line.trim_start().len()];
+                format!("{}- {}", indent, text)
+            }
+            None => line.clone(),
+        };
+        if rules.body_max_line_length > 0 && line.chars().count() > rules.body_max_line_length {
+            body.extend(wrap_line(&line, rules.body_max_line_length));
+        } else {
+            body.push(line);
+        }
+    }
+
+    // Collapse blank runs left by removed headers, and drop leading blanks
+    let mut cleaned: Vec<String> = Vec::new();
+    for line in body {
+        let blank = line.trim().is_empty();
+        if blank && cleaned.last().is_none_or(|last| last.trim().is_empty()) {
+            continue;
+        }
+        cleaned.push(line);
+    }
+    while cleaned.last().is_some_and(|line| line.trim().is_empty()) {
+        cleaned.pop();
+    }
+
+    let mut fixed = lines[0].clone();
+    if !cleaned.is_empty() {
+        fixed.push_str("\n\n");
+        fixed.push_str(&cleaned.join("\n"));
+    }
+    fixed
+}
+
+/// Prints violations, marking the ones [`fix`] can repair.
+pub fn print_violations(violations: &[Violation]) {
+    for violation in violations {
+        let fixable = if violation.fixable { " (fixable)" } else { "" };
+        println!(
+            "    {}  {}{}",
+            style(violation.rule).yellow(),
+            violation.message,
+            style(fixable).dim()
+        );
+    }
+}
+
+/// Outcome of [`review_message`].
+pub enum Review {
+    /// Commit this message.
+    Ready(String),
+    /// The user asked for a new message.
+    Regenerate,
+}
+
+/// Lints a generated or edited message before it is committed.
+///
+/// Interactively, the user picks between fixing, regenerating, and keeping
+/// it while problems remain. Otherwise fixable problems are fixed and the
+/// rest reported as warnings.
+pub fn review_message(
+    mut message: String,
+    rules: &LintRules,
+    can_regenerate: bool,
+    interactive: bool,
+) -> Review {
+    loop {
+        let violations = lint(&message, rules);
+        if violations.is_empty() {
+            return Review::Ready(message);
+        }
+        let can_fix = violations.iter().any(|v| v.fixable);
+
+        if !interactive {
+            if can_fix {
+                message = fix(&message, rules);
+                println!("{} Fixed commit message lint problems:", style("✓").green());
+                println!();
+                println!("{}", message);
+            }
+            let remaining = lint(&message, rules);
+            if !remaining.is_empty() {
+                println!(
+                    "{} Commit message has {} lint problems:",
+                    style("⚠").yellow(),
+                    remaining.len()
+                );
+                print_violations(&remaining);
+            }
+            return Review::Ready(message);
+        }
+
+        println!();
+        println!(
+            "{} Commit message has {} lint problems:",
+            style("⚠").yellow(),
+            violations.len()
+        );
+        print_violations(&violations);
+
+        match prompt_lint_action(can_fix, can_regenerate) {
+            LintAction::Fix => {
+                message = fix(&message, rules);
+                println!();
+                println!("{}", message);
+            }
+            LintAction::Regenerate => return Review::Regenerate,
+            LintAction::Keep => return Review::Ready(message),
+        }
+    }
+}
+
+/// Line git adds above the diff in `commit --verbose`, after the comment
+/// character; it ignores everything from here on.
+const SCISSORS: &str = " ------------------------ >8 ------------------------";
+
+/// Splits a commit message file into the message and the comment lines git
+/// strips from it (lines starting with `comment_char`, and everything after
+/// a scissors line).
+pub fn split_message_file(contents: &str, comment_char: &str) -> (String, String) {
+    let scissors_line = format!("{}{}", comment_char, SCISSORS);
+    let (body, scissors) = match contents.find(&scissors_line) {
+        Some(index) => contents.split_at(index),
+        None => (contents, ""),
+    };
+    let (comments, message): (Vec<&str>, Vec<&str>) = body
+        .lines()
+        .partition(|line| line.starts_with(comment_char));
+
+    let mut tail: String = comments.iter().map(|line| format!("{}\n", line)).collect();
+    tail.push_str(scissors);
+    (message.join("\n").trim().to_string(), tail)
+}
+
+/// Main handler for `committer lint <file>`.
+///
+/// Exits with status 1 if problems remain, so a `commit-msg` hook aborts
+/// the commit. Passes everything when `lint` is off or the repository's
+/// learned style isn't conventional commits, like the other flows.
+pub async fn handle_lint_command(
+    args: LintArgs,
+    config: &Config,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let profile = load_style(config, config.verbose).await;
+    let Some(rules) = lint_rules_for(config, profile.as_ref()) else {
+        return Ok(());
+    };
+    let path: &Path = &args.file;
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+    let (mut message, tail) = split_message_file(&contents, &get_comment_char().await);
+
+    if args.fix {
+        let fixed = fix(&message, &rules);
+        if fixed != message {
+            std::fs::write(path, format!("{}\n{}", fixed, tail))?;
+            message = fixed;
+        }
+    }
+
+    let violations = lint(&message, &rules);
+    if violations.is_empty() {
+        return Ok(());
+    }
+
+    println!(
+        "{} Commit message has {} lint problems:",
+        style("✗").red(),
+        violations.len()
+    );
+    print_violations(&violations);
+    let fixable = violations.iter().filter(|v| v.fixable).count();
+    if fixable > 0 {
+        println!(
+            "  {} Run 'committer lint --fix {}' to fix {} of them",
+            style("→").dim(),
+            path.display(),
+            fixable
+        );
+    }
+    std::process::exit(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules() -> LintRules {
+        LintRules {
+            types: DEFAULT_TYPES.iter().map(|t| t.to_string()).collect(),
+            scopes: Vec::new(),
+            subject_max_length: 72,
+            body_max_line_length: 40,
+        }
+    }
+
+    fn rule_names(message: &str, rules: &LintRules) -> Vec<&'static str> {
+        lint(message, rules).iter().map(|v| v.rule).collect()
+    }
+
+    #[test]
+    fn accepts_conventional_messages() {
+        assert!(rule_names("feat(auth): add login\n\n- Support OAuth", &rules()).is_empty());
+        assert!(rule_names("fix!: drop legacy flag", &rules()).is_empty());
+        assert!(rule_names("Merge branch 'main' into dev", &rules()).is_empty());
+    }
+
+    #[test]
+    fn reports_header_problems() {
+        assert_eq!(rule_names("Add login", &rules()), vec!["header-format"]);
+        assert_eq!(
+            rule_names("Feature(auth): add login.", &rules()),
+            vec!["type-case", "type-enum", "subject-full-stop"]
+        );
+        assert_eq!(
+            rule_names(&format!("feat: {}", "x".repeat(70)), &rules()),
+            vec!["header-max-length"]
+        );
+
+        let mut scoped = rules();
+        scoped.scopes = vec!["api".to_string(), "ui".to_string()];
+        assert_eq!(
+            rule_names("feat(db): add index", &scoped),
+            vec!["scope-enum"]
+        );
+        assert!(rule_names("feat(api, ui): share types", &scoped).is_empty());
+    }
+
+    #[test]
+    fn fixes_body_formatting() {
+        let message = "Feature(auth): add login.\n## Changes\n* Support OAuth providers for signing in with Google accounts\n* Refresh tokens";
+
+        let fixed = fix(message, &rules());
+
+        assert_eq!(
+            fixed,
+            "feat(auth): add login\n\n- Support OAuth providers for signing in\n  with Google accounts\n- Refresh tokens"
+        );
+        assert!(lint(&fixed, &rules()).is_empty());
+    }
+
+    #[test]
+    fn separates_message_from_git_comments() {
+        let contents = "feat: add x\n# Please enter the commit message\n\n- Detail\n# ------------------------ >8 ------------------------\ndiff --git a/x b/x\n# not a comment\n";
+
+        let (message, tail) = split_message_file(contents, "#");
+
+        assert_eq!(message, "feat: add x\n\n- Detail");
+        assert!(tail.starts_with("# Please enter the commit message\n# ---"));
+        assert!(tail.ends_with("diff --git a/x b/x\n# not a comment\n"));
+    }
+
+    #[test]
+    fn honours_the_comment_char() {
+        let contents = "fix: handle #12\n\n#12 crashed on save\n; Please enter the commit message\n; ------------------------ >8 ------------------------\ndiff --git a/x b/x\n";
+
+        let (message, tail) = split_message_file(contents, ";");
+
+        assert_eq!(message, "fix: handle #12\n\n#12 crashed on save");
+        assert!(tail.starts_with("; Please enter the commit message\n; ---"));
+        assert!(tail.ends_with("diff --git a/x b/x\n"));
+    }
+}
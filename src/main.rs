@@ -1,29 +1,48 @@
 //! Committer - AI-powered git commit message generator.
 //!
-//! Committer uses LLMs via OpenRouter to generate conventional commit messages
+//! Committer uses LLMs via OpenRouter (or another configured provider) to generate conventional commit messages
 //! from staged changes, with features like:
 //!
 //! - **Streaming output**: Watch messages generate token-by-token
 //! - **Branch intelligence**: Detects misaligned branches and suggests alternatives
 //! - **Interactive editing**: Edit messages before committing
-//! - **Smart diff handling**: Filters noise and truncates large diffs
+//! - **Smart diff handling**: Filters noise and fits large diffs to the model's context window
 //! - **PR generation**: Create pull requests with AI-generated descriptions
 //!
 //! # Modules
 //!
-//! - [`api`]: OpenRouter API integration
+//! - [`api`]: LLM provider integration
+//! - [`auth`]: API key storage and lookup
 //! - [`branch`]: Branch analysis and naming
+//! - [`budget`]: Token-aware diff budgeting
+//! - [`candidates`]: Several alternative commit messages to choose from
+//! - [`changes`]: Structured notes for binary, renamed, and submodule changes
 //! - [`clean`]: Safe cleanup of merged local branches
 //! - [`cli`]: Command-line interface
 //! - [`config`]: Configuration management
+//! - [`context`]: Per-file function context in diffs
+//! - [`exclude`]: Diff exclusion rules
+//! - [`generate`]: Commit message generation and review
 //! - [`git`]: Git operations
+//! - [`history`]: Commit style learned from repository history
+//! - [`hook`]: `prepare-commit-msg` hook for plain `git commit`
+//! - [`lint`]: Conventional-commit message linting
+//! - [`models`]: Model metadata such as context windows
 //! - [`pr`]: Pull request generation
+//! - [`prompt`]: Overridable prompt templates
+//! - [`scopes`]: Commit scopes derived from the repository layout
+//! - [`secrets`]: Secret scanning and redaction of diffs
+//! - [`settings`]: Typed settings registry and `config` subcommand
+//! - [`split`]: Splitting staged changes into focused commits
+//! - [`sse`]: Incremental decoding of streamed responses
+//! - [`summarize`]: Map-reduce summarization of very large diffs
 //! - [`ui`]: User interaction prompts
+//! - [`usage`]: Token usage and cost tracking
 //!
 //! # Quick Start
 //!
 //! ```bash
-//! export OPENROUTER_API_KEY="sk-or-..."
+//! committer auth login   # or: export OPENROUTER_API_KEY="sk-or-..."
 //! git add .
 //! committer
 //! ```
@@ -31,32 +50,67 @@
 use clap::Parser;
 use console::{style, Term};
 use indicatif::{ProgressBar, ProgressStyle};
-use reqwest::Client;
-use std::io::Write;
+use std::io::IsTerminal;
 use tokio::process::Command;
 
 mod api;
+mod auth;
 mod branch;
+mod budget;
+mod candidates;
+mod changes;
 mod clean;
 mod cli;
 mod config;
+mod context;
+mod exclude;
+mod generate;
 mod git;
+mod history;
+mod hook;
+mod lint;
+mod models;
 mod pr;
+mod prompt;
+mod scopes;
+mod secrets;
+mod settings;
+mod split;
+mod sse;
+mod summarize;
+#[cfg(test)]
+mod test_support;
 mod ui;
+mod usage;
 
-use api::{stream_commit_message, CommitMessageOptions};
+use api::{ApiClient, CommitMessageOptions, Refinement};
+use auth::{handle_auth_command, resolve_api_key};
 use branch::{
-    analyze_branch_alignment, generate_branch_suggestion, generate_fallback_branch, BranchAction,
+    analyze_branch_alignment, fallback_branch_analysis, generate_branch_suggestion,
+    generate_fallback_branch, BranchAction,
 };
+use candidates::{assign_models, choose_candidate};
 use clean::handle_clean_command;
-use cli::{Cli, Commands, ConfigAction};
-use config::{config_path, get_api_key, load_config, save_config};
+use cli::{Cli, Commands, PromptAction};
+use config::{exit_with_config_error, load_layered_config};
+use exclude::DiffFilter;
+use generate::{generate_reviewed_message, CommitRefiner, Refined};
 use git::{
     check_git_installed, create_and_switch_branch, get_current_branch, get_git_diff,
     get_recent_commits, get_staged_files, run_git_commit, stage_all_changes,
 };
+use history::{lint_rules_for, load_style, StyleProfile};
+use hook::handle_hook_command;
+use lint::{handle_lint_command, review_message, Review};
 use pr::handle_pr_command;
-use ui::{prompt_branch_action, prompt_commit, CommitAction};
+use prompt::{handle_prompt_show, PromptTemplates};
+use scopes::infer_scopes;
+use secrets::guard_diff;
+use settings::handle_config_command;
+use split::handle_split_command;
+use summarize::prepare_diff;
+use ui::prompt_branch_action;
+use usage::{finish_invocation, handle_usage_command, Ledger};
 
 // ============================================================================
 // Main
@@ -65,136 +119,68 @@ use ui::{prompt_branch_action, prompt_commit, CommitAction};
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
-    let mut config = load_config();
+    let layered = load_layered_config().await;
 
     // Handle subcommands
     if let Some(command) = cli.command {
         match command {
             Commands::Config { action } => {
-                match action {
-                    ConfigAction::Show => {
-                        println!("{}", style("Configuration").bold());
-                        println!("  {} {}", style("file:").dim(), config_path().display());
-                        println!();
-                        let bool_style = |v: bool| {
-                            if v {
-                                style("true").green()
-                            } else {
-                                style("false").dim()
-                            }
-                        };
-                        println!(
-                            "  {} {}",
-                            style("auto_commit:").cyan(),
-                            bool_style(config.auto_commit)
-                        );
-                        println!(
-                            "  {} {}",
-                            style("commit_after_branch:").cyan(),
-                            bool_style(config.commit_after_branch)
-                        );
-                        println!(
-                            "  {} {}",
-                            style("verbose:").cyan(),
-                            bool_style(config.verbose)
-                        );
-                        println!(
-                            "  {} {}",
-                            style("model:").cyan(),
-                            style(&config.model).yellow()
-                        );
-                        println!(
-                            "  {} {}",
-                            style("api_key:").cyan(),
-                            if std::env::var("OPENROUTER_API_KEY").is_ok() {
-                                style("[set via env]").green()
-                            } else {
-                                style("[not set]").red()
-                            }
-                        );
-                    }
-                    ConfigAction::AutoCommit { value } => {
-                        config.auto_commit = value.parse().unwrap_or(false);
-                        save_config(&config)?;
-                        let val_style = if config.auto_commit {
-                            style("true").green()
-                        } else {
-                            style("false").dim()
-                        };
-                        println!(
-                            "{} {} set to {}",
-                            style("✓").green(),
-                            style("auto_commit").cyan(),
-                            val_style
-                        );
-                    }
-                    ConfigAction::CommitAfterBranch { value } => {
-                        config.commit_after_branch = value.parse().unwrap_or(false);
-                        save_config(&config)?;
-                        let val_style = if config.commit_after_branch {
-                            style("true").green()
-                        } else {
-                            style("false").dim()
-                        };
-                        println!(
-                            "{} {} set to {}",
-                            style("✓").green(),
-                            style("commit_after_branch").cyan(),
-                            val_style
-                        );
-                    }
-                    ConfigAction::Model { value } => {
-                        config.model = value;
-                        save_config(&config)?;
-                        println!(
-                            "{} {} set to {}",
-                            style("✓").green(),
-                            style("model").cyan(),
-                            style(&config.model).yellow()
-                        );
-                    }
-                    ConfigAction::Verbose { value } => {
-                        config.verbose = value.parse().unwrap_or(false);
-                        save_config(&config)?;
-                        let val_style = if config.verbose {
-                            style("true").green()
-                        } else {
-                            style("false").dim()
-                        };
-                        println!(
-                            "{} {} set to {}",
-                            style("✓").green(),
-                            style("verbose").cyan(),
-                            val_style
-                        );
-                    }
-                }
-                return Ok(());
+                return handle_config_command(action, layered).await;
             }
             Commands::Pr(args) => {
-                return handle_pr_command(args, &config).await;
+                let layered = layered.unwrap_or_else(|e| exit_with_config_error(&e));
+                return handle_pr_command(args, &layered.config).await;
             }
             Commands::Clean(args) => {
                 return handle_clean_command(args).await;
             }
+            Commands::Usage(args) => {
+                return handle_usage_command(args);
+            }
+            Commands::Auth { action } => {
+                let layered = layered.unwrap_or_else(|e| exit_with_config_error(&e));
+                return handle_auth_command(action, &layered.config).await;
+            }
+            Commands::Lint(args) => {
+                let layered = layered.unwrap_or_else(|e| exit_with_config_error(&e));
+                return handle_lint_command(args, &layered.config).await;
+            }
+            Commands::Split(args) => {
+                let layered = layered.unwrap_or_else(|e| exit_with_config_error(&e));
+                return handle_split_command(args, &layered.config).await;
+            }
+            Commands::Hook { action } => {
+                let layered = layered.unwrap_or_else(|e| exit_with_config_error(&e));
+                return handle_hook_command(action, &layered.config).await;
+            }
+            Commands::Prompt { action } => {
+                match action {
+                    PromptAction::Show { kind } => handle_prompt_show(kind).await,
+                }
+                return Ok(());
+            }
         }
     }
 
+    let config = layered
+        .unwrap_or_else(|e| exit_with_config_error(&e))
+        .config;
+
     // Verify git is installed before any git operations
     check_git_installed().await?;
 
     // Get API key
-    let api_key = match get_api_key() {
-        Some(key) => key,
-        None => {
-            println!("{} No API key found", style("✗").red());
-            println!(
-                "  {} Set OPENROUTER_API_KEY environment variable",
-                style("→").dim()
-            );
-            std::process::exit(1);
-        }
-    };
+    // Local providers work without a key
+    let api_key = resolve_api_key(config.provider).await.map(|key| key.value);
+    if api_key.is_none() && config.provider.requires_api_key() {
+        println!("{} No API key found", style("✗").red());
+        println!(
+            "  {} Set {} or run 'committer auth login'",
+            style("→").dim(),
+            config.provider.api_key_env()
+        );
+        std::process::exit(1);
+    }
 
     // Stage all changes if requested
     if cli.all {
@@ -204,9 +190,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Determine verbose mode (CLI flag overrides config)
     let verbose = cli.verbose || config.verbose;
 
+    // Create API client
+    let api = ApiClient::new(&config, api_key, verbose)?.with_ledger(Ledger::open("commit").await);
+    let prompts = PromptTemplates::load().await;
+
+    // Determine which model to use
+    let model = cli.model.as_ref().unwrap_or(&config.model);
+
     // Get diff and file list in parallel
-    let (diff_result, files_result) =
-        tokio::join!(get_git_diff(true, verbose), get_staged_files(verbose));
+    let diff_filter = DiffFilter::load(&config.exclude).await;
+    let (diff_result, files_result) = tokio::join!(
+        get_git_diff(true, &diff_filter, config.diff_context, verbose),
+        get_staged_files(&diff_filter, verbose)
+    );
 
     let diff = diff_result?;
     let files = files_result?;
@@ -230,50 +226,88 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
-    // Determine which model to use
-    let model = cli.model.as_ref().unwrap_or(&config.model);
-
-    // Create HTTP client
-    let client = Client::builder().build()?;
-
-    // Stream the commit message with spinner
-    let term = Term::stdout();
-    let _ = term.hide_cursor();
-
-    let spinner = ProgressBar::new_spinner();
-    spinner.set_style(
-        ProgressStyle::default_spinner()
-            .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"])
-            .template("{spinner:.cyan} Generating commit message...")
-            .unwrap(),
-    );
-    spinner.enable_steady_tick(std::time::Duration::from_millis(80));
-
-    // Ensure spinner renders before starting API call
-    std::io::stdout().flush().ok();
-
-    let message_result = stream_commit_message(
-        &client,
-        &api_key,
+    // Keep secrets out of the prompt, then fit the diff to the model's
+    // context, summarizing it if very large
+    let diff = guard_diff(diff, &config);
+    let diff = prepare_diff(
+        &api,
+        &config,
         model,
         &diff,
-        &files,
-        &spinner,
-        CommitMessageOptions {
-            verbose,
-            oneline: cli.oneline,
-        },
+        &[&prompts.commit, &files],
+        &prompts.summary,
+        verbose,
     )
-    .await;
-
-    let _ = term.show_cursor();
-    let message = message_result?;
+    .await?;
+
+    // Follow the repository's own commit conventions where it has them,
+    // and scopes from the workspace layout
+    let profile = load_style(&config, verbose).await;
+    let conventional = profile.as_ref().is_none_or(StyleProfile::is_conventional);
+    let guidance = profile.as_ref().map(StyleProfile::guidance);
+    let scopes = if conventional {
+        infer_scopes(&config, &diff_filter, verbose).await
+    } else {
+        Vec::new()
+    };
+    let options = CommitMessageOptions {
+        oneline: cli.oneline,
+        template: &prompts.commit,
+        refinement: Refinement::default(),
+        echo: true,
+        style: guidance.as_deref().unwrap_or_default(),
+        scopes: &scopes,
+    };
+    let term = Term::stdout();
 
-    if message.is_empty() {
-        spinner.finish_and_clear();
-        println!("{} Empty commit message generated", style("✗").red());
-        std::process::exit(1);
-    }
+    // Check the message before anything uses it; unattended runs only
+    // auto-fix and warn. Conventional-commit rules don't apply to
+    // repositories with another convention.
+    let rules = lint_rules_for(&config, profile.as_ref());
+    let lint_rules = rules.as_ref();
+    let interactive =
+        !(cli.yes || config.auto_commit || cli.dry_run) && std::io::stdin().is_terminal();
+    let candidates = cli.candidates.unwrap_or(1) as usize;
+    let message = if candidates > 1 {
+        // An explicit --model wins over the configured candidate models
+        let models = match cli.model {
+            Some(_) => &[][..],
+            None => &config.candidate_models[..],
+        };
+        let assigned = assign_models(models, model, candidates);
+        let chosen = choose_candidate(
+            &api,
+            &assigned,
+            &diff,
+            &files,
+            options,
+            lint_rules,
+            interactive,
+        )
+        .await?;
+        let Some(chosen) = chosen else {
+            println!("{} Cancelled", style("—").dim());
+            finish_invocation(&api, verbose || config.show_usage);
+            return Ok(());
+        };
+        match lint_rules {
+            Some(rules) if interactive => match review_message(chosen.clone(), rules, true, true) {
+                Review::Ready(message) => message,
+                Review::Regenerate => exit_if_empty(
+                    generate_reviewed_message(
+                        &api, model, &diff, &files, options, lint_rules, true,
+                    )
+                    .await?,
+                ),
+            },
+            _ => chosen,
+        }
+    } else {
+        exit_if_empty(
+            generate_reviewed_message(&api, model, &diff, &files, options, lint_rules, interactive)
+                .await?,
+        )
+    };
 
     // Track if branch was already handled via --branch or --auto-branch flags
     let mut branch_already_handled = false;
@@ -293,20 +327,34 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         );
         branch_spinner.enable_steady_tick(std::time::Duration::from_millis(120));
 
-        let analysis = analyze_branch_alignment(
-            &client,
-            &api_key,
+        let analysis_result = analyze_branch_alignment(
+            &api,
             model,
+            &prompts.branch,
             &current_branch,
             &message,
             &files,
             &recent_commits,
         )
-        .await?;
+        .await;
 
         branch_spinner.finish_and_clear();
         let _ = term.show_cursor();
 
+        let analysis = match analysis_result {
+            Ok(analysis) => analysis,
+            Err(e) => {
+                println!(
+                    "{} Branch analysis failed; using offline heuristic",
+                    style("⚠").yellow()
+                );
+                if verbose {
+                    eprintln!("— {}", e);
+                }
+                fallback_branch_analysis(&current_branch, &message)
+            }
+        };
+
         if verbose {
             eprintln!("[Branch Analysis]: {}\n", analysis.reason);
         }
@@ -350,7 +398,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
+    let show_usage = verbose || config.show_usage;
+
     if cli.dry_run {
+        finish_invocation(&api, show_usage);
         return Ok(());
     }
 
@@ -359,20 +410,22 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         println!("{} Committed", style("✓").green());
     } else {
         let mut show_branch_option = !branch_already_handled;
+        let mut refiner = CommitRefiner::new(&api, model, &diff, &files, options, lint_rules);
         let mut current_message = message.clone();
 
         loop {
-            match prompt_commit(&current_message, show_branch_option) {
-                CommitAction::Commit(final_message) => {
+            match refiner.refine(current_message, show_branch_option).await? {
+                Refined::Commit(final_message) => {
                     run_git_commit(&final_message).await?;
                     println!("{} Committed", style("✓").green());
                     break;
                 }
-                CommitAction::Cancel => {
+                Refined::Cancel => {
                     println!("{} Cancelled", style("—").dim());
                     break;
                 }
-                CommitAction::CreateBranch(msg) => {
+                Refined::Empty => exit_on_empty_message(),
+                Refined::CreateBranch(msg) => {
                     current_message = msg;
 
                     let _ = term.hide_cursor();
@@ -387,9 +440,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     branch_spinner.enable_steady_tick(std::time::Duration::from_millis(120));
 
                     let suggested = match generate_branch_suggestion(
-                        &client,
-                        &api_key,
+                        &api,
                         model,
+                        &prompts.branch_name,
                         &current_message,
                     )
                     .await
@@ -447,5 +500,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
+    finish_invocation(&api, show_usage);
+
     Ok(())
 }
+
+/// Exits when the model came back with an empty message.
+fn exit_if_empty(message: String) -> String {
+    if message.is_empty() {
+        exit_on_empty_message();
+    }
+    message
+}
+
+fn exit_on_empty_message() -> ! {
+    println!("{} Empty commit message generated", style("✗").red());
+    std::process::exit(1);
+}
@@ -0,0 +1,109 @@
+//! Model metadata from the provider's model list.
+//!
+//! Only context windows are used, to size the diff (see [`crate::budget`]).
+//! The list is fetched at most once a day per endpoint and cached at
+//! `~/.cache/committer/models-<provider>-<hash>.json`, where the hash is of
+//! the base URL, so servers behind the same provider don't share a list.
+//! Providers without a model list, network errors, and unknown models all
+//! yield `None`, and callers fall back to a default.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::api::ApiClient;
+
+/// How long a cached model list is trusted, in seconds.
+const CACHE_TTL_SECS: u64 = 24 * 60 * 60;
+
+#[derive(Serialize, Deserialize)]
+struct ModelCache {
+    fetched_at: u64,
+    context_windows: BTreeMap<String, u64>,
+}
+
+/// Returns the path to the model list cache for `provider` at `base_url`.
+///
+/// Typically `~/.cache/committer/models-openrouter-<hash>.json` on Linux.
+fn cache_path(provider: &str, base_url: &str) -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("committer")
+        .join(format!("models-{}-{:016x}.json", provider, fnv1a(base_url)))
+}
+
+/// FNV-1a, which unlike `DefaultHasher` is stable across Rust releases.
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn read_cache(path: &PathBuf) -> Option<ModelCache> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    let cache: ModelCache = serde_json::from_str(&contents).ok()?;
+    (now_secs().saturating_sub(cache.fetched_at) < CACHE_TTL_SECS).then_some(cache)
+}
+
+fn write_cache(path: &PathBuf, cache: &ModelCache) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_string(cache)?)?;
+    Ok(())
+}
+
+/// Looks up the context window of `model` in tokens.
+pub async fn context_window(api: &ApiClient, model: &str, verbose: bool) -> Option<u64> {
+    let path = cache_path(api.provider().name(), api.provider().base_url());
+    if let Some(cache) = read_cache(&path) {
+        return cache.context_windows.get(model).copied();
+    }
+
+    let context_windows = match api.fetch_context_windows().await {
+        Ok(Some(models)) => models.into_iter().collect::<BTreeMap<_, _>>(),
+        Ok(None) => return None,
+        Err(e) => {
+            if verbose {
+                eprintln!("— Could not fetch model list: {}", e);
+            }
+            return None;
+        }
+    };
+
+    let cache = ModelCache {
+        fetched_at: now_secs(),
+        context_windows,
+    };
+    if let Err(e) = write_cache(&path, &cache) {
+        if verbose {
+            eprintln!("— Could not cache model list: {}", e);
+        }
+    }
+    cache.context_windows.get(model).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caches_each_endpoint_separately() {
+        let local = cache_path("openai", "http://localhost:8000/v1");
+        let hosted = cache_path("openai", "https://api.openai.com/v1");
+        assert_ne!(local, hosted);
+        assert_eq!(local, cache_path("openai", "http://localhost:8000/v1"));
+        assert!(local.to_string_lossy().ends_with(&format!(
+            "models-openai-{:016x}.json",
+            fnv1a("http://localhost:8000/v1")
+        )));
+    }
+}
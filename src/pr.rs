@@ -19,22 +19,57 @@
 
 use console::{style, Term};
 use indicatif::{ProgressBar, ProgressStyle};
-use reqwest::Client;
 use tokio::process::Command;
 
-use crate::api::{stream_commit_message, stream_pr_content, CommitMessageOptions};
+use crate::api::{
+    push_feedback, stream_pr_content, ApiClient, CommitMessageOptions, Message, PrContentOptions,
+    Refinement, Sampling,
+};
+use crate::auth::resolve_api_key;
 use crate::branch::PROTECTED_BRANCHES;
 use crate::cli::PrArgs;
-use crate::config::{get_api_key, Config};
+use crate::config::Config;
+use crate::exclude::DiffFilter;
+use crate::generate::{generate_reviewed_message, CommitRefiner, Refined};
 use crate::git::{
     branch_has_merge_base, check_git_installed, get_branch_commits, get_branch_diff,
     get_cached_remote_head, get_current_branch, get_git_diff, get_pr_changed_files,
     get_remote_default_branch, get_staged_files, get_uncommitted_changes, get_upstream_remote,
     push_branch_with_spinner, run_git_commit, stage_all_changes,
 };
-use crate::ui::{
-    prompt_commit, prompt_pr, prompt_uncommitted_changes, CommitAction, PrAction, UncommittedAction,
-};
+use crate::history::{lint_rules_for, load_style, StyleProfile};
+use crate::prompt::PromptTemplates;
+use crate::scopes::infer_scopes;
+use crate::secrets::guard_diff;
+use crate::summarize::prepare_diff;
+use crate::ui::{prompt_pr, prompt_uncommitted_changes, PrAction, UncommittedAction};
+use crate::usage::{finish_invocation, Ledger};
+
+/// Streams a PR title and body under a spinner.
+async fn generate_pr_content(
+    api: &ApiClient,
+    model: &str,
+    diff: &str,
+    files: &str,
+    options: PrContentOptions<'_>,
+) -> Result<(String, String), Box<dyn std::error::Error>> {
+    let term = Term::stdout();
+    let _ = term.hide_cursor();
+
+    let spinner = ProgressBar::new_spinner();
+    spinner.set_style(
+        ProgressStyle::default_spinner()
+            .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"])
+            .template("{spinner:.cyan} Generating PR content...")
+            .unwrap(),
+    );
+    spinner.enable_steady_tick(std::time::Duration::from_millis(80));
+
+    let result = stream_pr_content(api, model, diff, files, &spinner, options).await;
+
+    let _ = term.show_cursor();
+    result
+}
 
 /// Checks if the GitHub CLI (`gh`) is installed.
 pub async fn check_gh_installed() -> Result<(), Box<dyn std::error::Error>> {
@@ -191,19 +226,22 @@ pub async fn handle_pr_command(
     check_gh_installed().await?;
 
     // Get API key
-    let api_key = match get_api_key() {
-        Some(key) => key,
-        None => {
-            println!("{} No API key found", style("✗").red());
-            println!(
-                "  {} Set OPENROUTER_API_KEY environment variable",
-                style("→").dim()
-            );
-            std::process::exit(1);
-        }
-    };
+    // Local providers work without a key
+    let api_key = resolve_api_key(config.provider).await.map(|key| key.value);
+    if api_key.is_none() && config.provider.requires_api_key() {
+        println!("{} No API key found", style("✗").red());
+        println!(
+            "  {} Set {} or run 'committer auth login'",
+            style("→").dim(),
+            config.provider.api_key_env()
+        );
+        std::process::exit(1);
+    }
 
     let verbose = args.verbose || config.verbose;
+    let api = ApiClient::new(config, api_key, verbose)?.with_ledger(Ledger::open("pr").await);
+    let prompts = PromptTemplates::load().await;
+    let diff_filter = DiffFilter::load(&config.exclude).await;
     let model = args.model.as_ref().unwrap_or(&config.model);
 
     // Get current branch
@@ -245,58 +283,76 @@ pub async fn handle_pr_command(
                 // Stage all and run commit flow
                 stage_all_changes().await?;
 
-                let commit_diff = get_git_diff(true, verbose).await?;
-                let commit_files = get_staged_files(verbose).await?;
+                let commit_diff =
+                    get_git_diff(true, &diff_filter, config.diff_context, verbose).await?;
+                let commit_files = get_staged_files(&diff_filter, verbose).await?;
 
                 if commit_diff.trim().is_empty() {
                     println!("{} No changes to commit", style("→").dim());
                 } else {
-                    let client = Client::builder().build()?;
-                    let term = Term::stdout();
-                    let _ = term.hide_cursor();
-
-                    let spinner = ProgressBar::new_spinner();
-                    spinner.set_style(
-                        ProgressStyle::default_spinner()
-                            .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"])
-                            .template("{spinner:.cyan} Generating commit message...")
-                            .unwrap(),
-                    );
-                    spinner.enable_steady_tick(std::time::Duration::from_millis(80));
-
-                    let commit_msg = stream_commit_message(
-                        &client,
-                        &api_key,
+                    let commit_diff = guard_diff(commit_diff, config);
+                    let commit_diff = prepare_diff(
+                        &api,
+                        config,
+                        model,
+                        &commit_diff,
+                        &[&prompts.commit, &commit_files],
+                        &prompts.summary,
+                        verbose,
+                    )
+                    .await?;
+                    let profile = load_style(config, verbose).await;
+                    let conventional = profile.as_ref().is_none_or(StyleProfile::is_conventional);
+                    let guidance = profile.as_ref().map(StyleProfile::guidance);
+                    let scopes = if conventional {
+                        infer_scopes(config, &diff_filter, verbose).await
+                    } else {
+                        Vec::new()
+                    };
+                    let options = CommitMessageOptions {
+                        oneline: false,
+                        template: &prompts.commit,
+                        refinement: Refinement::default(),
+                        echo: true,
+                        style: guidance.as_deref().unwrap_or_default(),
+                        scopes: &scopes,
+                    };
+                    let rules = lint_rules_for(config, profile.as_ref());
+                    let lint_rules = rules.as_ref();
+                    let commit_msg = generate_reviewed_message(
+                        &api,
                         model,
                         &commit_diff,
                         &commit_files,
-                        &spinner,
-                        CommitMessageOptions {
-                            verbose,
-                            oneline: false,
-                        },
+                        options,
+                        lint_rules,
+                        true,
                     )
                     .await?;
 
-                    let _ = term.show_cursor();
-
-                    if !commit_msg.is_empty() {
-                        match prompt_commit(&commit_msg, false) {
-                            CommitAction::Commit(msg) => {
-                                run_git_commit(&msg).await?;
-                                println!("{} Committed", style("✓").green());
-                                println!();
-                            }
-                            CommitAction::Cancel => {
-                                println!(
-                                    "{} Commit cancelled, continuing with PR...",
-                                    style("→").dim()
-                                );
-                                println!();
-                            }
-                            _ => {}
+                    let mut refiner = CommitRefiner::new(
+                        &api,
+                        model,
+                        &commit_diff,
+                        &commit_files,
+                        options,
+                        lint_rules,
+                    );
+                    match refiner.refine(commit_msg, false).await? {
+                        Refined::Commit(msg) => {
+                            run_git_commit(&msg).await?;
+                            println!("{} Committed", style("✓").green());
                         }
+                        Refined::Empty => println!(
+                            "{} Empty commit message generated, continuing with PR...",
+                            style("→").dim()
+                        ),
+                        Refined::Cancel | Refined::CreateBranch(_) => println!(
+                            "{} Commit cancelled, continuing with PR...",
+                            style("→").dim()
+                        ),
                     }
+                    println!();
                 }
             }
             UncommittedAction::Skip => {
@@ -332,8 +388,8 @@ pub async fn handle_pr_command(
 
     // Get diff and file list
     let (diff_result, files_result) = tokio::join!(
-        get_branch_diff(&base_branch, verbose),
-        get_pr_changed_files(&base_branch, verbose)
+        get_branch_diff(&base_branch, &diff_filter, config.diff_context, verbose),
+        get_pr_changed_files(&base_branch, &diff_filter, verbose)
     );
 
     let diff = diff_result?;
@@ -349,31 +405,31 @@ pub async fn handle_pr_command(
         std::process::exit(1);
     }
 
-    // Create HTTP client
-    let client = Client::builder().build()?;
-    let term = Term::stdout();
-    let _ = term.hide_cursor();
-
-    // Stream PR content with spinner
-    let spinner = ProgressBar::new_spinner();
-    spinner.set_style(
-        ProgressStyle::default_spinner()
-            .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"])
-            .template("{spinner:.cyan} Generating PR content...")
-            .unwrap(),
-    );
-    spinner.enable_steady_tick(std::time::Duration::from_millis(80));
-
-    let (title, body) = stream_pr_content(
-        &client, &api_key, model, &diff, &files, &commits, &spinner, verbose,
+    let diff = guard_diff(diff, config);
+    let diff = prepare_diff(
+        &api,
+        config,
+        model,
+        &diff,
+        &[&prompts.pr, &files, &commits.join("\n")],
+        &prompts.summary,
+        verbose,
     )
     .await?;
 
-    let _ = term.show_cursor();
+    let options = PrContentOptions {
+        template: &prompts.pr,
+        commits: &commits,
+        refinement: Refinement::default(),
+    };
+    let (title, body) = generate_pr_content(&api, model, &diff, &files, options).await?;
+
+    let show_usage = verbose || config.show_usage;
 
     if args.dry_run {
         println!();
         println!("{} Dry run complete (PR not created)", style("✓").green());
+        finish_invocation(&api, show_usage);
         return Ok(());
     }
 
@@ -387,22 +443,55 @@ pub async fn handle_pr_command(
             style(&url).cyan().underlined()
         );
     } else {
-        match prompt_pr(&title, &body) {
-            PrAction::Create(final_title, final_body) => {
-                // Push branch if needed
-                push_branch_with_spinner(&current_branch).await?;
-                let url = create_pr(&final_title, &final_body, args.draft).await?;
-                println!(
-                    "{} PR created: {}",
-                    style("✓").green(),
-                    style(&url).cyan().underlined()
-                );
-            }
-            PrAction::Cancel => {
-                println!("{} Cancelled", style("—").dim());
+        let (mut title, mut body) = (title, body);
+        // Earlier replies and feedback, sent along when refining
+        let mut followups: Vec<Message> = Vec::new();
+
+        loop {
+            match prompt_pr(&title, &body) {
+                PrAction::Create(final_title, final_body) => {
+                    // Push branch if needed
+                    push_branch_with_spinner(&current_branch).await?;
+                    let url = create_pr(&final_title, &final_body, args.draft).await?;
+                    println!(
+                        "{} PR created: {}",
+                        style("✓").green(),
+                        style(&url).cyan().underlined()
+                    );
+                    break;
+                }
+                PrAction::Cancel => {
+                    println!("{} Cancelled", style("—").dim());
+                    break;
+                }
+                PrAction::Regenerate => {
+                    let regenerate = PrContentOptions {
+                        refinement: Refinement {
+                            followups: &followups,
+                            sampling: Some(Sampling::varied()),
+                        },
+                        ..options
+                    };
+                    (title, body) =
+                        generate_pr_content(&api, model, &diff, &files, regenerate).await?;
+                }
+                PrAction::Feedback(previous_title, previous_body, instruction) => {
+                    let previous = format!("{}\n\n{}", previous_title, previous_body);
+                    push_feedback(&mut followups, &previous, &instruction);
+                    let revise = PrContentOptions {
+                        refinement: Refinement {
+                            followups: &followups,
+                            sampling: None,
+                        },
+                        ..options
+                    };
+                    (title, body) = generate_pr_content(&api, model, &diff, &files, revise).await?;
+                }
             }
         }
     }
 
+    finish_invocation(&api, show_usage);
+
     Ok(())
 }
@@ -0,0 +1,399 @@
+//! Prompt templates for commit, PR, branch, summary, and split generation.
+//!
+//! Every prompt sent to the LLM starts from a template. The built-in
+//! templates can be overridden per user or per repository by dropping a text
+//! file named after the prompt kind into a `prompts` directory:
+//!
+//! 1. `<repo>/.committer/prompts/<kind>.txt`
+//! 2. `~/.config/committer/prompts/<kind>.txt` (next to `config.toml`)
+//! 3. The built-in template
+//!
+//! The first file found wins. `committer prompt show <kind>` prints the
+//! effective template and where it came from.
+//!
+//! # Placeholders
+//!
+//! | Kind          | Placeholders                                            |
+//! |---------------|---------------------------------------------------------|
+//! | `commit`      | `{diff}`, `{files}`, `{oneline}`, `{style}`, `{scopes}` |
+//! | `pr`          | `{diff}`, `{files}`, `{commits}`                        |
+//! | `branch`      | `{branch}`, `{commits}`, `{files}`, `{message}`         |
+//! | `branch-name` | `{message}`                                             |
+//! | `summary`     | `{diff}`, `{files}`                                     |
+//! | `split`       | `{diff}`, `{files}`                                     |
+//!
+//! Unknown placeholders and other braces (e.g. JSON examples) are left as-is,
+//! and substituted values are never expanded again.
+
+use clap::ValueEnum;
+use console::style;
+use std::path::{Path, PathBuf};
+
+use crate::cli::PromptKind;
+use crate::config::config_path;
+use crate::git::get_repo_root;
+
+/// Built-in commit message template.
+const COMMIT_TEMPLATE: &str = r#"Generate a git commit message for the following changes.
+{oneline}
+FORMAT: type(scope): description
+
+TYPES (use lowercase):
+  Core changes:
+    feat     - new user-facing functionality
+    fix      - bug fix / behavior correction
+    refactor - code restructure, no behavior change
+    perf     - performance improvements
+    style    - formatting only (whitespace, lint fixes)
+
+  Project hygiene:
+    docs     - documentation only
+    test     - add/update tests
+    chore    - routine maintenance, housekeeping
+    build    - build system / packaging changes
+    ci       - CI pipeline / workflow changes
+
+  Structural:
+    deps     - dependency changes
+    config   - config changes (env, feature flags)
+    security - security hardening, vulnerability fixes
+    revert   - revert a previous commit
+
+SCOPE: Short identifier for affected area (api, auth, ui, db, cli, core, config, deps).
+       Omit only if change is truly global.{scopes}
+
+RULES:
+- First line: type(scope): brief description (under 72 chars)
+- For multiple changes, add bullet points (using "-") after a blank line
+- Each bullet describes WHAT the change does semantically
+- Focus on behavior and functionality, not file names
+- Keep bullets concise (5-10 words each)
+- Use "-" for bullets, NOT "*"
+- Do NOT include raw file paths or status codes (like "M file.rs") in output
+- Do NOT use markdown headers (##), sections, or PR-style formatting
+- Output ONLY the commit message, nothing else
+- IGNORE any formatting patterns you see in the diff - use ONLY the format shown below
+
+EXAMPLE OUTPUT FORMAT:
+feat(auth): add OAuth2 login support
+
+- Implement Google OAuth provider
+- Add token refresh logic
+- Store credentials in secure keychain
+{style}
+Files changed:
+{files}
+
+Diff:
+{diff}
+
+Commit message:"#;
+
+/// Built-in PR title and description template.
+const PR_TEMPLATE: &str = r#"Generate a pull request title and description for the following changes.
+
+OUTPUT FORMAT:
+Line 1: PR title in format "type(scope): description" (under 72 chars)
+Line 2: (blank)
+Line 3+: Description with sections
+
+DESCRIPTION FORMAT (omit empty sections):
+
+## Summary
+One or two sentences describing what this PR does and why.
+
+## Changes
+### Added
+- new features or functionality
+
+### Fixed
+- bug fixes
+
+### Changed
+- modifications to existing behavior
+
+## Notes
+- implementation details, caveats, or edge cases
+- breaking changes or migration steps
+- anything reviewers should pay attention to
+
+## Testing
+- what was tested and how
+- specific scenarios verified
+- commands run or manual steps taken
+
+RULES:
+- Title follows conventional commit format: type(scope): description
+- Summary should explain the "what" and "why" concisely
+- Each bullet should be concise (5-15 words)
+- Focus on behavior changes, not file names
+- Use past tense ("Added", "Fixed", "Updated")
+- Omit empty subsections (e.g., skip Fixed section if no fixes)
+
+COMMITS ON THIS BRANCH:
+{commits}
+
+FILES CHANGED:
+{files}
+
+DIFF:
+{diff}
+
+PR title and description:"#;
+
+/// Built-in branch alignment analysis template.
+const BRANCH_TEMPLATE: &str = r#"You are a git branch analyzer. Determine if the current commit belongs on this branch.
+
+CURRENT BRANCH: {branch}
+
+RECENT COMMITS ON THIS BRANCH:
+{commits}
+
+FILES BEING CHANGED IN THIS COMMIT:
+{files}
+
+NEW COMMIT MESSAGE:
+{message}
+
+ANALYSIS RULES:
+1. Protected branches (main, master, develop, dev, staging, production) - NEVER match, always suggest a feature branch
+2. The commit scope/module MUST relate to the branch name. Example: branch "feat/auth-login" should only have auth-related commits, NOT unrelated features like "feat(db): add migration"
+3. Different commit TYPES (feat, fix, refactor, docs, test) on the SAME feature are fine - e.g., feat/auth can have "feat(auth): add login" then "fix(auth): handle edge case" then "docs(auth): add comments"
+4. If the commit introduces a NEW scope/module not mentioned in the branch name, flag as MISMATCH
+5. Be STRICT: when in doubt, flag as mismatch. It's better to suggest a new branch than pollute an existing one with unrelated work
+
+BRANCH NAMING CONVENTION: <type>/<scope>-<short-description>
+Examples: feat/auth-refresh-token, fix/ui-chat-scroll, refactor/server-ws-reconnect
+
+Respond with ONLY valid JSON:
+- If matches: {"matches": true, "reason": "brief explanation", "suggested_branch": null}
+- If mismatch: {"matches": false, "reason": "brief explanation", "suggested_branch": "type/scope-description"}"#;
+
+/// Built-in branch name suggestion template.
+const BRANCH_NAME_TEMPLATE: &str = r#"Given this commit message, suggest an appropriate git branch name.
+
+COMMIT MESSAGE:
+{message}
+
+BRANCH NAMING RULES:
+1. Use format: <type>/<scope>-<short-description>
+2. Type should match the commit type (feat, fix, docs, refactor, test, chore, etc.)
+3. Scope is the area/module being changed (auth, ui, server, api, etc.)
+4. Description should be kebab-case, concise (2-4 words)
+5. Keep the full branch name under 50 characters when possible
+
+BRANCH NAMING CONVENTION: <type>/<scope>-<short-description>
+Examples: feat/auth-refresh-token, fix/ui-chat-scroll, refactor/server-ws-reconnect
+
+Respond with ONLY the branch name, nothing else."#;
+
+/// Built-in template for summarizing one part of a very large diff.
+const SUMMARY_TEMPLATE: &str = r#"Summarize this part of a larger change. The summary will be used, together with summaries of the other parts, to write a commit message or pull request description.
+
+RULES:
+- 2-6 bullet points using "-"
+- Describe what changed and why it matters, not line-by-line edits
+- Name notable functions, types, or settings that were added, removed, or renamed
+- Do NOT repeat file paths that are already listed below
+- Output ONLY the bullet points
+
+FILES IN THIS PART:
+{files}
+
+DIFF:
+{diff}
+
+Summary:"#;
+
+/// Built-in template for splitting staged changes into focused commits.
+const SPLIT_TEMPLATE: &str = r#"These staged changes may mix unrelated work. Group the files into the smallest number of focused commits, each one logical change that could be reviewed on its own, and write a commit message for each.
+
+GROUPING RULES:
+- Every file belongs to exactly one commit; use the paths exactly as listed
+- Keep a change and its tests, docs, and config in the same commit
+- If everything is one logical change, return a single commit
+- Order commits so each builds on the previous ones (e.g. refactors before features that use them)
+
+MESSAGE FORMAT: type(scope): description (under 72 chars), optionally followed by a blank line and "-" bullets.
+Types: feat, fix, refactor, perf, style, docs, test, chore, build, ci, deps, config, security, revert
+
+FILES CHANGED:
+{files}
+
+DIFF:
+{diff}
+
+Respond with ONLY valid JSON:
+{"commits": [{"message": "feat(auth): add login\n\n- Support OAuth", "files": ["src/auth.rs"]}]}"#;
+
+/// Where an effective template was loaded from.
+pub enum TemplateSource {
+    Repo(PathBuf),
+    User(PathBuf),
+    BuiltIn,
+}
+
+/// The effective templates for every prompt kind.
+pub struct PromptTemplates {
+    pub commit: String,
+    pub pr: String,
+    pub branch: String,
+    pub branch_name: String,
+    pub summary: String,
+    pub split: String,
+}
+
+impl PromptTemplates {
+    /// Resolves all templates for the current repository.
+    pub async fn load() -> Self {
+        let repo_root = get_repo_root().await.ok();
+        let resolve = |kind| resolve_template(kind, repo_root.as_deref()).0;
+        Self {
+            commit: resolve(PromptKind::Commit),
+            pr: resolve(PromptKind::Pr),
+            branch: resolve(PromptKind::Branch),
+            branch_name: resolve(PromptKind::BranchName),
+            summary: resolve(PromptKind::Summary),
+            split: resolve(PromptKind::Split),
+        }
+    }
+}
+
+/// Returns the built-in template for a prompt kind.
+pub fn default_template(kind: PromptKind) -> &'static str {
+    match kind {
+        PromptKind::Commit => COMMIT_TEMPLATE,
+        PromptKind::Pr => PR_TEMPLATE,
+        PromptKind::Branch => BRANCH_TEMPLATE,
+        PromptKind::BranchName => BRANCH_NAME_TEMPLATE,
+        PromptKind::Summary => SUMMARY_TEMPLATE,
+        PromptKind::Split => SPLIT_TEMPLATE,
+    }
+}
+
+/// Returns the user-level prompts directory, next to the config file.
+pub fn user_prompts_dir() -> PathBuf {
+    config_path()
+        .parent()
+        .map(|dir| dir.join("prompts"))
+        .unwrap_or_else(|| PathBuf::from("prompts"))
+}
+
+/// Returns the repository-level prompts directory.
+pub fn repo_prompts_dir(repo_root: &Path) -> PathBuf {
+    repo_root.join(".committer").join("prompts")
+}
+
+fn template_file_name(kind: PromptKind) -> String {
+    let name = kind
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default();
+    format!("{}.txt", name)
+}
+
+/// Finds the effective template for `kind`: repo, then user, then built-in.
+///
+/// An override that exists but can't be read is reported and skipped.
+pub fn resolve_template(kind: PromptKind, repo_root: Option<&Path>) -> (String, TemplateSource) {
+    let file_name = template_file_name(kind);
+
+    if let Some(root) = repo_root {
+        let path = repo_prompts_dir(root).join(&file_name);
+        if let Some(template) = read_template(&path) {
+            return (template, TemplateSource::Repo(path));
+        }
+    }
+
+    let path = user_prompts_dir().join(&file_name);
+    if let Some(template) = read_template(&path) {
+        return (template, TemplateSource::User(path));
+    }
+
+    (default_template(kind).to_string(), TemplateSource::BuiltIn)
+}
+
+fn read_template(path: &Path) -> Option<String> {
+    match std::fs::read_to_string(path) {
+        Ok(template) => Some(template),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => {
+            eprintln!(
+                "{} Ignoring prompt template {}: {}",
+                style("⚠").yellow(),
+                path.display(),
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Substitutes `{name}` placeholders in a single pass.
+///
+/// Braces that don't name one of `vars` are copied through unchanged.
+pub fn render(template: &str, vars: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let value = after.find('}').and_then(|end| {
+            let name = &after[..end];
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| (end, *value))
+        });
+        match value {
+            Some((end, value)) => {
+                out.push_str(value);
+                rest = &after[end + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = after;
+            }
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+/// Main handler for `committer prompt show`.
+///
+/// The template goes to stdout so it can be redirected into an override file.
+pub async fn handle_prompt_show(kind: PromptKind) {
+    let repo_root = get_repo_root().await.ok();
+    let (template, source) = resolve_template(kind, repo_root.as_deref());
+
+    let source = match source {
+        TemplateSource::Repo(path) | TemplateSource::User(path) => path.display().to_string(),
+        TemplateSource::BuiltIn => "built-in".to_string(),
+    };
+    eprintln!("{} {}", style("source:").dim(), source);
+    println!("{}", template.trim_end());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_known_placeholders_once() {
+        let rendered = render(
+            "{files}\n{diff}\n{unknown}",
+            &[("files", "a.rs"), ("diff", "+ {files}")],
+        );
+
+        assert_eq!(rendered, "a.rs\n+ {files}\n{unknown}");
+    }
+
+    #[test]
+    fn leaves_json_braces_alone() {
+        let rendered = render(r#"{branch}: {"matches": true}"#, &[("branch", "feat/auth")]);
+
+        assert_eq!(rendered, r#"feat/auth: {"matches": true}"#);
+    }
+}
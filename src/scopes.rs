@@ -0,0 +1,350 @@
+//! Commit scopes derived from the repository layout.
+//!
+//! Rather than let the model invent a scope for every commit, staged paths
+//! are matched against the units the repository is made of, in order:
+//!
+//! 1. `scope_map` entries, written `path=scope` (paths may use globs)
+//! 2. Cargo workspace members
+//! 3. npm, Yarn, and pnpm workspace packages
+//! 4. Go modules, from `go.work` or the nearest `go.mod`
+//! 5. Top-level directories, except generic ones such as `src` and `tests`
+//!
+//! Units other than `scope_map` entries are named after their directory, and
+//! the most specific unit wins. The scopes of the staged files are passed to
+//! the model as the allowed set (see [`crate::api::build_prompt`]).
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::exclude::glob_match;
+use crate::exclude::DiffFilter;
+use crate::git::{excluded_paths, get_repo_root, get_staged_paths};
+
+/// Top-level directories too generic to name a scope.
+const GENERIC_DIRS: &[&str] = &[
+    "src",
+    "lib",
+    "test",
+    "tests",
+    "spec",
+    "bin",
+    "pkg",
+    "internal",
+    "cmd",
+    "dist",
+    "build",
+    "target",
+    "vendor",
+    "node_modules",
+];
+
+/// A directory pattern and, for `scope_map` entries, the scope it maps to.
+struct Unit {
+    pattern: String,
+    scope: Option<String>,
+}
+
+impl Unit {
+    fn new(pattern: &str, scope: Option<String>) -> Option<Self> {
+        let pattern = pattern
+            .trim()
+            .trim_start_matches("./")
+            .trim_end_matches('/');
+        // `packages/**` names each package, not every directory below one
+        let pattern = pattern
+            .strip_suffix("/**")
+            .map_or(pattern.to_string(), |p| format!("{}/*", p));
+        (!pattern.is_empty() && pattern != "." && !pattern.starts_with('!'))
+            .then_some(Self { pattern, scope })
+    }
+
+    /// The shortest leading part of `path` this unit matches.
+    fn matched_prefix<'a>(&self, path: &'a str) -> Option<&'a str> {
+        path.char_indices()
+            .filter(|(_, c)| *c == '/')
+            .map(|(i, _)| &path[..i])
+            .chain(std::iter::once(path))
+            .find(|prefix| glob_match(self.pattern.as_bytes(), prefix.as_bytes()))
+    }
+}
+
+/// The units of a repository, read from its manifests.
+pub struct Layout {
+    root: PathBuf,
+    mapped: Vec<Unit>,
+    members: Vec<Unit>,
+    go_work: bool,
+}
+
+fn cargo_members(root: &Path) -> Vec<String> {
+    let Ok(contents) = std::fs::read_to_string(root.join("Cargo.toml")) else {
+        return Vec::new();
+    };
+    let Ok(manifest) = contents.parse::<toml::Table>() else {
+        return Vec::new();
+    };
+    manifest
+        .get("workspace")
+        .and_then(|workspace| workspace.get("members"))
+        .and_then(|members| members.as_array())
+        .map(|members| {
+            members
+                .iter()
+                .filter_map(|member| member.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn npm_workspaces(root: &Path) -> Vec<String> {
+    let Ok(contents) = std::fs::read_to_string(root.join("package.json")) else {
+        return Vec::new();
+    };
+    let Ok(manifest) = serde_json::from_str::<serde_json::Value>(&contents) else {
+        return Vec::new();
+    };
+    // An array, or Yarn's `{ "packages": [...] }`
+    let workspaces = &manifest["workspaces"];
+    workspaces
+        .as_array()
+        .or_else(|| workspaces["packages"].as_array())
+        .map(|patterns| {
+            patterns
+                .iter()
+                .filter_map(|pattern| pattern.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Reads the `packages` list of `pnpm-workspace.yaml`.
+fn pnpm_workspaces(root: &Path) -> Vec<String> {
+    let Ok(contents) = std::fs::read_to_string(root.join("pnpm-workspace.yaml")) else {
+        return Vec::new();
+    };
+    let mut patterns = Vec::new();
+    let mut in_packages = false;
+    for line in contents.lines() {
+        if !line.starts_with([' ', '\t', '-']) && !line.trim().is_empty() {
+            in_packages = line.trim_end() == "packages:";
+            continue;
+        }
+        if let Some(item) = line.trim().strip_prefix('-').filter(|_| in_packages) {
+            let item = item.split(" #").next().unwrap_or_default().trim();
+            patterns.push(item.trim_matches(['\'', '"']).to_string());
+        }
+    }
+    patterns
+}
+
+/// Reads the `use` directives of `go.work`, or `None` without one.
+fn go_work_modules(root: &Path) -> Option<Vec<String>> {
+    let contents = std::fs::read_to_string(root.join("go.work")).ok()?;
+    let mut modules = Vec::new();
+    let mut in_block = false;
+    for line in contents.lines() {
+        let line = line.split("//").next().unwrap_or_default().trim();
+        if in_block {
+            if line == ")" {
+                in_block = false;
+            } else if !line.is_empty() {
+                modules.push(line.to_string());
+            }
+        } else if let Some(rest) = line.strip_prefix("use") {
+            match rest.trim() {
+                "(" => in_block = true,
+                module if !module.is_empty() => modules.push(module.to_string()),
+                _ => {}
+            }
+        }
+    }
+    Some(modules)
+}
+
+/// Parses `path=scope` entries, skipping malformed ones.
+fn parse_scope_map(entries: &[String]) -> Vec<Unit> {
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let (path, scope) = entry.split_once('=')?;
+            let scope = scope.trim();
+            (!scope.is_empty())
+                .then(|| Unit::new(path, Some(scope.to_string())))
+                .flatten()
+        })
+        .collect()
+}
+
+impl Layout {
+    /// Reads the manifests at the repository `root`.
+    pub fn load(root: &Path, scope_map: &[String]) -> Self {
+        let go_work = go_work_modules(root);
+        let members = cargo_members(root)
+            .into_iter()
+            .chain(npm_workspaces(root))
+            .chain(pnpm_workspaces(root))
+            .chain(go_work.clone().unwrap_or_default())
+            .filter_map(|pattern| Unit::new(&pattern, None))
+            .collect();
+        Self {
+            root: root.to_path_buf(),
+            mapped: parse_scope_map(scope_map),
+            members,
+            go_work: go_work.is_some(),
+        }
+    }
+
+    /// The scope for a path relative to the repository root, if any.
+    pub fn scope_for(&self, path: &str) -> Option<String> {
+        // The most specific mapping or member wins
+        let most_specific = |units: &[Unit]| {
+            units
+                .iter()
+                .filter_map(|unit| unit.matched_prefix(path).map(|prefix| (unit, prefix)))
+                .max_by_key(|(_, prefix)| prefix.len())
+                .map(|(unit, prefix)| unit.scope.clone().unwrap_or_else(|| dir_name(prefix)))
+        };
+        if let Some(scope) = most_specific(&self.mapped).or_else(|| most_specific(&self.members)) {
+            return Some(scope);
+        }
+
+        if !self.go_work {
+            let mut dir = Path::new(path).parent();
+            while let Some(current) = dir.filter(|d| !d.as_os_str().is_empty()) {
+                if self.root.join(current).join("go.mod").is_file() {
+                    return Some(dir_name(&current.to_string_lossy()));
+                }
+                dir = current.parent();
+            }
+        }
+
+        let (top, _) = path.split_once('/')?;
+        (!top.starts_with('.') && !GENERIC_DIRS.contains(&top)).then(|| top.to_string())
+    }
+
+    /// The distinct scopes of `paths`, the most used first.
+    pub fn scopes_for(&self, paths: &[String]) -> Vec<String> {
+        let mut counts: HashMap<String, (usize, usize)> = HashMap::new();
+        for (i, scope) in paths.iter().filter_map(|p| self.scope_for(p)).enumerate() {
+            counts.entry(scope).or_insert((0, i)).0 += 1;
+        }
+        let mut scopes: Vec<(String, (usize, usize))> = counts.into_iter().collect();
+        scopes.sort_by(|a, b| b.1 .0.cmp(&a.1 .0).then(a.1 .1.cmp(&b.1 .1)));
+        scopes.into_iter().map(|(scope, _)| scope).collect()
+    }
+}
+
+fn dir_name(path: &str) -> String {
+    path.rsplit('/').next().unwrap_or(path).to_string()
+}
+
+/// Scopes for the staged files `filter` keeps in the diff, or none when
+/// `infer_scopes` is off.
+pub async fn infer_scopes(config: &Config, filter: &DiffFilter, verbose: bool) -> Vec<String> {
+    if !config.infer_scopes {
+        return Vec::new();
+    }
+    let (Ok(root), Ok(mut paths)) = (get_repo_root().await, get_staged_paths().await) else {
+        return Vec::new();
+    };
+    // Lock files and generated code shouldn't name the change
+    let excluded = excluded_paths(
+        &paths.iter().map(String::as_str).collect::<Vec<_>>(),
+        filter,
+    )
+    .await;
+    paths.retain(|path| !excluded.contains(path));
+    let scopes = Layout::load(&root, &config.scope_map).scopes_for(&paths);
+    if verbose && !scopes.is_empty() {
+        eprintln!("— Scopes from layout: {}", scopes.join(", "));
+    }
+    scopes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn repo(name: &str, files: &[(&str, &str)]) -> TempDir {
+        let dir = TempDir::new(&format!("scopes-{}", name));
+        for (path, contents) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+        dir
+    }
+
+    fn paths(list: &[&str]) -> Vec<String> {
+        list.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn matches_workspace_members_and_scope_map() {
+        let root = repo(
+            "workspace",
+            &[
+                (
+                    "Cargo.toml",
+                    "[workspace]\nmembers = [\"crates/*\", \"tools/xtask\"]\n",
+                ),
+                (
+                    "package.json",
+                    r#"{"workspaces": {"packages": ["web/**"]}}"#,
+                ),
+            ],
+        );
+        let layout = Layout::load(&root, &["crates/core-*=core".to_string()]);
+
+        assert_eq!(layout.scope_for("crates/api/src/lib.rs").unwrap(), "api");
+        assert_eq!(
+            layout.scope_for("crates/core-io/src/lib.rs").unwrap(),
+            "core"
+        );
+        assert_eq!(layout.scope_for("tools/xtask/main.rs").unwrap(), "xtask");
+        assert_eq!(layout.scope_for("web/admin/src/app.ts").unwrap(), "admin");
+        assert_eq!(layout.scope_for("docs/guide.md").unwrap(), "docs");
+        assert_eq!(layout.scope_for("src/main.rs"), None);
+        assert_eq!(layout.scope_for(".github/ci.yml"), None);
+        assert_eq!(layout.scope_for("README.md"), None);
+
+        assert_eq!(
+            layout.scopes_for(&paths(&[
+                "crates/api/a.rs",
+                "web/admin/b.ts",
+                "web/admin/c.ts",
+                "README.md",
+            ])),
+            vec!["admin", "api"]
+        );
+    }
+
+    #[test]
+    fn reads_pnpm_and_go_workspaces() {
+        let root = repo(
+            "pnpm-go",
+            &[
+                (
+                    "pnpm-workspace.yaml",
+                    "packages:\n  - 'apps/*'\n  - \"!apps/legacy\"\ncatalog:\n  - ignored/*\n",
+                ),
+                (
+                    "go.work",
+                    "go 1.22\n\nuse (\n\t./svc/billing // payments\n)\n",
+                ),
+                ("svc/billing/go.mod", "module billing\n"),
+            ],
+        );
+        let layout = Layout::load(&root, &[]);
+
+        assert_eq!(layout.scope_for("apps/site/index.ts").unwrap(), "site");
+        assert_eq!(layout.scope_for("ignored/x/y.ts").unwrap(), "ignored");
+        assert_eq!(layout.scope_for("svc/billing/main.go").unwrap(), "billing");
+
+        let root = repo("gomod", &[("svc/users/go.mod", "module users\n")]);
+        let layout = Layout::load(&root, &[]);
+        assert_eq!(layout.scope_for("svc/users/api/h.go").unwrap(), "users");
+    }
+}
@@ -0,0 +1,539 @@
+//! Typed settings registry and the `committer config` subcommand.
+//!
+//! [`SETTINGS`] lists every option in [`Config`] with its type and a short
+//! description. The generic `config get/set/unset/list` commands, the
+//! `COMMITTER_*` environment overrides, and `config show` are all driven by
+//! it, so adding an option only means adding a field and a registry entry.
+//!
+//! # Value Syntax
+//!
+//! - Booleans: `true`/`false`, `yes`/`no`, `on`/`off`, `1`/`0`
+//! - Lists: comma-separated (`a/model, b/model`)
+//! - Choices: one of the listed values (`provider`)
+
+use console::style;
+
+use crate::api::MAX_RETRIES;
+use crate::auth::resolve_api_key;
+use crate::cli::ConfigAction;
+use crate::config::{
+    config_path, exit_with_config_error, load_global_table, save_global_table,
+    validate_config_files, Config, ConfigError, LayeredConfig,
+};
+
+/// The type of a setting's value.
+pub enum SettingType {
+    Bool,
+    Integer,
+    /// An integer of at least 1.
+    Positive,
+    /// A non-empty string.
+    Text,
+    /// A list of strings, written comma-separated on the command line.
+    List,
+    /// One of a fixed set of strings.
+    Choice(&'static [&'static str]),
+    /// An integer between two bounds, inclusive.
+    Range(u32, u32),
+}
+
+/// A single configuration option.
+pub struct Setting {
+    /// Key in `config.toml`, also used by `config get/set/unset`.
+    pub key: &'static str,
+    pub ty: SettingType,
+    pub description: &'static str,
+}
+
+/// Every configuration option, in display order.
+pub const SETTINGS: &[Setting] = &[
+    Setting {
+        key: "model",
+        ty: SettingType::Text,
+        description: "Model used for commits, branches, and PRs",
+    },
+    Setting {
+        key: "fallback_models",
+        ty: SettingType::List,
+        description: "Models tried in order when the primary model keeps failing",
+    },
+    Setting {
+        key: "candidate_models",
+        ty: SettingType::List,
+        description: "Models asked in turn for --candidates messages",
+    },
+    Setting {
+        key: "provider",
+        ty: SettingType::Choice(&[
+            "openrouter",
+            "openai",
+            "anthropic",
+            "ollama",
+            "llamacpp",
+            "replay",
+        ]),
+        description: "Backend that receives API requests",
+    },
+    Setting {
+        key: "base_url",
+        ty: SettingType::Text,
+        description: "Override for the provider's API base URL",
+    },
+    Setting {
+        key: "auto_commit",
+        ty: SettingType::Bool,
+        description: "Commit without asking for confirmation",
+    },
+    Setting {
+        key: "commit_after_branch",
+        ty: SettingType::Bool,
+        description: "Commit right after creating a branch via the 'b' option",
+    },
+    Setting {
+        key: "verbose",
+        ty: SettingType::Bool,
+        description: "Show detailed operation logs",
+    },
+    Setting {
+        key: "show_usage",
+        ty: SettingType::Bool,
+        description: "Print a token and cost summary after each commit or PR",
+    },
+    Setting {
+        key: "max_retries",
+        ty: SettingType::Range(0, MAX_RETRIES),
+        description: "Retries per model for timeouts, rate limits, and gateway errors",
+    },
+    Setting {
+        key: "connect_timeout_secs",
+        ty: SettingType::Positive,
+        description: "Seconds to wait for a connection to the API",
+    },
+    Setting {
+        key: "read_timeout_secs",
+        ty: SettingType::Positive,
+        description: "Seconds to wait for each read from the API",
+    },
+    Setting {
+        key: "exclude",
+        ty: SettingType::List,
+        description: "Gitignore-style patterns for files left out of the diff",
+    },
+    Setting {
+        key: "context_window",
+        ty: SettingType::Integer,
+        description: "Model context window in tokens, overriding the provider's model list",
+    },
+    Setting {
+        key: "max_diff_tokens",
+        ty: SettingType::Integer,
+        description: "Most tokens spent on the diff, however large the context window",
+    },
+    Setting {
+        key: "summarize_above_tokens",
+        ty: SettingType::Integer,
+        description: "Summarize diffs larger than this many tokens part by part",
+    },
+    Setting {
+        key: "diff_context",
+        ty: SettingType::Choice(&["lines", "function", "auto"]),
+        description: "Context around each change; auto picks per file by hunk size",
+    },
+    Setting {
+        key: "secret_scan",
+        ty: SettingType::Choice(&["warn", "redact", "block"]),
+        description: "What to do with possible secrets in the diff",
+    },
+    Setting {
+        key: "secret_allowlist",
+        ty: SettingType::List,
+        description: "Regexes for secret scan false positives (value or file path)",
+    },
+    Setting {
+        key: "learn_style",
+        ty: SettingType::Bool,
+        description: "Follow the commit style learned from recent history",
+    },
+    Setting {
+        key: "learn_style_commits",
+        ty: SettingType::Integer,
+        description: "How many recent commits the style is learned from",
+    },
+    Setting {
+        key: "infer_scopes",
+        ty: SettingType::Bool,
+        description: "Derive allowed scopes from the workspace layout",
+    },
+    Setting {
+        key: "scope_map",
+        ty: SettingType::List,
+        description: "Scopes for paths, as path=scope entries",
+    },
+    Setting {
+        key: "lint",
+        ty: SettingType::Bool,
+        description: "Check messages against conventional-commit rules before committing",
+    },
+    Setting {
+        key: "lint_types",
+        ty: SettingType::List,
+        description: "Commit types the linter accepts",
+    },
+    Setting {
+        key: "lint_scopes",
+        ty: SettingType::List,
+        description: "Scopes the linter accepts (any when empty)",
+    },
+    Setting {
+        key: "lint_subject_max_length",
+        ty: SettingType::Integer,
+        description: "Longest subject line the linter accepts",
+    },
+    Setting {
+        key: "lint_body_max_line_length",
+        ty: SettingType::Integer,
+        description: "Wrap body lines longer than this (0 to disable)",
+    },
+    Setting {
+        key: "fixtures_dir",
+        ty: SettingType::Text,
+        description: "Directory of recorded responses for the replay provider",
+    },
+    Setting {
+        key: "record_fixtures",
+        ty: SettingType::Bool,
+        description: "Save every API request and response into fixtures_dir",
+    },
+];
+
+/// Looks up a setting by key.
+pub fn find_setting(key: &str) -> Option<&'static Setting> {
+    SETTINGS.iter().find(|setting| setting.key == key)
+}
+
+impl Setting {
+    /// Parses a command-line or environment value into its TOML form.
+    pub fn parse(&self, raw: &str) -> Result<toml::Value, String> {
+        let raw = raw.trim();
+        match self.ty {
+            SettingType::Bool => match raw.to_lowercase().as_str() {
+                "true" | "yes" | "on" | "1" => Ok(toml::Value::Boolean(true)),
+                "false" | "no" | "off" | "0" => Ok(toml::Value::Boolean(false)),
+                _ => Err(format!("expected true or false, got '{}'", raw)),
+            },
+            SettingType::Integer => raw
+                .parse::<u32>()
+                .map(|n| toml::Value::Integer(i64::from(n)))
+                .map_err(|_| format!("expected a non-negative number, got '{}'", raw)),
+            SettingType::Positive => raw
+                .parse::<u32>()
+                .ok()
+                .filter(|n| *n >= 1)
+                .map(|n| toml::Value::Integer(i64::from(n)))
+                .ok_or_else(|| format!("expected a number of at least 1, got '{}'", raw)),
+            SettingType::Range(min, max) => raw
+                .parse::<u32>()
+                .ok()
+                .filter(|n| (min..=max).contains(n))
+                .map(|n| toml::Value::Integer(i64::from(n)))
+                .ok_or_else(|| format!("expected a number from {} to {}, got '{}'", min, max, raw)),
+            SettingType::Text if raw.is_empty() => Err("expected a value, got nothing".to_string()),
+            SettingType::Text => Ok(toml::Value::String(raw.to_string())),
+            SettingType::List => Ok(toml::Value::Array(
+                raw.split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .map(|item| toml::Value::String(item.to_string()))
+                    .collect(),
+            )),
+            SettingType::Choice(choices) => {
+                let value = raw.to_lowercase();
+                if choices.contains(&value.as_str()) {
+                    Ok(toml::Value::String(value))
+                } else {
+                    Err(format!(
+                        "expected one of {}, got '{}'",
+                        choices.join(", "),
+                        raw
+                    ))
+                }
+            }
+        }
+    }
+
+    fn type_name(&self) -> String {
+        match self.ty {
+            SettingType::Bool => "bool".to_string(),
+            SettingType::Integer => "integer".to_string(),
+            SettingType::Positive => "integer ≥ 1".to_string(),
+            SettingType::Text => "string".to_string(),
+            SettingType::List => "list".to_string(),
+            SettingType::Choice(choices) => choices.join("|"),
+            SettingType::Range(min, max) => format!("{}-{}", min, max),
+        }
+    }
+}
+
+/// Returns `config`'s value for `key`, or `None` if it's unset.
+fn config_value(config: &Config, key: &str) -> Option<toml::Value> {
+    toml::Table::try_from(config)
+        .ok()
+        .and_then(|mut table| table.remove(key))
+}
+
+/// Formats a value the way `config set` accepts it.
+fn format_value(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(s) => s.clone(),
+        toml::Value::Array(items) => items
+            .iter()
+            .map(format_value)
+            .collect::<Vec<_>>()
+            .join(", "),
+        other => other.to_string(),
+    }
+}
+
+fn styled_value(value: Option<&toml::Value>) -> console::StyledObject<String> {
+    match value {
+        Some(toml::Value::Boolean(true)) => style("true".to_string()).green(),
+        Some(toml::Value::Boolean(false)) => style("false".to_string()).dim(),
+        Some(toml::Value::Array(items)) if items.is_empty() => style("(none)".to_string()).dim(),
+        Some(value) => style(format_value(value)).yellow(),
+        None => style("(none)".to_string()).dim(),
+    }
+}
+
+/// Reports an unknown key and exits.
+fn exit_unknown_setting(key: &str) -> ! {
+    println!("{} Unknown setting '{}'", style("✗").red(), key);
+    println!(
+        "  {} Run 'committer config list' to see all settings",
+        style("→").dim()
+    );
+    std::process::exit(1);
+}
+
+/// Main handler for the `committer config` subcommand.
+///
+/// `layered` is only required by the actions that read the effective
+/// configuration; `validate` and the writers work on the files directly.
+pub async fn handle_config_command(
+    action: ConfigAction,
+    layered: Result<LayeredConfig, ConfigError>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match action {
+        ConfigAction::Show => {
+            let layered = layered.unwrap_or_else(|e| exit_with_config_error(&e));
+            show_config(&layered).await;
+        }
+        ConfigAction::Validate => {
+            let files = validate_config_files().await;
+            if files.is_empty() {
+                println!("{} No config files; using defaults", style("✓").green());
+            }
+            let mut valid = true;
+            for (path, error) in &files {
+                match error {
+                    None => println!("{} {}", style("✓").green(), path.display()),
+                    Some(e) => {
+                        valid = false;
+                        println!("{} {}", style("✗").red(), e);
+                    }
+                }
+            }
+            if !valid {
+                std::process::exit(1);
+            }
+        }
+        ConfigAction::List { defaults } => {
+            let layered = if defaults {
+                None
+            } else {
+                Some(layered.unwrap_or_else(|e| exit_with_config_error(&e)))
+            };
+            let default_config = Config::default();
+            for setting in SETTINGS {
+                let value = match &layered {
+                    Some(layered) => config_value(&layered.config, setting.key),
+                    None => config_value(&default_config, setting.key),
+                };
+                let source = layered
+                    .as_ref()
+                    .map(|layered| format!(" ({})", layered.source(setting.key)))
+                    .unwrap_or_default();
+                println!(
+                    "{} {}{} {}",
+                    style(format!("{}:", setting.key)).cyan(),
+                    styled_value(value.as_ref()),
+                    style(source).dim(),
+                    style(format!("[{}]", setting.type_name())).dim()
+                );
+                println!("    {}", style(setting.description).dim());
+            }
+        }
+        ConfigAction::Get { key } => {
+            if find_setting(&key).is_none() {
+                exit_unknown_setting(&key);
+            }
+            let layered = layered.unwrap_or_else(|e| exit_with_config_error(&e));
+            if let Some(value) = config_value(&layered.config, &key) {
+                println!("{}", format_value(&value));
+            }
+        }
+        ConfigAction::Set { key, value } => {
+            let Some(setting) = find_setting(&key) else {
+                exit_unknown_setting(&key);
+            };
+            let parsed = match setting.parse(&value) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    println!("{} Invalid value for {}: {}", style("✗").red(), key, e);
+                    std::process::exit(1);
+                }
+            };
+
+            let mut table = load_global_table().unwrap_or_else(|e| exit_with_config_error(&e));
+            table.insert(key.clone(), parsed.clone());
+            if let Err(e) = toml::Value::Table(table.clone()).try_into::<Config>() {
+                println!(
+                    "{} Invalid value for {}: {}",
+                    style("✗").red(),
+                    key,
+                    e.message().trim()
+                );
+                std::process::exit(1);
+            }
+            save_global_table(&table)?;
+
+            println!(
+                "{} {} set to {}",
+                style("✓").green(),
+                style(&key).cyan(),
+                styled_value(Some(&parsed))
+            );
+        }
+        ConfigAction::Unset { key } => {
+            if find_setting(&key).is_none() {
+                exit_unknown_setting(&key);
+            }
+            let mut table = load_global_table().unwrap_or_else(|e| exit_with_config_error(&e));
+            if table.remove(&key).is_some() {
+                save_global_table(&table)?;
+            }
+
+            let default = config_value(&Config::default(), &key);
+            println!(
+                "{} {} reset to default {}",
+                style("✓").green(),
+                style(&key).cyan(),
+                styled_value(default.as_ref())
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Prints the effective configuration, marking where each value came from.
+async fn show_config(layered: &LayeredConfig) {
+    let config = &layered.config;
+
+    println!("{}", style("Configuration").bold());
+    println!("  {} {}", style("file:").dim(), config_path().display());
+    if let Some(repo_path) = &layered.repo_path {
+        println!("  {} {}", style("repo file:").dim(), repo_path.display());
+    }
+    println!();
+
+    for setting in SETTINGS {
+        let value = config_value(config, setting.key);
+        // Unset optional values only clutter the output
+        let is_unset = match &value {
+            None => true,
+            Some(toml::Value::Array(items)) => items.is_empty(),
+            Some(_) => false,
+        };
+        if is_unset {
+            continue;
+        }
+        println!(
+            "  {} {} {}",
+            style(format!("{}:", setting.key)).cyan(),
+            styled_value(value.as_ref()),
+            style(format!("({})", layered.source(setting.key))).dim()
+        );
+    }
+
+    // Report the key's source, never the key itself
+    println!(
+        "  {} {}",
+        style("api_key:").cyan(),
+        if let Some(key) = resolve_api_key(config.provider).await {
+            style(format!("[set via {}]", key.source)).green()
+        } else if !config.provider.requires_api_key() {
+            style("[not required]".to_string()).dim()
+        } else {
+            style("[not set]".to_string()).red()
+        }
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_values_by_setting_type() {
+        let parse = |key: &str, raw: &str| find_setting(key).unwrap().parse(raw);
+
+        assert_eq!(parse("verbose", "yes"), Ok(toml::Value::Boolean(true)));
+        assert!(parse("verbose", "maybe").is_err());
+        assert_eq!(
+            parse("model", "openai/gpt-4o"),
+            Ok(toml::Value::String("openai/gpt-4o".to_string()))
+        );
+        assert_eq!(
+            parse("fallback_models", "a, b"),
+            Ok(toml::Value::Array(vec![
+                toml::Value::String("a".to_string()),
+                toml::Value::String("b".to_string()),
+            ]))
+        );
+        assert!(parse("max_retries", "lots").is_err());
+        assert!(parse("max_retries", "4000000000").is_err());
+        assert_eq!(
+            parse("max_retries", "10").unwrap(),
+            toml::Value::Integer(10)
+        );
+        assert!(parse("provider", "bedrock").is_err());
+        assert!(parse("connect_timeout_secs", "0").is_err());
+        assert_eq!(
+            parse("read_timeout_secs", "1").unwrap(),
+            toml::Value::Integer(1)
+        );
+        assert!(parse("base_url", "  ").is_err());
+    }
+
+    #[test]
+    fn registry_covers_every_config_field() {
+        let config = Config {
+            base_url: Some(String::new()),
+            fallback_models: vec![String::new()],
+            candidate_models: vec![String::new()],
+            exclude: vec![String::new()],
+            fixtures_dir: Some(Default::default()),
+            context_window: Some(0),
+            summarize_above_tokens: Some(0),
+            secret_allowlist: vec![String::new()],
+            lint_scopes: vec![String::new()],
+            scope_map: vec![String::new()],
+            ..Config::default()
+        };
+        let table = toml::Table::try_from(&config).unwrap();
+
+        for key in table.keys() {
+            assert!(find_setting(key).is_some(), "{} missing from SETTINGS", key);
+        }
+        assert_eq!(table.len(), SETTINGS.len());
+    }
+}
@@ -0,0 +1,596 @@
+//! Splitting staged changes into several focused commits.
+//!
+//! `committer split` asks the model to group the staged files into logical
+//! commits, each with its own message, and shows the plan for review:
+//!
+//! ```text
+//! 1. refactor(api): extract retry policy
+//!      src/api.rs
+//!      src/retry.rs
+//! 2. feat(cli): add --timeout flag
+//!      src/cli.rs
+//!      README.md
+//! ```
+//!
+//! The plan can be edited in `$EDITOR` to change messages, move files
+//! between commits, or reorder them. Each commit is then created by
+//! resetting the index to `HEAD` and restaging only that commit's paths from
+//! a snapshot of the original index. Files the plan leaves out stay staged.
+//!
+//! Grouping is per file; a renamed file keeps both of its paths together.
+//! If any commit fails, `HEAD` and the index are put back exactly as they
+//! were, so a split either completes or changes nothing.
+
+use console::style;
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use tokio::process::Command;
+
+use crate::api::{send_chat, ApiClient, ChatRequest, Message, ResponseFormat};
+use crate::auth::resolve_api_key;
+use crate::cli::SplitArgs;
+use crate::config::Config;
+use crate::exclude::DiffFilter;
+use crate::git::{check_git_installed, get_git_diff, get_staged_files, run_git_commit};
+use crate::history::{lint_rules_for, load_style};
+use crate::lint::fix;
+use crate::prompt::{render, PromptTemplates};
+use crate::secrets::guard_diff;
+use crate::summarize::prepare_diff;
+use crate::ui::{prompt_split, SplitAction};
+use crate::usage::{finish_invocation, Ledger};
+
+/// Starts each commit in the editable plan.
+const COMMIT_MARKER: &str = "=== commit ===";
+
+const PLAN_HELP: &str = "\
+# Each commit starts with a \"=== commit ===\" line, followed by its
+# \"file:\" lines and its message. Reorder blocks to reorder commits, move
+# \"file:\" lines between them to regroup, or delete a block to leave its
+# files staged. Lines starting with '#' are ignored.
+";
+
+/// One commit of a split plan.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct PlannedCommit {
+    pub message: String,
+    pub files: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct SplitReply {
+    commits: Vec<PlannedCommit>,
+}
+
+/// A staged file, with its old path when it was renamed.
+#[derive(Debug, Clone, PartialEq)]
+struct StagedPath {
+    path: String,
+    old_path: Option<String>,
+}
+
+impl StagedPath {
+    fn paths(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.path.as_str()).chain(self.old_path.as_deref())
+    }
+}
+
+/// Runs a git command, returning trimmed stdout.
+///
+/// Pathspecs are taken literally, so file names with glob characters only
+/// match themselves.
+async fn git(args: &[&str]) -> Result<String, Box<dyn std::error::Error>> {
+    let output = Command::new("git")
+        .args(args)
+        .env("GIT_LITERAL_PATHSPECS", "1")
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("git {} failed: {}", args[0], stderr.trim()).into());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Parses `git diff --cached --name-status -z` output.
+fn parse_name_status(output: &str) -> Vec<StagedPath> {
+    let mut fields = output.split('\0').filter(|field| !field.is_empty());
+    let mut staged = Vec::new();
+    while let Some(status) = fields.next() {
+        let moved = status.starts_with('R');
+        let Some(first) = fields.next() else { break };
+        if moved {
+            let Some(second) = fields.next() else { break };
+            staged.push(StagedPath {
+                path: second.to_string(),
+                old_path: Some(first.to_string()),
+            });
+        } else {
+            staged.push(StagedPath {
+                path: first.to_string(),
+                old_path: None,
+            });
+        }
+    }
+    staged
+}
+
+/// Lists staged entries, pairing renamed files with their old paths.
+async fn get_staged_entries() -> Result<Vec<StagedPath>, Box<dyn std::error::Error>> {
+    let output = git(&["diff", "--cached", "--name-status", "-M", "-z"]).await?;
+    Ok(parse_name_status(&output))
+}
+
+/// Checks a plan against the staged files.
+///
+/// Paths that aren't staged are dropped, a path listed twice stays in its
+/// first commit, and commits left without files are removed. Returns the
+/// cleaned plan and the staged files no commit includes.
+fn normalize_plan(
+    plan: Vec<PlannedCommit>,
+    staged: &[StagedPath],
+) -> (Vec<PlannedCommit>, Vec<String>) {
+    // Either side of a rename refers to the whole entry
+    let index: HashMap<&str, usize> = staged
+        .iter()
+        .enumerate()
+        .flat_map(|(i, entry)| entry.paths().map(move |path| (path, i)))
+        .collect();
+    let mut assigned = vec![false; staged.len()];
+
+    let mut commits = Vec::new();
+    for commit in plan {
+        let mut files = Vec::new();
+        for file in &commit.files {
+            if let Some(&i) = index.get(file.trim()) {
+                if !assigned[i] {
+                    assigned[i] = true;
+                    files.push(staged[i].path.clone());
+                }
+            }
+        }
+        if !files.is_empty() {
+            commits.push(PlannedCommit {
+                message: commit.message.trim().to_string(),
+                files,
+            });
+        }
+    }
+
+    let unassigned = staged
+        .iter()
+        .zip(assigned)
+        .filter(|(_, assigned)| !assigned)
+        .map(|(entry, _)| entry.path.clone())
+        .collect();
+    (commits, unassigned)
+}
+
+/// Parses a split reply, repairing JSON wrapped in prose or code fences.
+fn parse_reply(content: &str) -> Result<Vec<PlannedCommit>, serde_json::Error> {
+    let content = content.trim();
+    let reply: SplitReply = match serde_json::from_str(content) {
+        Ok(reply) => reply,
+        Err(e) => match (content.find('{'), content.rfind('}')) {
+            (Some(start), Some(end)) if start < end => serde_json::from_str(&content[start..=end])?,
+            _ => return Err(e),
+        },
+    };
+    Ok(reply.commits)
+}
+
+fn split_request(model: &str, prompt: &str) -> ChatRequest {
+    let mut request = ChatRequest::user(model, prompt.to_string(), false);
+    request.response_format = Some(ResponseFormat::json_schema(
+        "split_plan",
+        json!({
+            "type": "object",
+            "properties": {
+                "commits": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "message": { "type": "string" },
+                            "files": { "type": "array", "items": { "type": "string" } }
+                        },
+                        "required": ["message", "files"],
+                        "additionalProperties": false
+                    }
+                }
+            },
+            "required": ["commits"],
+            "additionalProperties": false
+        }),
+    ));
+    request
+}
+
+/// Asks the model for a split plan, re-asking once if the reply isn't valid
+/// JSON.
+async fn plan_commits(
+    api: &ApiClient,
+    model: &str,
+    template: &str,
+    diff: &str,
+    files: &str,
+) -> Result<Vec<PlannedCommit>, Box<dyn std::error::Error>> {
+    let prompt = render(template, &[("diff", diff), ("files", files)]);
+
+    let content = send_chat(api, split_request(model, &prompt)).await?;
+    let error = match parse_reply(&content) {
+        Ok(plan) => return Ok(plan),
+        Err(e) => e,
+    };
+
+    let mut request = split_request(model, &prompt);
+    request.messages.push(Message {
+        role: "assistant".to_string(),
+        content,
+    });
+    request.messages.push(Message {
+        role: "user".to_string(),
+        content: format!(
+            "That reply was not valid JSON ({}). Respond again with ONLY the JSON object.",
+            error
+        ),
+    });
+
+    let content = send_chat(api, request).await?;
+    parse_reply(&content).map_err(|e| {
+        format!(
+            "Failed to parse split plan: {} - raw: {}",
+            e,
+            content.trim()
+        )
+        .into()
+    })
+}
+
+/// Renders a plan for editing.
+fn plan_to_text(commits: &[PlannedCommit], unassigned: &[String]) -> String {
+    let mut text = String::from(PLAN_HELP);
+    for commit in commits {
+        text.push('\n');
+        text.push_str(COMMIT_MARKER);
+        text.push('\n');
+        for file in &commit.files {
+            text.push_str(&format!("file: {}\n", file));
+        }
+        text.push('\n');
+        text.push_str(&commit.message);
+        text.push('\n');
+    }
+    if !unassigned.is_empty() {
+        text.push_str("\n# Left staged (add \"file:\" lines to a commit to include):\n");
+        for file in unassigned {
+            text.push_str(&format!("#   {}\n", file));
+        }
+    }
+    text
+}
+
+/// Parses an edited plan back into commits.
+fn plan_from_text(text: &str) -> Result<Vec<PlannedCommit>, String> {
+    let mut blocks: Vec<(Vec<String>, Vec<&str>)> = Vec::new();
+    for line in text.lines().filter(|line| !line.starts_with('#')) {
+        if line.trim() == COMMIT_MARKER {
+            blocks.push((Vec::new(), Vec::new()));
+        } else if let Some((files, message)) = blocks.last_mut() {
+            match line.strip_prefix("file:") {
+                Some(file) => files.push(file.trim().to_string()),
+                None => message.push(line),
+            }
+        }
+    }
+
+    blocks
+        .into_iter()
+        .enumerate()
+        .map(|(i, (files, message))| {
+            let message = message.join("\n").trim().to_string();
+            if message.is_empty() {
+                return Err(format!("Commit {} has no message", i + 1));
+            }
+            Ok(PlannedCommit { message, files })
+        })
+        .collect()
+}
+
+fn print_plan(commits: &[PlannedCommit], unassigned: &[String]) {
+    println!();
+    for (i, commit) in commits.iter().enumerate() {
+        let mut lines = commit.message.lines();
+        println!(
+            "{} {}",
+            style(format!("{}.", i + 1)).cyan().bold(),
+            lines.next().unwrap_or("")
+        );
+        for line in lines.skip_while(|line| line.trim().is_empty()) {
+            println!("   {}", style(line).dim());
+        }
+        for file in &commit.files {
+            println!("     {}", style(file).green());
+        }
+    }
+    if !unassigned.is_empty() {
+        println!(
+            "{} Left staged, in no commit: {}",
+            style("⚠").yellow(),
+            unassigned.join(", ")
+        );
+    }
+}
+
+/// Puts `HEAD` and the index back to where the split started.
+async fn restore(head: &str, tree: &str) -> Result<(), Box<dyn std::error::Error>> {
+    git(&["reset", "-q", "--soft", head]).await?;
+    git(&["read-tree", tree]).await?;
+    Ok(())
+}
+
+/// Restages `paths` from `tree` on top of `HEAD`.
+async fn stage_from(tree: &str, paths: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    git(&["reset", "-q"]).await?;
+    if !paths.is_empty() {
+        let mut args = vec!["restore", "--staged", "--source", tree, "--"];
+        args.extend_from_slice(paths);
+        git(&args).await?;
+    }
+    Ok(())
+}
+
+/// Creates each planned commit in order, leaving unassigned files staged.
+async fn create_commits(
+    commits: &[PlannedCommit],
+    unassigned: &[String],
+    staged: &[StagedPath],
+    tree: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let entries: HashMap<&str, &StagedPath> = staged
+        .iter()
+        .map(|entry| (entry.path.as_str(), entry))
+        .collect();
+    let paths_of = |files: &[String]| -> Vec<&str> {
+        files
+            .iter()
+            .filter_map(|file| entries.get(file.as_str()))
+            .flat_map(|entry| entry.paths())
+            .collect()
+    };
+
+    for (i, commit) in commits.iter().enumerate() {
+        stage_from(tree, &paths_of(&commit.files)).await?;
+        run_git_commit(&commit.message).await?;
+        println!(
+            "{} [{}/{}] {}",
+            style("✓").green(),
+            i + 1,
+            commits.len(),
+            commit.message.lines().next().unwrap_or("")
+        );
+    }
+    stage_from(tree, &paths_of(unassigned)).await
+}
+
+/// Main handler for `committer split`.
+pub async fn handle_split_command(
+    args: SplitArgs,
+    config: &Config,
+) -> Result<(), Box<dyn std::error::Error>> {
+    check_git_installed().await?;
+
+    // Local providers work without a key
+    let api_key = resolve_api_key(config.provider).await.map(|key| key.value);
+    if api_key.is_none() && config.provider.requires_api_key() {
+        println!("{} No API key found", style("✗").red());
+        println!(
+            "  {} Set {} or run 'committer auth login'",
+            style("→").dim(),
+            config.provider.api_key_env()
+        );
+        std::process::exit(1);
+    }
+
+    let Ok(head) = git(&["rev-parse", "--verify", "HEAD"]).await else {
+        println!("{} Nothing to split onto yet", style("✗").red());
+        println!(
+            "  {} Make the first commit with 'committer' or 'git commit'",
+            style("→").dim()
+        );
+        std::process::exit(1);
+    };
+
+    let verbose = args.verbose || config.verbose;
+    let api = ApiClient::new(config, api_key, verbose)?.with_ledger(Ledger::open("split").await);
+    let prompts = PromptTemplates::load().await;
+    let diff_filter = DiffFilter::load(&config.exclude).await;
+    let model = args.model.as_ref().unwrap_or(&config.model);
+
+    let staged = get_staged_entries().await?;
+    if staged.is_empty() {
+        println!("{} No staged changes", style("⚠").yellow());
+        println!(
+            "  {} Use 'git add' to stage changes to split",
+            style("→").dim()
+        );
+        std::process::exit(1);
+    }
+
+    let (diff_result, files_result) = tokio::join!(
+        get_git_diff(true, &diff_filter, config.diff_context, verbose),
+        get_staged_files(&diff_filter, verbose)
+    );
+    let diff = guard_diff(diff_result?, config);
+    let files = files_result?;
+    let diff = prepare_diff(
+        &api,
+        config,
+        model,
+        &diff,
+        &[&prompts.split, &files],
+        &prompts.summary,
+        verbose,
+    )
+    .await?;
+
+    let spinner = ProgressBar::new_spinner();
+    spinner.set_style(
+        ProgressStyle::default_spinner()
+            .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"])
+            .template("{spinner:.cyan} Planning commits...")
+            .unwrap(),
+    );
+    spinner.enable_steady_tick(std::time::Duration::from_millis(80));
+    let plan = plan_commits(&api, model, &prompts.split, &diff, &files).await;
+    spinner.finish_and_clear();
+
+    // Conventional-commit fixes only suit repositories that use them
+    let profile = load_style(config, verbose).await;
+    let (mut commits, mut unassigned) = normalize_plan(plan?, &staged);
+    if let Some(rules) = lint_rules_for(config, profile.as_ref()) {
+        for commit in &mut commits {
+            commit.message = fix(&commit.message, &rules);
+        }
+    }
+    if commits.is_empty() {
+        println!("{} The model proposed no usable commits", style("✗").red());
+        std::process::exit(1);
+    }
+    if verbose {
+        eprintln!(
+            "— Split plan: {} commits, {} files left staged",
+            commits.len(),
+            unassigned.len()
+        );
+    }
+
+    print_plan(&commits, &unassigned);
+    let show_usage = verbose || config.show_usage;
+
+    if args.dry_run {
+        finish_invocation(&api, show_usage);
+        return Ok(());
+    }
+
+    if !args.yes {
+        loop {
+            match prompt_split(commits.len()) {
+                SplitAction::Create => break,
+                SplitAction::Cancel => {
+                    println!("{} Cancelled, staged changes untouched", style("—").dim());
+                    finish_invocation(&api, show_usage);
+                    return Ok(());
+                }
+                SplitAction::Edit => {
+                    let text = plan_to_text(&commits, &unassigned);
+                    let edited = dialoguer::Editor::new()
+                        .extension(".txt")
+                        .edit(&text)
+                        .unwrap_or(None)
+                        .unwrap_or(text);
+                    match plan_from_text(&edited) {
+                        Ok(plan) => (commits, unassigned) = normalize_plan(plan, &staged),
+                        Err(e) => println!("{} {}, keeping the previous plan", style("✗").red(), e),
+                    }
+                    if commits.is_empty() {
+                        println!("{} Cancelled, no commits left", style("—").dim());
+                        finish_invocation(&api, show_usage);
+                        return Ok(());
+                    }
+                    print_plan(&commits, &unassigned);
+                }
+            }
+        }
+    }
+
+    let tree = git(&["write-tree"]).await?;
+    if let Err(e) = create_commits(&commits, &unassigned, &staged, &tree).await {
+        println!("{} Split failed: {}", style("✗").red(), e);
+        match restore(&head, &tree).await {
+            Ok(()) => println!(
+                "  {} Restored HEAD and the original staged changes",
+                style("→").dim()
+            ),
+            Err(restore_error) => {
+                println!(
+                    "  {} Could not restore the index: {}",
+                    style("→").dim(),
+                    restore_error
+                );
+                println!(
+                    "  {} Recover with: git reset --soft {} && git read-tree {}",
+                    style("→").dim(),
+                    head,
+                    tree
+                );
+            }
+        }
+        std::process::exit(1);
+    }
+
+    finish_invocation(&api, show_usage);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(message: &str, files: &[&str]) -> PlannedCommit {
+        PlannedCommit {
+            message: message.to_string(),
+            files: files.iter().map(|f| f.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn normalizes_plan_against_staged_files() {
+        let staged =
+            parse_name_status("M\0src/a.rs\0R100\0old.rs\0new.rs\0A\0README.md\0M\0Cargo.lock\0");
+
+        let (commits, unassigned) = normalize_plan(
+            vec![
+                commit("feat: a", &["src/a.rs", "old.rs", "missing.rs"]),
+                commit("docs: readme", &["README.md", "src/a.rs"]),
+                commit("chore: nothing", &["missing.rs"]),
+            ],
+            &staged,
+        );
+
+        assert_eq!(
+            commits,
+            vec![
+                commit("feat: a", &["src/a.rs", "new.rs"]),
+                commit("docs: readme", &["README.md"]),
+            ]
+        );
+        assert_eq!(unassigned, vec!["Cargo.lock"]);
+    }
+
+    #[test]
+    fn round_trips_edited_plans() {
+        let commits = vec![
+            commit("feat(api): add retry\n\n- Back off on 429", &["src/api.rs"]),
+            commit("docs: describe retries", &["README.md"]),
+        ];
+
+        let text = plan_to_text(&commits, &["Cargo.lock".to_string()]);
+        assert_eq!(plan_from_text(&text).unwrap(), commits);
+
+        // Reordered, with a file moved between commits
+        let edited = "=== commit ===\nfile: README.md\nfile: src/api.rs\n\ndocs: retries\n=== commit ===\n\nfeat: nothing\n";
+        assert_eq!(
+            plan_from_text(edited).unwrap(),
+            vec![
+                commit("docs: retries", &["README.md", "src/api.rs"]),
+                commit("feat: nothing", &[]),
+            ]
+        );
+        assert!(plan_from_text("=== commit ===\nfile: a.rs\n").is_err());
+    }
+}
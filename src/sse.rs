@@ -0,0 +1,203 @@
+//! Incremental decoding of streamed API responses.
+//!
+//! Network chunks don't line up with event boundaries: a single `data:` line
+//! (or even a multi-byte UTF-8 character) can arrive split across two chunks.
+//! [`EventDecoder`] buffers raw bytes and only emits complete events, for both
+//! server-sent events and newline-delimited JSON.
+//!
+//! # SSE Handling
+//!
+//! - Lines end with `\n` or `\r\n`
+//! - Lines starting with `:` are comments (e.g. `: OPENROUTER PROCESSING`)
+//! - Consecutive `data:` lines are joined with `\n` into one payload
+//! - A blank line dispatches the event along with its `event:` name, if any
+
+/// How a provider frames events in a streamed response body.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamFormat {
+    /// Server-sent events with JSON in `data:` lines.
+    Sse,
+    /// One JSON object per line (NDJSON).
+    JsonLines,
+}
+
+/// A complete event decoded from a stream.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SseEvent {
+    /// The `event:` field, if the server sent one.
+    pub event: Option<String>,
+    /// The event payload.
+    pub data: String,
+}
+
+/// Buffers streamed bytes and yields complete events.
+pub struct EventDecoder {
+    format: StreamFormat,
+    buffer: Vec<u8>,
+    event: Option<String>,
+    data: Option<String>,
+}
+
+impl EventDecoder {
+    /// Creates a decoder for the given framing.
+    pub fn new(format: StreamFormat) -> Self {
+        Self {
+            format,
+            buffer: Vec::new(),
+            event: None,
+            data: None,
+        }
+    }
+
+    /// Feeds a network chunk and returns every event it completes.
+    ///
+    /// Trailing partial lines are kept until a later chunk completes them.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut events = Vec::new();
+        while let Some(pos) = self.buffer.iter().position(|&b| b == b'\n') {
+            let mut line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            line.pop();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            if let Some(event) = self.process_line(&String::from_utf8_lossy(&line)) {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    /// Flushes any buffered line and pending event at end of stream.
+    pub fn finish(&mut self) -> Vec<SseEvent> {
+        let mut events = Vec::new();
+        if !self.buffer.is_empty() {
+            let line = String::from_utf8_lossy(&std::mem::take(&mut self.buffer)).to_string();
+            if let Some(event) = self.process_line(line.trim_end_matches('\r')) {
+                events.push(event);
+            }
+        }
+        if let Some(event) = self.dispatch() {
+            events.push(event);
+        }
+        events
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<SseEvent> {
+        match self.format {
+            StreamFormat::JsonLines => {
+                let line = line.trim();
+                (!line.is_empty()).then(|| SseEvent {
+                    event: None,
+                    data: line.to_string(),
+                })
+            }
+            StreamFormat::Sse => {
+                if line.is_empty() {
+                    return self.dispatch();
+                }
+                if line.starts_with(':') {
+                    return None;
+                }
+
+                let (field, value) = match line.split_once(':') {
+                    Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+                    None => (line, ""),
+                };
+                match field {
+                    "data" => match &mut self.data {
+                        Some(data) => {
+                            data.push('\n');
+                            data.push_str(value);
+                        }
+                        None => self.data = Some(value.to_string()),
+                    },
+                    "event" => self.event = Some(value.to_string()),
+                    // `id` and `retry` are irrelevant for one-shot completions
+                    _ => {}
+                }
+                None
+            }
+        }
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = self.event.take();
+        self.data.take().map(|data| SseEvent { event, data })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(payload: &str) -> SseEvent {
+        SseEvent {
+            event: None,
+            data: payload.to_string(),
+        }
+    }
+
+    #[test]
+    fn joins_lines_split_across_chunks() {
+        let mut decoder = EventDecoder::new(StreamFormat::Sse);
+
+        assert!(decoder.push(b"data: {\"a\":").is_empty());
+        assert!(decoder.push(b"1}\n").is_empty());
+        assert_eq!(
+            decoder.push(b"\ndata: [DONE]\n\n"),
+            vec![data("{\"a\":1}"), data("[DONE]")]
+        );
+    }
+
+    #[test]
+    fn keeps_multibyte_characters_split_across_chunks() {
+        let mut decoder = EventDecoder::new(StreamFormat::Sse);
+        let bytes = "data: héllo\n\n".as_bytes();
+        let split = bytes.iter().position(|&b| b == 0xc3).unwrap() + 1;
+
+        assert!(decoder.push(&bytes[..split]).is_empty());
+        assert_eq!(decoder.push(&bytes[split..]), vec![data("héllo")]);
+    }
+
+    #[test]
+    fn skips_comments_and_joins_multiline_data() {
+        let mut decoder = EventDecoder::new(StreamFormat::Sse);
+        let events =
+            decoder.push(b": OPENROUTER PROCESSING\r\n\r\ndata: first\r\ndata:second\r\n\r\n");
+
+        assert_eq!(events, vec![data("first\nsecond")]);
+    }
+
+    #[test]
+    fn reports_event_names() {
+        let mut decoder = EventDecoder::new(StreamFormat::Sse);
+        let events = decoder.push(b"event: error\ndata: {\"message\":\"overloaded\"}\n\n");
+
+        assert_eq!(
+            events,
+            vec![SseEvent {
+                event: Some("error".to_string()),
+                data: "{\"message\":\"overloaded\"}".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn flushes_unterminated_event_on_finish() {
+        let mut decoder = EventDecoder::new(StreamFormat::Sse);
+
+        assert!(decoder.push(b"data: tail").is_empty());
+        assert_eq!(decoder.finish(), vec![data("tail")]);
+    }
+
+    #[test]
+    fn decodes_json_lines() {
+        let mut decoder = EventDecoder::new(StreamFormat::JsonLines);
+
+        assert_eq!(decoder.push(b"{\"a\":1}\n{\"b\""), vec![data("{\"a\":1}")]);
+        assert_eq!(decoder.push(b":2}\n\n"), vec![data("{\"b\":2}")]);
+        assert!(decoder.finish().is_empty());
+    }
+}
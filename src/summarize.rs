@@ -0,0 +1,281 @@
+//! Map-reduce summarization of very large diffs.
+//!
+//! Diffs larger than `summarize_above_tokens` are split into parts, keeping
+//! files from the same directory together, and each part is summarized
+//! concurrently with the `summary` prompt. The commit message or PR
+//! description is then written from those summaries, so the model hears
+//! about every part of the change instead of only the hunks that fit.
+//!
+//! Without a threshold, or below it, the diff is fitted to the token budget
+//! as usual (see [`crate::budget`]).
+
+use futures::stream::{self, StreamExt, TryStreamExt};
+use indicatif::{ProgressBar, ProgressStyle};
+
+use crate::api::{send_chat, ApiClient, ChatRequest};
+use crate::budget::{estimate_tokens, fit_diff, resolve_diff_budget, split_files, CHARS_PER_TOKEN};
+use crate::config::Config;
+use crate::git::extract_filename_from_diff_header;
+use crate::prompt::render;
+
+/// Summary requests in flight at once.
+const MAX_CONCURRENT_SUMMARIES: usize = 4;
+
+/// Largest part sent for summarization, in estimated tokens.
+const MAX_PART_TOKENS: usize = 16_000;
+
+/// Smallest part size, so a tiny budget doesn't mean one request per file.
+const MIN_PART_TOKENS: usize = 1_000;
+
+/// Tokens set aside for the note on omitted summary lines.
+const OMISSION_NOTE_TOKENS: usize = 20;
+
+/// Files summarized together in one request.
+#[derive(Default)]
+struct Part<'a> {
+    dirs: Vec<&'a str>,
+    paths: Vec<&'a str>,
+    sections: Vec<&'a str>,
+}
+
+fn directory_of(path: &str) -> &str {
+    path.rsplit_once('/').map(|(dir, _)| dir).unwrap_or(".")
+}
+
+/// Splits a diff into parts of about `max_tokens`, keeping each directory in
+/// one part where it fits.
+fn split_parts(diff: &str, max_tokens: usize) -> Vec<Part<'_>> {
+    // git orders files by path, so a directory's files are adjacent
+    let mut groups: Vec<(&str, Vec<(&str, &str)>)> = Vec::new();
+    for section in split_files(diff) {
+        let path = extract_filename_from_diff_header(section).unwrap_or("(unknown)");
+        let dir = directory_of(path);
+        match groups.last_mut() {
+            Some((last, files)) if *last == dir => files.push((path, section)),
+            _ => groups.push((dir, vec![(path, section)])),
+        }
+    }
+
+    let mut parts = Vec::new();
+    let mut current = Part::default();
+    let mut current_tokens = 0;
+    for (dir, files) in groups {
+        let group_tokens: usize = files.iter().map(|(_, s)| estimate_tokens(s)).sum();
+        if current_tokens > 0
+            && current_tokens + group_tokens > max_tokens
+            && group_tokens <= max_tokens
+        {
+            parts.push(std::mem::take(&mut current));
+            current_tokens = 0;
+        }
+
+        for (path, section) in files {
+            let tokens = estimate_tokens(section);
+            if current_tokens > 0 && current_tokens + tokens > max_tokens {
+                parts.push(std::mem::take(&mut current));
+                current_tokens = 0;
+            }
+            if !current.dirs.contains(&dir) {
+                current.dirs.push(dir);
+            }
+            current.paths.push(path);
+            current.sections.push(section);
+            current_tokens += tokens;
+        }
+    }
+    if !current.paths.is_empty() {
+        parts.push(current);
+    }
+    parts
+}
+
+async fn summarize_part(
+    api: &ApiClient,
+    model: &str,
+    template: &str,
+    part: &Part<'_>,
+    max_tokens: usize,
+) -> Result<String, Box<dyn std::error::Error>> {
+    // A single file can still be larger than a part
+    let diff = fit_diff(&part.sections.concat(), max_tokens, false);
+    let files = part.paths.join("\n");
+    let prompt = render(template, &[("diff", &diff), ("files", &files)]);
+
+    let summary = send_chat(api, ChatRequest::user(model, prompt, false)).await?;
+    Ok(format!(
+        "### {} ({} files)\n{}\n",
+        part.dirs.join(", "),
+        part.paths.len(),
+        summary.trim()
+    ))
+}
+
+/// Summarizes `diff` part by part, concurrently, and joins the summaries.
+pub async fn summarize_diff(
+    api: &ApiClient,
+    model: &str,
+    template: &str,
+    diff: &str,
+    max_part_tokens: usize,
+    verbose: bool,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let parts = split_parts(diff, max_part_tokens);
+    if verbose {
+        eprintln!(
+            "— Summarizing ~{} tokens of diff in {} parts",
+            estimate_tokens(diff),
+            parts.len()
+        );
+    }
+
+    let spinner = ProgressBar::new_spinner();
+    spinner.set_style(
+        ProgressStyle::default_spinner()
+            .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"])
+            .template("{spinner:.cyan} {msg}")
+            .unwrap(),
+    );
+    spinner.set_message(format!(
+        "Summarizing {} parts of a large diff...",
+        parts.len()
+    ));
+    spinner.enable_steady_tick(std::time::Duration::from_millis(80));
+
+    let summaries: Result<Vec<String>, _> = stream::iter(
+        parts
+            .iter()
+            .map(|part| summarize_part(api, model, template, part, max_part_tokens)),
+    )
+    .buffered(MAX_CONCURRENT_SUMMARIES)
+    .try_collect()
+    .await;
+    spinner.finish_and_clear();
+
+    Ok(format!(
+        "The full diff is too large to include. Below are summaries of each part of the change.\n\n{}",
+        summaries?.join("\n")
+    ))
+}
+
+/// Cuts summaries down to `budget` tokens at a line boundary.
+///
+/// Summaries have no `diff --git` sections for [`fit_diff`] to budget by, so
+/// they are truncated as plain text.
+fn fit_summaries(summaries: &str, budget: usize, verbose: bool) -> String {
+    let total_tokens = estimate_tokens(summaries);
+    if total_tokens <= budget {
+        return summaries.to_string();
+    }
+
+    let max_len = budget.saturating_sub(OMISSION_NOTE_TOKENS) * CHARS_PER_TOKEN;
+    let mut result = String::new();
+    let mut omitted_lines = 0;
+    for line in summaries.split_inclusive('\n') {
+        if omitted_lines == 0 && result.len() + line.len() <= max_len {
+            result.push_str(line);
+        } else {
+            omitted_lines += 1;
+        }
+    }
+    result.push_str(&format!(
+        "\n[... {} more summary lines omitted to fit the context budget ...]\n",
+        omitted_lines
+    ));
+
+    if verbose {
+        eprintln!(
+            "— Summaries truncated: ~{} of ~{} tokens kept ({} lines omitted)",
+            estimate_tokens(&result),
+            total_tokens,
+            omitted_lines
+        );
+    }
+    result
+}
+
+/// Shapes a filtered diff for a prompt whose other text is `prompt`: its
+/// template and the other values rendered into it.
+///
+/// Diffs above `summarize_above_tokens` are replaced by per-part summaries;
+/// either way the result is fitted to the model's diff budget.
+pub async fn prepare_diff(
+    api: &ApiClient,
+    config: &Config,
+    model: &str,
+    diff: &str,
+    prompt: &[&str],
+    summary_template: &str,
+    verbose: bool,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let budget = resolve_diff_budget(api, config, model, prompt, verbose).await;
+
+    match config.summarize_above_tokens {
+        Some(threshold) if estimate_tokens(diff) > threshold as usize => {
+            let part_tokens = budget.clamp(MIN_PART_TOKENS, MAX_PART_TOKENS);
+            let summaries =
+                summarize_diff(api, model, summary_template, diff, part_tokens, verbose).await?;
+            Ok(fit_summaries(&summaries, budget, verbose))
+        }
+        _ => Ok(fit_diff(diff, budget, verbose)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_diff(path: &str, lines: usize) -> String {
+        let mut diff = format!(
+            "diff --git a/{0} b/{0}\n--- a/{0}\n+++ b/{0}\n@@ -1 +1 @@\n",
+            path
+        );
+        diff.push_str(&"+added line of code\n".repeat(lines));
+        diff
+    }
+
+    #[test]
+    fn groups_parts_by_directory() {
+        let diff = [
+            file_diff("src/api/a.rs", 40),
+            file_diff("src/api/b.rs", 40),
+            file_diff("src/ui/c.rs", 40),
+            file_diff("src/ui/d.rs", 40),
+            file_diff("README.md", 5),
+        ]
+        .concat();
+
+        let parts = split_parts(&diff, 500);
+
+        let dirs: Vec<Vec<&str>> = parts.iter().map(|part| part.dirs.clone()).collect();
+        assert_eq!(dirs, vec![vec!["src/api"], vec!["src/ui", "."]]);
+        assert_eq!(
+            parts[1].paths,
+            vec!["src/ui/c.rs", "src/ui/d.rs", "README.md"]
+        );
+    }
+
+    #[test]
+    fn splits_directories_larger_than_a_part() {
+        let diff: String = (0..6)
+            .map(|i| file_diff(&format!("gen/f{}.rs", i), 40))
+            .collect();
+
+        let parts = split_parts(&diff, 500);
+
+        assert_eq!(parts.len(), 3);
+        assert!(parts.iter().all(|part| part.dirs == vec!["gen"]));
+    }
+
+    #[test]
+    fn truncates_summaries_as_text() {
+        let summaries: String = (0..200)
+            .map(|i| format!("- Summary line {}\n", i))
+            .collect();
+        assert_eq!(fit_summaries(&summaries, 10_000, false), summaries);
+
+        let fitted = fit_summaries(&summaries, 200, false);
+        assert!(estimate_tokens(&fitted) <= 200);
+        assert!(fitted.starts_with("- Summary line 0\n"));
+        assert!(fitted.ends_with("more summary lines omitted to fit the context budget ...]\n"));
+    }
+}
@@ -6,6 +6,7 @@
 //! - Single-key responses (y/n/e)
 //! - Full word responses (yes/no/edit)
 //! - Editor integration for message editing
+//! - Regeneration and natural-language feedback (r/f)
 //!
 //! # Prompts
 //!
@@ -13,12 +14,16 @@
 //! - [`prompt_pr`]: Confirm or edit PR title/body
 //! - [`prompt_branch_action`]: Create or skip branch creation
 //! - [`prompt_uncommitted_changes`]: Handle uncommitted changes before PR
+//! - [`prompt_split`]: Create, edit, or cancel a plan of split commits
+//! - [`prompt_lint_action`]: Fix, regenerate, or keep a message with lint problems
+//! - [`prompt_candidate`]: Pick one of several candidate messages
 
 use console::style;
 use dialoguer::Input;
 use std::io::{self, Write};
 
 use crate::branch::BranchAction;
+use crate::candidates::Candidate;
 use crate::git::UncommittedChanges;
 
 /// User's choice when uncommitted changes are detected.
@@ -156,11 +161,28 @@ pub enum CommitAction {
     Cancel,
     /// Create a new branch first, then prompt again.
     CreateBranch(String),
+    /// Generate a new message.
+    Regenerate,
+    /// Revise the current message with the user's feedback: (message, instruction).
+    Feedback(String, String),
 }
 
-/// Prompts user to confirm, edit, or cancel a commit.
+/// Asks what should change about a generated message or PR.
 ///
-/// Options: `y` (commit), `n` (cancel), `e` (edit in $EDITOR), `b` (create branch first).
+/// Returns `None` when the user enters nothing.
+fn read_feedback() -> Option<String> {
+    let instruction: String = Input::new()
+        .with_prompt("What should change?")
+        .allow_empty(true)
+        .interact_text()
+        .unwrap_or_default();
+    Some(instruction.trim().to_string()).filter(|instruction| !instruction.is_empty())
+}
+
+/// Prompts user to confirm, edit, refine, or cancel a commit.
+///
+/// Options: `y` (commit), `n` (cancel), `e` (edit in $EDITOR), `r` (regenerate),
+/// `f` (feedback), `b` (create branch first).
 pub fn prompt_commit(message: &str, show_branch_option: bool) -> CommitAction {
     let mut current_message = message.to_string();
 
@@ -169,6 +191,8 @@ pub fn prompt_commit(message: &str, show_branch_option: bool) -> CommitAction {
         println!("  {} Commit", style("[y]").cyan().bold());
         println!("  {} Cancel", style("[n]").cyan().bold());
         println!("  {} Edit in $EDITOR", style("[e]").cyan().bold());
+        println!("  {} Regenerate", style("[r]").cyan().bold());
+        println!("  {} Give feedback", style("[f]").cyan().bold());
         if show_branch {
             println!("  {} Create branch first", style("[b]").cyan().bold());
         }
@@ -176,9 +200,9 @@ pub fn prompt_commit(message: &str, show_branch_option: bool) -> CommitAction {
     };
 
     let invalid_msg = if show_branch_option {
-        "Please enter y, n, e, or b"
+        "Please enter y, n, e, r, f, or b"
     } else {
-        "Please enter y, n, or e"
+        "Please enter y, n, e, r, or f"
     };
 
     print_menu(show_branch_option);
@@ -204,6 +228,13 @@ pub fn prompt_commit(message: &str, show_branch_option: bool) -> CommitAction {
                 println!("{}", current_message);
                 print_menu(show_branch_option);
             }
+            "r" | "regenerate" => return CommitAction::Regenerate,
+            "f" | "feedback" => {
+                if let Some(instruction) = read_feedback() {
+                    return CommitAction::Feedback(current_message, instruction);
+                }
+                print_menu(show_branch_option);
+            }
             "b" | "branch" if show_branch_option => {
                 return CommitAction::CreateBranch(current_message)
             }
@@ -218,11 +249,16 @@ pub enum PrAction {
     Create(String, String),
     /// Cancel PR creation.
     Cancel,
+    /// Generate new content.
+    Regenerate,
+    /// Revise the current content with the user's feedback: (title, body, instruction).
+    Feedback(String, String, String),
 }
 
-/// Prompts user to confirm, edit, or cancel PR creation.
+/// Prompts user to confirm, edit, refine, or cancel PR creation.
 ///
-/// Options: `y` (create), `n` (cancel), `e` (edit in $EDITOR).
+/// Options: `y` (create), `n` (cancel), `e` (edit in $EDITOR), `r` (regenerate),
+/// `f` (feedback).
 pub fn prompt_pr(title: &str, body: &str) -> PrAction {
     let mut current_title = title.to_string();
     let mut current_body = body.to_string();
@@ -232,6 +268,8 @@ pub fn prompt_pr(title: &str, body: &str) -> PrAction {
         println!("  {} Create PR", style("[y]").cyan().bold());
         println!("  {} Cancel", style("[n]").cyan().bold());
         println!("  {} Edit in $EDITOR", style("[e]").cyan().bold());
+        println!("  {} Regenerate", style("[r]").cyan().bold());
+        println!("  {} Give feedback", style("[f]").cyan().bold());
         println!();
     };
 
@@ -268,7 +306,125 @@ pub fn prompt_pr(title: &str, body: &str) -> PrAction {
                 println!("{}", current_body);
                 print_menu();
             }
+            "r" | "regenerate" => return PrAction::Regenerate,
+            "f" | "feedback" => {
+                if let Some(instruction) = read_feedback() {
+                    return PrAction::Feedback(current_title, current_body, instruction);
+                }
+                print_menu();
+            }
+            _ => println!("  {} Please enter y, n, e, r, or f", style("→").dim()),
+        }
+    }
+}
+
+/// User's choice after reviewing a split plan.
+pub enum SplitAction {
+    /// Create the planned commits.
+    Create,
+    /// Edit the plan in $EDITOR.
+    Edit,
+    /// Cancel without committing.
+    Cancel,
+}
+
+/// Prompts user to create, edit, or cancel the planned commits.
+///
+/// Options: `y` (create), `n` (cancel), `e` (edit in $EDITOR).
+pub fn prompt_split(commit_count: usize) -> SplitAction {
+    println!();
+    println!(
+        "  {} Create {} commits",
+        style("[y]").cyan().bold(),
+        commit_count
+    );
+    println!("  {} Cancel", style("[n]").cyan().bold());
+    println!(
+        "  {} Edit, reorder, or regroup in $EDITOR",
+        style("[e]").cyan().bold()
+    );
+    println!();
+
+    loop {
+        print!("{} ", style("Choice:").bold());
+        io::stdout().flush().unwrap();
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input).unwrap();
+
+        match input.trim().to_lowercase().as_str() {
+            "y" | "yes" => return SplitAction::Create,
+            "n" | "no" => return SplitAction::Cancel,
+            "e" | "edit" => return SplitAction::Edit,
             _ => println!("  {} Please enter y, n, or e", style("→").dim()),
         }
     }
 }
+
+/// User's choice when a commit message has lint problems.
+pub enum LintAction {
+    /// Apply the automatic fixes.
+    Fix,
+    /// Generate a new message.
+    Regenerate,
+    /// Use the message as it is.
+    Keep,
+}
+
+/// Prompts user to fix, regenerate, or keep a message that fails linting.
+///
+/// Options: `f` (fix, when any problem is fixable), `r` (regenerate, when
+/// available), `k` (keep).
+pub fn prompt_lint_action(can_fix: bool, can_regenerate: bool) -> LintAction {
+    println!();
+    if can_fix {
+        println!("  {} Fix automatically", style("[f]").cyan().bold());
+    }
+    if can_regenerate {
+        println!("  {} Regenerate", style("[r]").cyan().bold());
+    }
+    println!("  {} Keep as is", style("[k]").cyan().bold());
+    println!();
+
+    let choices: Vec<&str> = [(can_fix, "f"), (can_regenerate, "r"), (true, "k")]
+        .into_iter()
+        .filter_map(|(shown, key)| shown.then_some(key))
+        .collect();
+    let invalid_msg = format!("Please enter {}", choices.join(", "));
+
+    loop {
+        print!("{} ", style("Choice:").bold());
+        io::stdout().flush().unwrap();
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input).unwrap();
+
+        match input.trim().to_lowercase().as_str() {
+            "f" | "fix" if can_fix => return LintAction::Fix,
+            "r" | "regenerate" if can_regenerate => return LintAction::Regenerate,
+            "k" | "keep" => return LintAction::Keep,
+            _ => println!("  {} {}", style("→").dim(), invalid_msg),
+        }
+    }
+}
+
+/// Asks which of the printed candidates to use.
+///
+/// Returns the chosen index, or `None` if the user cancelled with Esc or `q`.
+pub fn prompt_candidate(candidates: &[Candidate]) -> Option<usize> {
+    let items: Vec<String> = candidates
+        .iter()
+        .enumerate()
+        .map(|(i, candidate)| {
+            let subject = candidate.message.lines().next().unwrap_or_default();
+            format!("{}. {}", i + 1, subject)
+        })
+        .collect();
+
+    dialoguer::Select::new()
+        .with_prompt("Use which message?")
+        .items(&items)
+        .default(0)
+        .interact_opt()
+        .unwrap_or(None)
+}
@@ -0,0 +1,452 @@
+//! Token usage and cost tracking.
+//!
+//! Every API response's token counts (and cost, where the provider reports
+//! one) are collected by [`ApiClient`](crate::api::ApiClient) and appended to
+//! a local ledger at `~/.local/share/committer/usage.jsonl` as each request
+//! completes, so nothing is lost when a command exits early.
+//! `committer usage` aggregates the ledger by day, model, or repo.
+
+use console::style;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::api::ApiClient;
+use crate::cli::{UsageArgs, UsageGroup};
+use crate::git::get_repo_root;
+
+/// Token counts and cost reported for a single API request.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Cost in USD, when the provider reports it (OpenRouter does).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost: Option<f64>,
+}
+
+impl Usage {
+    /// Folds in counts from a later event of the same response.
+    ///
+    /// Providers report usage incrementally (Anthropic sends input tokens at
+    /// the start and output tokens at the end), so non-zero values win.
+    pub fn merge(&mut self, other: Usage) {
+        if other.prompt_tokens > 0 {
+            self.prompt_tokens = other.prompt_tokens;
+        }
+        if other.completion_tokens > 0 {
+            self.completion_tokens = other.completion_tokens;
+        }
+        if other.cost.is_some() {
+            self.cost = other.cost;
+        }
+    }
+
+    fn add(&mut self, other: &Usage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.cost = match (self.cost, other.cost) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+        };
+    }
+
+    fn is_empty(&self) -> bool {
+        self.prompt_tokens == 0 && self.completion_tokens == 0 && self.cost.is_none()
+    }
+}
+
+/// Usage for one request made during this invocation.
+pub struct RequestUsage {
+    pub provider: String,
+    pub model: String,
+    pub usage: Usage,
+}
+
+/// A single line of the usage ledger.
+#[derive(Debug, Serialize, Deserialize)]
+struct LedgerEntry {
+    /// Seconds since the Unix epoch.
+    timestamp: u64,
+    command: String,
+    /// Root directory of the repository, empty outside one. Older entries
+    /// hold only its name.
+    repo: String,
+    provider: String,
+    model: String,
+    #[serde(flatten)]
+    usage: Usage,
+}
+
+/// Returns the path to the usage ledger.
+///
+/// Typically `~/.local/share/committer/usage.jsonl` on Linux.
+pub fn ledger_path() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("committer")
+        .join("usage.jsonl")
+}
+
+/// Where a command's requests are recorded.
+pub struct Ledger {
+    command: String,
+    repo: String,
+}
+
+impl Ledger {
+    /// Records requests made by `command` in the current repository.
+    pub async fn open(command: &str) -> Self {
+        let repo = get_repo_root()
+            .await
+            .ok()
+            .map(|root| root.to_string_lossy().to_string())
+            .unwrap_or_default();
+        Self {
+            command: command.to_string(),
+            repo,
+        }
+    }
+
+    /// Appends a completed request to the ledger.
+    ///
+    /// Errors are reported but never fail the request.
+    pub fn record(&self, record: &RequestUsage) {
+        if let Err(e) = self.append(record) {
+            eprintln!(
+                "{} Could not record usage in {}: {}",
+                style("⚠").yellow(),
+                ledger_path().display(),
+                e
+            );
+        }
+    }
+
+    fn append(&self, record: &RequestUsage) -> Result<(), Box<dyn std::error::Error>> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let entry = LedgerEntry {
+            timestamp,
+            command: self.command.clone(),
+            repo: self.repo.clone(),
+            provider: record.provider.clone(),
+            model: record.model.clone(),
+            usage: record.usage,
+        };
+
+        let path = ledger_path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        Ok(())
+    }
+}
+
+/// Prints a summary line of the usage collected by `api` for this
+/// invocation, if `show` is set.
+pub fn finish_invocation(api: &ApiClient, show: bool) {
+    let records = api.take_usage();
+    if show {
+        print_summary(&records);
+    }
+}
+
+/// The models used, each once, in order of first use.
+fn distinct_models(records: &[RequestUsage]) -> Vec<&str> {
+    let mut models: Vec<&str> = Vec::new();
+    for record in records {
+        if !models.contains(&record.model.as_str()) {
+            models.push(&record.model);
+        }
+    }
+    models
+}
+
+/// Prints a one-line summary of this invocation's usage.
+fn print_summary(records: &[RequestUsage]) {
+    let mut total = Usage::default();
+    for record in records {
+        total.add(&record.usage);
+    }
+    if total.is_empty() {
+        return;
+    }
+
+    let models = distinct_models(records);
+
+    println!(
+        "{} {} tokens ({} in, {} out){} · {}",
+        style("Σ").dim(),
+        total.prompt_tokens + total.completion_tokens,
+        total.prompt_tokens,
+        total.completion_tokens,
+        total
+            .cost
+            .map(|cost| format!(" · ${:.4}", cost))
+            .unwrap_or_default(),
+        style(models.join(", ")).dim()
+    );
+}
+
+/// Converts a Unix timestamp to a `YYYY-MM-DD` date in UTC.
+fn utc_date(timestamp: u64) -> String {
+    // Howard Hinnant's days-to-civil algorithm
+    let days = (timestamp / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Totals per group key: (requests, usage).
+fn aggregate(
+    entries: &[LedgerEntry],
+    group: UsageGroup,
+    since: Option<u64>,
+) -> BTreeMap<String, (usize, Usage)> {
+    let mut totals: BTreeMap<String, (usize, Usage)> = BTreeMap::new();
+    for entry in entries {
+        if since.is_some_and(|since| entry.timestamp < since) {
+            continue;
+        }
+        let key = match group {
+            UsageGroup::Day => utc_date(entry.timestamp),
+            UsageGroup::Model => entry.model.clone(),
+            UsageGroup::Repo if entry.repo.is_empty() => "(unknown)".to_string(),
+            UsageGroup::Repo => entry.repo.clone(),
+        };
+        let total = totals.entry(key).or_default();
+        total.0 += 1;
+        total.1.add(&entry.usage);
+    }
+    totals
+}
+
+/// Labels for repository keys: the directory name, or the whole path where
+/// two repositories share a name.
+fn repo_labels<'a>(keys: impl Iterator<Item = &'a String> + Clone) -> Vec<String> {
+    let name = |key: &'a str| {
+        Path::new(key)
+            .file_name()
+            .map_or(key.to_string(), |name| name.to_string_lossy().to_string())
+    };
+    keys.clone()
+        .map(|key| {
+            let label = name(key);
+            let shared = keys
+                .clone()
+                .any(|other| other != key && name(other) == label);
+            if shared {
+                key.clone()
+            } else {
+                label
+            }
+        })
+        .collect()
+}
+
+/// Main handler for the `committer usage` subcommand.
+pub fn handle_usage_command(args: UsageArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let path = ledger_path();
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let entries: Vec<LedgerEntry> = contents
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+
+    let since = args.days.map(|days| {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        // Include all of today plus the previous `days - 1` days
+        (now - now % 86_400).saturating_sub(u64::from(days.saturating_sub(1)) * 86_400)
+    });
+    let totals = aggregate(&entries, args.by, since);
+
+    if totals.is_empty() {
+        println!("{} No usage recorded yet", style("—").dim());
+        println!("  {} {}", style("ledger:").dim(), path.display());
+        return Ok(());
+    }
+
+    let mut rows: Vec<(String, &(usize, Usage))> = match args.by {
+        UsageGroup::Repo => repo_labels(totals.keys())
+            .into_iter()
+            .zip(totals.values())
+            .collect(),
+        UsageGroup::Day | UsageGroup::Model => totals
+            .iter()
+            .map(|(key, total)| (key.clone(), total))
+            .collect(),
+    };
+    rows.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let key_width = rows.iter().map(|(k, _)| k.len()).max().unwrap_or(0).max(5);
+    println!(
+        "{}",
+        style(format!(
+            "{:<key_width$}  {:>8}  {:>12}  {:>12}  {:>10}",
+            match args.by {
+                UsageGroup::Day => "day",
+                UsageGroup::Model => "model",
+                UsageGroup::Repo => "repo",
+            },
+            "requests",
+            "prompt",
+            "completion",
+            "cost"
+        ))
+        .bold()
+    );
+
+    let mut grand_total = Usage::default();
+    let mut grand_requests = 0;
+    for (key, (requests, usage)) in rows {
+        grand_total.add(usage);
+        grand_requests += requests;
+        println!(
+            "{:<key_width$}  {:>8}  {:>12}  {:>12}  {:>10}",
+            key,
+            requests,
+            usage.prompt_tokens,
+            usage.completion_tokens,
+            format_cost(usage.cost)
+        );
+    }
+
+    println!(
+        "{}",
+        style(format!(
+            "{:<key_width$}  {:>8}  {:>12}  {:>12}  {:>10}",
+            "total",
+            grand_requests,
+            grand_total.prompt_tokens,
+            grand_total.completion_tokens,
+            format_cost(grand_total.cost)
+        ))
+        .bold()
+    );
+
+    Ok(())
+}
+
+fn format_cost(cost: Option<f64>) -> String {
+    cost.map(|c| format!("${:.4}", c))
+        .unwrap_or_else(|| "—".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(timestamp: u64, model: &str, prompt: u64, cost: Option<f64>) -> LedgerEntry {
+        LedgerEntry {
+            timestamp,
+            command: "commit".to_string(),
+            repo: "committer".to_string(),
+            provider: "openrouter".to_string(),
+            model: model.to_string(),
+            usage: Usage {
+                prompt_tokens: prompt,
+                completion_tokens: 10,
+                cost,
+            },
+        }
+    }
+
+    #[test]
+    fn formats_utc_dates() {
+        assert_eq!(utc_date(0), "1970-01-01");
+        assert_eq!(utc_date(951_782_400), "2000-02-29");
+        assert_eq!(utc_date(1_767_225_599), "2025-12-31");
+    }
+
+    #[test]
+    fn merges_incremental_usage() {
+        let mut usage = Usage {
+            prompt_tokens: 120,
+            ..Default::default()
+        };
+        usage.merge(Usage {
+            completion_tokens: 30,
+            ..Default::default()
+        });
+
+        assert_eq!(usage.prompt_tokens, 120);
+        assert_eq!(usage.completion_tokens, 30);
+    }
+
+    #[test]
+    fn lists_each_model_once() {
+        let records: Vec<RequestUsage> = ["a", "b", "a"]
+            .iter()
+            .map(|model| RequestUsage {
+                provider: "openrouter".to_string(),
+                model: model.to_string(),
+                usage: Usage::default(),
+            })
+            .collect();
+
+        assert_eq!(distinct_models(&records), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn labels_repos_by_name_unless_shared() {
+        let keys = [
+            "/home/a/committer",
+            "/home/a/site",
+            "/work/site",
+            "legacy",
+            "(unknown)",
+        ]
+        .map(String::from);
+
+        assert_eq!(
+            repo_labels(keys.iter()),
+            [
+                "committer",
+                "/home/a/site",
+                "/work/site",
+                "legacy",
+                "(unknown)"
+            ]
+        );
+    }
+
+    #[test]
+    fn aggregates_by_model_since_cutoff() {
+        let entries = vec![
+            entry(100, "a", 5, None),
+            entry(200_000, "a", 7, Some(0.5)),
+            entry(200_000, "b", 9, Some(0.25)),
+            entry(300_000, "a", 11, None),
+        ];
+
+        let totals = aggregate(&entries, UsageGroup::Model, Some(150_000));
+
+        assert_eq!(totals["a"].0, 2);
+        assert_eq!(totals["a"].1.prompt_tokens, 18);
+        assert_eq!(totals["a"].1.cost, Some(0.5));
+        assert_eq!(totals["b"].1.cost, Some(0.25));
+    }
+}
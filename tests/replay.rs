@@ -0,0 +1,307 @@
+//! End-to-end tests of the commit, branch, and PR flows against replayed
+//! fixtures.
+
+#[path = "../src/test_support.rs"]
+mod test_support;
+
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use test_support::TempDir;
+
+fn fixtures(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(name)
+}
+
+/// A command that doesn't inherit the developer's `COMMITTER_*` settings.
+fn isolated(program: impl AsRef<OsStr>) -> Command {
+    let mut command = Command::new(program);
+    for (key, _) in std::env::vars_os() {
+        if key.to_string_lossy().starts_with("COMMITTER_") {
+            command.env_remove(key);
+        }
+    }
+    command
+}
+
+fn git(repo: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(repo)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "git {:?} failed", args);
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+/// A scratch repository, removed along with its config when dropped.
+struct TempRepo {
+    _dir: TempDir,
+    path: PathBuf,
+}
+
+impl std::ops::Deref for TempRepo {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Creates a repository with one commit on `main` and one staged file.
+fn temp_repo(name: &str) -> TempRepo {
+    let dir = TempDir::new(name);
+    let repo = dir.join("repo");
+    std::fs::create_dir_all(&repo).unwrap();
+
+    git(&repo, &["init", "-q", "-b", "main"]);
+    git(&repo, &["config", "user.name", "Test"]);
+    git(&repo, &["config", "user.email", "test@example.com"]);
+    std::fs::write(repo.join("README.md"), "# demo\n").unwrap();
+    git(&repo, &["add", "."]);
+    git(&repo, &["commit", "-q", "-m", "chore: initial commit"]);
+
+    std::fs::write(repo.join("hello.sh"), "echo hello\n").unwrap();
+    git(&repo, &["add", "hello.sh"]);
+    TempRepo {
+        _dir: dir,
+        path: repo,
+    }
+}
+
+/// A `committer` command run in `repo` with responses from `fixtures_dir`.
+fn committer(repo: &Path, fixtures_dir: &Path) -> Command {
+    let config = repo.parent().unwrap().join("config.toml");
+    std::fs::write(
+        &config,
+        format!(
+            "provider = \"replay\"\nfixtures_dir = {:?}\n",
+            fixtures_dir.display().to_string()
+        ),
+    )
+    .unwrap();
+
+    let mut command = isolated(env!("CARGO_BIN_EXE_committer"));
+    command.current_dir(repo).env("COMMITTER_CONFIG", &config);
+    command
+}
+
+fn run_committer(repo: &Path, fixtures_dir: &Path, args: &[&str]) -> Output {
+    committer(repo, fixtures_dir).args(args).output().unwrap()
+}
+
+/// `PATH` with `dir` searched first.
+fn path_with(dir: &Path) -> String {
+    format!(
+        "{}:{}",
+        dir.display(),
+        std::env::var("PATH").unwrap_or_default()
+    )
+}
+
+#[test]
+fn commits_replayed_message() {
+    let repo = temp_repo("commit");
+
+    let output = run_committer(&repo, &fixtures("commit"), &["-y"]);
+
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        git(&repo, &["log", "-1", "--format=%B"]),
+        "feat(greeting): add hello script\n\n- Print a greeting on startup"
+    );
+}
+
+#[test]
+fn dry_run_leaves_index_untouched() {
+    let repo = temp_repo("dry-run");
+
+    let output = run_committer(&repo, &fixtures("commit"), &["-d"]);
+
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("feat(greeting): add hello script"));
+    assert_eq!(git(&repo, &["rev-list", "--count", "HEAD"]), "1");
+    assert_eq!(git(&repo, &["diff", "--staged", "--name-only"]), "hello.sh");
+}
+
+#[test]
+fn candidates_are_listed_and_first_is_used_unattended() {
+    let repo = temp_repo("candidates");
+
+    let output = run_committer(&repo, &fixtures("candidates"), &["-y", "--candidates", "2"]);
+
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("feat(greeting): add hello script"));
+    assert!(stdout.contains("chore: add greeting script"));
+    assert_eq!(
+        git(&repo, &["log", "-1", "--format=%B"]),
+        "feat(greeting): add hello script\n\n- Print a greeting on startup"
+    );
+}
+
+#[test]
+fn auto_branch_moves_commit_off_protected_branch() {
+    let repo = temp_repo("branch");
+
+    let output = run_committer(&repo, &fixtures("branch"), &["-B", "-y"]);
+
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        git(&repo, &["rev-parse", "--abbrev-ref", "HEAD"]),
+        "feat/greeting-hello"
+    );
+    assert_eq!(
+        git(&repo, &["log", "-1", "--format=%s"]),
+        "feat(greeting): add hello script"
+    );
+    assert_eq!(git(&repo, &["rev-list", "--count", "main"]), "1");
+}
+
+#[test]
+fn unparseable_analysis_falls_back_to_offline_branch_name() {
+    let repo = temp_repo("branch-fallback");
+
+    let output = run_committer(&repo, &fixtures("branch-fallback"), &["-B", "-y"]);
+
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        git(&repo, &["rev-parse", "--abbrev-ref", "HEAD"]),
+        "feat/greeting-hello-script"
+    );
+}
+
+#[test]
+fn hook_fills_in_plain_git_commit() {
+    let repo = temp_repo("hook");
+    let install = run_committer(&repo, &fixtures("hook"), &["hook", "install"]);
+    assert!(install.status.success(), "{:?}", install);
+
+    // The hook calls `committer` from PATH
+    let path = path_with(Path::new(env!("CARGO_BIN_EXE_committer")).parent().unwrap());
+    let git_commit = |args: &[&str]| {
+        let output = isolated("git")
+            .arg("commit")
+            .args(args)
+            .current_dir(&*repo)
+            .env("PATH", &path)
+            .env("GIT_EDITOR", "true")
+            .env(
+                "COMMITTER_CONFIG",
+                repo.parent().unwrap().join("config.toml"),
+            )
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+    };
+
+    git_commit(&[]);
+    assert_eq!(
+        git(&repo, &["log", "-1", "--format=%B"]),
+        "feat(greeting): add hello script\n\n- Print a greeting on startup"
+    );
+
+    // -m messages are kept; the single fixture is already used up
+    std::fs::write(repo.join("bye.sh"), "echo bye\n").unwrap();
+    git(&repo, &["add", "bye.sh"]);
+    git_commit(&["-m", "feat: add bye script"]);
+    assert_eq!(
+        git(&repo, &["log", "-1", "--format=%B"]),
+        "feat: add bye script"
+    );
+}
+
+#[test]
+fn split_commits_each_group_separately() {
+    let repo = temp_repo("split");
+    std::fs::write(repo.join("README.md"), "# demo\n\nRun hello.sh\n").unwrap();
+    git(&repo, &["add", "README.md"]);
+
+    let output = run_committer(&repo, &fixtures("split"), &["split", "-y"]);
+
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        git(&repo, &["log", "-2", "--format=%s"]),
+        "feat(greeting): add hello script\ndocs: describe the greeting"
+    );
+    assert_eq!(
+        git(&repo, &["show", "--name-only", "--format=", "HEAD~1"]),
+        "README.md"
+    );
+    assert_eq!(git(&repo, &["diff", "--staged", "--name-only"]), "");
+}
+
+#[cfg(unix)]
+#[test]
+fn pr_pushes_branch_and_creates_pr() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let repo = temp_repo("pr");
+    let dir = repo.parent().unwrap();
+    let remote = dir.join("remote.git");
+    git(
+        &repo,
+        &["init", "-q", "--bare", &remote.display().to_string()],
+    );
+    git(
+        &repo,
+        &["remote", "add", "origin", &remote.display().to_string()],
+    );
+    git(&repo, &["push", "-q", "origin", "main"]);
+    git(&repo, &["checkout", "-q", "-b", "feat/greeting"]);
+    git(
+        &repo,
+        &["commit", "-q", "-m", "feat(greeting): add hello script"],
+    );
+
+    // A stand-in for the GitHub CLI that records how it was called
+    let bin = dir.join("bin");
+    std::fs::create_dir_all(&bin).unwrap();
+    let gh = bin.join("gh");
+    std::fs::write(
+        &gh,
+        "#!/bin/sh\ncase \"$1\" in\n--version) ;;\npr) printf '%s\\n' \"$@\" > \"$0.args\"; echo https://example.com/pull/1 ;;\n*) exit 1 ;;\nesac\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&gh, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let output = committer(&repo, &fixtures("pr"))
+        .args(["pr", "-y", "--base", "main"])
+        .env("PATH", path_with(&bin))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("https://example.com/pull/1"));
+    assert_eq!(
+        git(&repo, &["rev-parse", "origin/feat/greeting"]),
+        git(&repo, &["rev-parse", "HEAD"])
+    );
+    assert_eq!(
+        std::fs::read_to_string(bin.join("gh.args")).unwrap(),
+        "pr\ncreate\n--title\nAdd hello script\n--body\n## Summary\n- Print a greeting on startup\n"
+    );
+}
+
+#[test]
+fn lint_command_follows_lint_setting() {
+    let repo = temp_repo("lint");
+    let message = repo.parent().unwrap().join("COMMIT_EDITMSG");
+    std::fs::write(&message, "Added some stuff.\n").unwrap();
+    let file = message.display().to_string();
+
+    let output = run_committer(&repo, &fixtures("commit"), &["lint", &file]);
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+
+    let mut command = committer(&repo, &fixtures("commit"));
+    let config = repo.parent().unwrap().join("config.toml");
+    let mut contents = std::fs::read_to_string(&config).unwrap();
+    contents.push_str("lint = false\n");
+    std::fs::write(&config, contents).unwrap();
+    let output = command.args(["lint", &file]).output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+}